@@ -0,0 +1,977 @@
+use std::{
+    cmp::Reverse,
+    marker::PhantomData,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    board::BoardPosition,
+    book::OpeningBook,
+    editor::Editor,
+    game::{
+        AiPaused, ClaimDrawEvent, ClaimableDraw, DrawOffer, GameRng, GameState, InvalidBoard, Move,
+        NewGameEvent, OfferDrawEvent, PlayerConfig, PlayerKind, RequestMoveEvent, TurnData,
+        TurnManager, UndoEvent,
+    },
+    pieces::{Piece, PieceColor, PieceKind},
+    review::ReviewState,
+};
+
+/// How much each piece is worth, in centipawns.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PieceValues {
+    pub pawn: i32,
+    pub knight: i32,
+    pub bishop: i32,
+    pub rook: i32,
+    pub queen: i32,
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        Self {
+            pawn: 100,
+            knight: 300,
+            bishop: 300,
+            rook: 500,
+            queen: 900,
+        }
+    }
+}
+
+impl PieceValues {
+    pub fn value(&self, kind: PieceKind) -> i32 {
+        match kind {
+            PieceKind::Pawn(_) => self.pawn,
+            PieceKind::Knight => self.knight,
+            PieceKind::Bishop => self.bishop,
+            PieceKind::Rook => self.rook,
+            PieceKind::Queen => self.queen,
+            PieceKind::King => 0, // Never actually captured, so it doesn't count towards material
+        }
+    }
+}
+
+/// White's material minus Black's, in centipawns.
+pub fn material_balance(game_state: &GameState, values: &PieceValues) -> i32 {
+    game_state
+        .pieces()
+        .map(|(piece, _)| match piece.color {
+            PieceColor::White => values.value(piece.kind),
+            PieceColor::Black => -values.value(piece.kind),
+        })
+        .sum()
+}
+
+type Table = [[i32; 8]; 8];
+
+/// Positional bonus for each piece kind on each square, in centipawns. Tables are from White's point of
+/// view with rank 1 first, same as the board, and get mirrored for Black.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PieceSquareTables {
+    pub pawn: Table,
+    pub knight: Table,
+    pub bishop: Table,
+    pub rook: Table,
+    pub queen: Table,
+    pub king: Table,
+}
+
+impl Default for PieceSquareTables {
+    // Based on the "simplified evaluation function" tables
+    #[rustfmt::skip]
+    fn default() -> Self {
+        Self {
+            pawn: [
+                [  0,   0,   0,   0,   0,   0,   0,   0],
+                [  5,  10,  10, -20, -20,  10,  10,   5],
+                [  5,  -5, -10,   0,   0, -10,  -5,   5],
+                [  0,   0,   0,  20,  20,   0,   0,   0],
+                [  5,   5,  10,  25,  25,  10,   5,   5],
+                [ 10,  10,  20,  30,  30,  20,  10,  10],
+                [ 50,  50,  50,  50,  50,  50,  50,  50],
+                [  0,   0,   0,   0,   0,   0,   0,   0],
+            ],
+            knight: [
+                [-50, -40, -30, -30, -30, -30, -40, -50],
+                [-40, -20,   0,   5,   5,   0, -20, -40],
+                [-30,   5,  10,  15,  15,  10,   5, -30],
+                [-30,   0,  15,  20,  20,  15,   0, -30],
+                [-30,   5,  15,  20,  20,  15,   5, -30],
+                [-30,   0,  10,  15,  15,  10,   0, -30],
+                [-40, -20,   0,   0,   0,   0, -20, -40],
+                [-50, -40, -30, -30, -30, -30, -40, -50],
+            ],
+            bishop: [
+                [-20, -10, -10, -10, -10, -10, -10, -20],
+                [-10,   5,   0,   0,   0,   0,   5, -10],
+                [-10,  10,  10,  10,  10,  10,  10, -10],
+                [-10,   0,  10,  10,  10,  10,   0, -10],
+                [-10,   5,   5,  10,  10,   5,   5, -10],
+                [-10,   0,   5,  10,  10,   5,   0, -10],
+                [-10,   0,   0,   0,   0,   0,   0, -10],
+                [-20, -10, -10, -10, -10, -10, -10, -20],
+            ],
+            rook: [
+                [  0,   0,   0,   5,   5,   0,   0,   0],
+                [ -5,   0,   0,   0,   0,   0,   0,  -5],
+                [ -5,   0,   0,   0,   0,   0,   0,  -5],
+                [ -5,   0,   0,   0,   0,   0,   0,  -5],
+                [ -5,   0,   0,   0,   0,   0,   0,  -5],
+                [ -5,   0,   0,   0,   0,   0,   0,  -5],
+                [  5,  10,  10,  10,  10,  10,  10,   5],
+                [  0,   0,   0,   0,   0,   0,   0,   0],
+            ],
+            queen: [
+                [-20, -10, -10,  -5,  -5, -10, -10, -20],
+                [-10,   0,   5,   0,   0,   0,   0, -10],
+                [-10,   5,   5,   5,   5,   5,   0, -10],
+                [  0,   0,   5,   5,   5,   5,   0,  -5],
+                [ -5,   0,   5,   5,   5,   5,   0,  -5],
+                [-10,   0,   5,   5,   5,   5,   0, -10],
+                [-10,   0,   0,   0,   0,   0,   0, -10],
+                [-20, -10, -10,  -5,  -5, -10, -10, -20],
+            ],
+            king: [
+                [ 20,  30,  10,   0,   0,  10,  30,  20],
+                [ 20,  20,   0,   0,   0,   0,  20,  20],
+                [-10, -20, -20, -20, -20, -20, -20, -10],
+                [-20, -30, -30, -40, -40, -30, -30, -20],
+                [-30, -40, -40, -50, -50, -40, -40, -30],
+                [-30, -40, -40, -50, -50, -40, -40, -30],
+                [-30, -40, -40, -50, -50, -40, -40, -30],
+                [-30, -40, -40, -50, -50, -40, -40, -30],
+            ],
+        }
+    }
+}
+
+impl PieceSquareTables {
+    pub fn bonus(&self, piece: Piece, pos: BoardPosition) -> i32 {
+        let table = match piece.kind {
+            PieceKind::Pawn(_) => &self.pawn,
+            PieceKind::Knight => &self.knight,
+            PieceKind::Bishop => &self.bishop,
+            PieceKind::Rook => &self.rook,
+            PieceKind::Queen => &self.queen,
+            PieceKind::King => &self.king,
+        };
+        let row = match piece.color {
+            PieceColor::White => pos.row,
+            PieceColor::Black => 7 - pos.row,
+        };
+        table[row as usize][pos.col as usize]
+    }
+}
+
+/// Material plus position, in centipawns. Positive is good for White.
+pub fn evaluate(game_state: &GameState, values: &PieceValues, tables: &PieceSquareTables) -> i32 {
+    game_state
+        .pieces()
+        .map(|(piece, pos)| {
+            let score = values.value(piece.kind) + tables.bonus(piece, pos);
+            match piece.color {
+                PieceColor::White => score,
+                PieceColor::Black => -score,
+            }
+        })
+        .sum()
+}
+
+// Bigger than any evaluation could get. Mates found sooner score higher.
+const MATE: i32 = 1_000_000;
+
+/// Looks ahead a fixed number of half-moves using negamax with alpha-beta pruning, then keeps following
+/// captures until things settle down. Draws by the fifty-move rule or repetition aren't considered.
+pub struct Search<'a> {
+    values: &'a PieceValues,
+    tables: &'a PieceSquareTables,
+    quiescence: bool,
+    nodes: u64,
+    out_of_time: Option<Box<dyn Fn() -> bool + 'a>>,
+    stopped: bool, // Ran out of time partway, so the current search result can't be trusted
+}
+
+impl<'a> Search<'a> {
+    pub fn new(values: &'a PieceValues, tables: &'a PieceSquareTables) -> Self {
+        Self {
+            values,
+            tables,
+            quiescence: true,
+            nodes: 0,
+            out_of_time: None,
+            stopped: false,
+        }
+    }
+
+    /// Whether to keep searching captures past the fixed depth. Without it the search happily grabs a
+    /// pawn even when that loses the queen on the very next move.
+    pub fn quiescence(mut self, enabled: bool) -> Self {
+        self.quiescence = enabled;
+        self
+    }
+
+    /// Number of positions visited so far.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// The best move for the current player along with its score (from their point of view), or None if
+    /// they have no moves.
+    pub fn best_move(&mut self, game_state: &GameState, depth: u32) -> Option<(Move, i32)> {
+        if game_state.variant_winner().is_some() {
+            return None;
+        }
+
+        let mut best = None;
+        let mut alpha = -MATE - 1;
+        for mv in self.ordered(game_state, game_state.legal_moves()) {
+            if self.should_stop() {
+                break;
+            }
+            let mut next = game_state.clone();
+            next.make_move(mv);
+            let score = -self.negamax(&next, depth.saturating_sub(1), 1, -MATE - 1, -alpha);
+            if score > alpha {
+                alpha = score;
+                best = Some((mv, score));
+            }
+        }
+        best
+    }
+
+    /// Searches one half-move deeper at a time until `out_of_time` says to stop, returning the best move
+    /// from the deepest search that finished. Depth 1 always finishes, so there's a move if there's any at all.
+    pub fn best_move_within(
+        &mut self,
+        game_state: &GameState,
+        max_depth: u32,
+        out_of_time: impl Fn() -> bool + 'a,
+    ) -> Option<(Move, i32)> {
+        let mut best = self.best_move(game_state, 1);
+
+        self.out_of_time = Some(Box::new(out_of_time));
+        for depth in 2..=max_depth {
+            let result = self.best_move(game_state, depth);
+            if self.stopped {
+                break;
+            }
+            best = result;
+        }
+        self.out_of_time = None;
+        self.stopped = false;
+
+        best
+    }
+
+    // Checking the time isn't free, so only do it every so often
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped && self.nodes % 256 == 0 {
+            self.stopped = self.out_of_time.as_ref().is_some_and(|f| f());
+        }
+        self.stopped
+    }
+
+    fn negamax(
+        &mut self,
+        game_state: &GameState,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0; // Thrown away anyway
+        }
+
+        if let Some(score) = variant_score(game_state, ply) {
+            return score;
+        }
+
+        let moves = self.ordered(game_state, game_state.legal_moves());
+        if moves.is_empty() {
+            return if game_state.is_in_check(game_state.curr_player) {
+                -(MATE - ply)
+            } else {
+                0 // Stalemate
+            };
+        }
+        if depth == 0 {
+            return if self.quiescence {
+                self.quiesce(game_state, ply, alpha, beta)
+            } else {
+                self.evaluate(game_state)
+            };
+        }
+
+        for mv in moves {
+            let mut next = game_state.clone();
+            next.make_move(mv);
+            let score = -self.negamax(&next, depth - 1, ply + 1, -beta, -alpha);
+            if score >= beta {
+                return beta; // The opponent won't allow this line, no need to look any further
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    // Only looks at captures and promotions, since any other move could make the position quiet
+    fn quiesce(&mut self, game_state: &GameState, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        if let Some(score) = variant_score(game_state, ply) {
+            return score;
+        }
+
+        // The player doesn't have to capture, so the current evaluation is the least they can get
+        let stand_pat = self.evaluate(game_state);
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
+
+        for mv in self.ordered(game_state, game_state.legal_captures()) {
+            let mut next = game_state.clone();
+            next.make_move(mv);
+            let score = -self.quiesce(&next, ply + 1, -beta, -alpha);
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    // From the current player's point of view
+    fn evaluate(&self, game_state: &GameState) -> i32 {
+        let score = evaluate(game_state, self.values, self.tables);
+        match game_state.curr_player {
+            PieceColor::White => score,
+            PieceColor::Black => -score,
+        }
+    }
+
+    // Captures first, most valuable victim and then least valuable attacker, so pruning kicks in sooner
+    fn ordered(&self, game_state: &GameState, mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_key(|mv| {
+            // TODO: en passant captures land on an empty square so they're not ordered as captures
+            match (game_state.get(mv.to), game_state.get(mv.from)) {
+                (Some(victim), Some(attacker)) => Reverse(
+                    1 + 10 * self.values.value(victim.kind) - self.values.value(attacker.kind),
+                ),
+                _ => Reverse(i32::MIN),
+            }
+        });
+        moves
+    }
+}
+
+// Scores a game won by a variant rule like a checkmate
+fn variant_score(game_state: &GameState, ply: i32) -> Option<i32> {
+    game_state.variant_winner().map(|winner| {
+        if winner == game_state.curr_player {
+            MATE - ply
+        } else {
+            -(MATE - ply)
+        }
+    })
+}
+
+// Deeper than there's ever time for, it's just there to stop searching once the game tree runs out
+const MAX_DEPTH: u32 = 64;
+
+/// How strong the AI plays. Setting this updates AiSettings to match.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(format!("Unknown difficulty: {s}")),
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn settings(self) -> AiSettings {
+        match self {
+            Self::Easy => AiSettings {
+                max_depth: 2,
+                think_time: Duration::from_millis(250),
+                blunder_chance: 0.2,
+            },
+            Self::Medium => AiSettings {
+                max_depth: 4,
+                think_time: Duration::from_millis(1000),
+                blunder_chance: 0.0,
+            },
+            Self::Hard => AiSettings {
+                max_depth: MAX_DEPTH,
+                think_time: Duration::from_millis(3000),
+                blunder_chance: 0.0,
+            },
+        }
+    }
+}
+
+/// What the AI actually uses to pick its moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiSettings {
+    pub max_depth: u32,
+    pub think_time: Duration, // Searching stops at whichever of this or max_depth comes first
+    pub blunder_chance: f64, // Chance of playing a random legal move instead of searching, from 0 to 1
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        Difficulty::default().settings()
+    }
+}
+
+fn apply_difficulty(difficulty: Res<Difficulty>, mut settings: ResMut<AiSettings>) {
+    if difficulty.is_changed() {
+        *settings = difficulty.settings();
+    }
+}
+
+// Where the AI's moves come from when it isn't searching for them
+#[derive(SystemParam)]
+struct ChanceMoves<'w, 's> {
+    book: Option<Res<'w, OpeningBook>>,
+    rng: ResMut<'w, GameRng>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// How the AI's last search went, for the diagnostics overlay.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SearchStats {
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+// A search running in the background so the game doesn't freeze while the AI thinks
+#[derive(Component)]
+struct AiSearch(Task<(Option<Move>, SearchStats)>);
+
+// Going easy on the player, with a random move instead of a searched one every so often
+fn blunder(settings: &AiSettings, game_state: &GameState, rng: &mut impl Rng) -> Option<Move> {
+    if rng.gen_bool(settings.blunder_chance.clamp(0.0, 1.0)) {
+        game_state.legal_moves().choose(rng).copied()
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_ai_search(
+    mut commands: Commands,
+    players: Res<PlayerConfig>,
+    settings: Res<AiSettings>,
+    game_state: Res<GameState>,
+    turn_data: Res<TurnData>,
+    review_state: Res<ReviewState>,
+    invalid_board: Res<InvalidBoard>,
+    values: Res<PieceValues>,
+    tables: Res<PieceSquareTables>,
+    search_query: Query<(), With<AiSearch>>,
+    mut chance: ChanceMoves,
+    claimable_draw: Res<ClaimableDraw>,
+    ai_paused: Res<AiPaused>,
+    editor: Res<Editor>,
+    mut request_move_events: EventWriter<RequestMoveEvent>,
+    mut claim_draw_events: EventWriter<ClaimDrawEvent>,
+) {
+    if players.get(game_state.curr_player) != PlayerKind::Ai
+        || ai_paused.0
+        || editor.active
+        || game_state.game_over.is_some()
+        || invalid_board.0.is_some()
+        || review_state.ply.is_some()
+        || !turn_data.is_awaiting_move()
+        || !search_query.is_empty()
+    {
+        return;
+    }
+
+    // Take a draw unless we're ahead. This also keeps AI vs AI games from shuffling back and forth forever.
+    if claimable_draw.0.is_some() && Search::new(&values, &tables).evaluate(&game_state) <= 0 {
+        claim_draw_events.send(ClaimDrawEvent);
+        return;
+    }
+
+    let rng = chance.rng.rng();
+
+    // No need to think while still in the book
+    if let Some(mv) = chance.book.and_then(|book| book.pick(&game_state, rng)) {
+        request_move_events.send(RequestMoveEvent(mv));
+        return;
+    }
+
+    if let Some(mv) = blunder(&settings, &game_state, rng) {
+        request_move_events.send(RequestMoveEvent(mv));
+        return;
+    }
+
+    let game_state = game_state.clone();
+    let (values, tables, settings) = (*values, *tables, *settings);
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let start = Instant::now();
+        let out_of_time = move || start.elapsed() >= settings.think_time;
+        let mut search = Search::new(&values, &tables);
+        let mv = search
+            .best_move_within(&game_state, settings.max_depth, out_of_time)
+            .map(|(mv, _)| mv);
+        let stats = SearchStats {
+            nodes: search.nodes(),
+            time: start.elapsed(),
+        };
+        (mv, stats)
+    });
+    commands.spawn().insert(AiSearch(task));
+}
+
+fn finish_ai_search(
+    mut commands: Commands,
+    mut search_query: Query<(Entity, &mut AiSearch)>,
+    mut request_move_events: EventWriter<RequestMoveEvent>,
+    mut search_stats: ResMut<SearchStats>,
+    mut undo_events: EventReader<UndoEvent>,
+    mut new_game_events: EventReader<NewGameEvent>,
+) {
+    // A search started before moves were taken back is for a position that's no longer on the board
+    let undone = undo_events.iter().count() + new_game_events.iter().count() > 0;
+    for (entity, mut search) in &mut search_query {
+        if undone {
+            commands.entity(entity).despawn(); // Dropping the task cancels it
+            continue;
+        }
+        if let Some((result, stats)) = future::block_on(future::poll_once(&mut search.0)) {
+            if let Some(mv) = result {
+                request_move_events.send(RequestMoveEvent(mv));
+            }
+            *search_stats = stats;
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Same as with claiming a draw, the AI agrees to one unless it's ahead
+fn answer_draw_offer(
+    players: Res<PlayerConfig>,
+    game_state: Res<GameState>,
+    draw_offer: Res<DrawOffer>,
+    values: Res<PieceValues>,
+    tables: Res<PieceSquareTables>,
+    mut offer_draw_events: EventWriter<OfferDrawEvent>,
+) {
+    let ai_player = match draw_offer.0 {
+        Some(offered_by) if draw_offer.is_changed() => offered_by.next(),
+        _ => return,
+    };
+    if players.get(ai_player) != PlayerKind::Ai || game_state.game_over.is_some() {
+        return;
+    }
+
+    let score = evaluate(&game_state, &values, &tables);
+    let ai_score = match ai_player {
+        PieceColor::White => score,
+        PieceColor::Black => -score,
+    };
+    if ai_score <= 0 {
+        offer_draw_events.send(OfferDrawEvent(ai_player));
+    }
+}
+
+/// Plays any legal move, each as likely as the next. Far too weak to be an opponent, but cheap enough to
+/// play thousands of games with (e.g. through `GameState::play_out()`) to shake out problems in the rules.
+pub struct RandomBot;
+
+impl RandomBot {
+    pub fn pick(game_state: &GameState, rng: &mut impl Rng) -> Option<Move> {
+        game_state.legal_moves().choose(rng).copied()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_random_moves(
+    players: Res<PlayerConfig>,
+    game_state: Res<GameState>,
+    turn_data: Res<TurnData>,
+    review_state: Res<ReviewState>,
+    invalid_board: Res<InvalidBoard>,
+    editor: Res<Editor>,
+    claimable_draw: Res<ClaimableDraw>,
+    mut rng: ResMut<GameRng>,
+    mut request_move_events: EventWriter<RequestMoveEvent>,
+    mut claim_draw_events: EventWriter<ClaimDrawEvent>,
+) {
+    if players.get(game_state.curr_player) != PlayerKind::Random
+        || editor.active
+        || game_state.game_over.is_some()
+        || invalid_board.0.is_some()
+        || review_state.ply.is_some()
+        || !turn_data.is_awaiting_move()
+    {
+        return;
+    }
+
+    // Always taking a draw is what guarantees random games end
+    if claimable_draw.0.is_some() {
+        claim_draw_events.send(ClaimDrawEvent);
+    } else if let Some(mv) = RandomBot::pick(&game_state, rng.rng()) {
+        request_move_events.send(RequestMoveEvent(mv));
+    }
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PieceValues>()
+            .init_resource::<PieceSquareTables>()
+            .init_resource::<Difficulty>()
+            .init_resource::<AiSettings>()
+            .init_resource::<SearchStats>()
+            .add_system(apply_difficulty.before(start_ai_search))
+            // Started after the turn manager has had its say, so the same turn isn't searched twice
+            .add_system(finish_ai_search.before(TurnManager))
+            .add_system(start_ai_search.after(TurnManager))
+            .add_system(play_random_moves.after(TurnManager))
+            .add_system(answer_draw_offer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        game::MoveHistory,
+        notation::{from_fen, parse_uci},
+        test_app,
+    };
+
+    #[test]
+    fn material_balance_uses_the_piece_values() {
+        let game_state = from_fen("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1").unwrap();
+        let mut values = PieceValues::default();
+        assert_eq!(material_balance(&game_state, &values), 300);
+        values.knight = 325;
+        assert_eq!(material_balance(&game_state, &values), 325);
+    }
+
+    #[test]
+    fn centralized_knight_evaluates_higher() {
+        let (values, tables) = (PieceValues::default(), PieceSquareTables::default());
+        let on_e4 = from_fen("4k3/8/8/8/4N3/8/8/4K3 w - - 0 1").unwrap();
+        let on_a1 = from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert!(evaluate(&on_e4, &values, &tables) > evaluate(&on_a1, &values, &tables));
+
+        // Mirrored for Black
+        let on_e5 = from_fen("4k3/8/8/4n3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            evaluate(&on_e5, &values, &tables),
+            -evaluate(&on_e4, &values, &tables)
+        );
+    }
+
+    // Plain negamax looking at every move, in the same order as the real search
+    fn full_negamax(search: &mut Search, game_state: &GameState, depth: u32, ply: i32) -> i32 {
+        search.nodes += 1;
+        if let Some(score) = variant_score(game_state, ply) {
+            return score;
+        }
+        let moves = search.ordered(game_state, game_state.legal_moves());
+        if moves.is_empty() {
+            return if game_state.is_in_check(game_state.curr_player) {
+                -(MATE - ply)
+            } else {
+                0
+            };
+        }
+        if depth == 0 {
+            return search.evaluate(game_state);
+        }
+        moves
+            .into_iter()
+            .map(|mv| {
+                let mut next = game_state.clone();
+                next.make_move(mv);
+                -full_negamax(search, &next, depth - 1, ply + 1)
+            })
+            .max()
+            .unwrap()
+    }
+
+    fn full_best_move(search: &mut Search, game_state: &GameState, depth: u32) -> (Move, i32) {
+        let mut best = None;
+        let mut best_score = -MATE - 1;
+        for mv in search.ordered(game_state, game_state.legal_moves()) {
+            let mut next = game_state.clone();
+            next.make_move(mv);
+            let score = -full_negamax(search, &next, depth - 1, 1);
+            if score > best_score {
+                best = Some(mv);
+                best_score = score;
+            }
+        }
+        (best.unwrap(), best_score)
+    }
+
+    #[test]
+    fn pruning_finds_the_same_moves_with_fewer_nodes() {
+        let (values, tables) = (PieceValues::default(), PieceSquareTables::default());
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            "r3k2r/ppp2ppp/2n1bn2/3pp3/3PP3/2N1BN2/PPP2PPP/R3K2R w KQkq - 0 8",
+            "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+        ] {
+            let game_state = from_fen(fen).unwrap();
+            let mut pruned = Search::new(&values, &tables).quiescence(false);
+            let mut full = Search::new(&values, &tables).quiescence(false);
+            let found = pruned.best_move(&game_state, 3).unwrap();
+            assert_eq!(found, full_best_move(&mut full, &game_state, 3), "{fen}");
+            assert!(pruned.nodes() < full.nodes(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn quiescence_stops_the_queen_grabbing_a_defended_pawn() {
+        let (values, tables) = (PieceValues::default(), PieceSquareTables::default());
+        let game_state = from_fen("4k3/8/4p3/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let grab = parse_uci("d2d5").unwrap();
+
+        let mut fixed = Search::new(&values, &tables).quiescence(false);
+        assert_eq!(fixed.best_move(&game_state, 1).unwrap().0, grab);
+        let mut quiet = Search::new(&values, &tables);
+        assert_ne!(quiet.best_move(&game_state, 1).unwrap().0, grab);
+    }
+
+    #[test]
+    fn out_of_time_straight_away_gives_the_depth_one_move() {
+        let (values, tables) = (PieceValues::default(), PieceSquareTables::default());
+        let game_state =
+            from_fen("r3k2r/ppp2ppp/2n1bn2/3pp3/3PP3/2N1BN2/PPP2PPP/R3K2R w KQkq - 0 8").unwrap();
+        let depth_one = Search::new(&values, &tables).best_move(&game_state, 1);
+
+        // A clock that's already run out by the time it's first looked at
+        let checked = Cell::new(0);
+        let out_of_time = || {
+            checked.set(checked.get() + 1);
+            true
+        };
+        let mut search = Search::new(&values, &tables);
+        let found = search.best_move_within(&game_state, MAX_DEPTH, out_of_time);
+        assert_eq!(found, depth_one);
+        assert!(checked.get() > 0);
+    }
+
+    #[test]
+    fn easy_searches_less_deep_than_hard() {
+        let (easy, hard) = (Difficulty::Easy.settings(), Difficulty::Hard.settings());
+        assert!(easy.max_depth < hard.max_depth);
+        assert!(easy.think_time < hard.think_time);
+    }
+
+    #[test]
+    fn no_blunder_chance_never_blunders() {
+        let game_state =
+            from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let mut settings = Difficulty::Hard.settings();
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            assert_eq!(blunder(&settings, &game_state, &mut rng), None);
+        }
+
+        settings.blunder_chance = 1.0;
+        let mv = blunder(&settings, &game_state, &mut rng).unwrap();
+        assert!(game_state.legal_moves().contains(&mv));
+    }
+
+    #[test]
+    fn ai_against_itself_plays_a_game_to_the_end() {
+        let mut app = test_app::game_app();
+        app.insert_resource(PlayerConfig {
+            white: PlayerKind::Ai,
+            black: PlayerKind::Ai,
+        })
+        .add_plugin(AiPlugin);
+        test_app::update(&mut app);
+        app.insert_resource(AiSettings {
+            max_depth: 1,
+            think_time: Duration::from_millis(10),
+            blunder_chance: 0.0,
+        });
+
+        for _ in 0..20_000 {
+            test_app::update(&mut app);
+            if app.world.resource::<GameState>().game_over.is_some() {
+                break;
+            }
+        }
+        let game_state = app.world.resource::<GameState>();
+        assert!(game_state.game_over.is_some(), "{game_state}");
+        assert!(game_state.plies > 0);
+    }
+
+    // The first moves of an AI against AI game, with half of them random
+    fn seeded_ai_game(seed: u64) -> Vec<Move> {
+        let mut app = test_app::game_app();
+        app.insert_resource(GameRng::new(seed)).add_plugin(AiPlugin);
+        test_app::update(&mut app);
+        // The search stops on its depth rather than the clock, which would vary. The AI only gets to
+        // move once this is set, so even the first move is searched this way.
+        app.insert_resource(AiSettings {
+            max_depth: 1,
+            think_time: Duration::from_secs(60),
+            blunder_chance: 0.5,
+        })
+        .insert_resource(PlayerConfig {
+            white: PlayerKind::Ai,
+            black: PlayerKind::Ai,
+        });
+
+        for _ in 0..2000 {
+            test_app::update(&mut app);
+            let game_state = app.world.resource::<GameState>();
+            if game_state.plies >= 30 || game_state.game_over.is_some() {
+                break;
+            }
+        }
+        app.world.resource::<MoveHistory>().moves().to_vec()
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_game() {
+        let moves = seeded_ai_game(875);
+        assert!(moves.len() >= 10);
+        assert_eq!(seeded_ai_game(875), moves);
+        assert_ne!(seeded_ai_game(876), moves);
+    }
+
+    // Positions reached after exactly `depth` half-moves, the usual check on a move generator
+    fn perft(game_state: &GameState, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        game_state
+            .legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut next = game_state.clone();
+                next.make_move(mv);
+                perft(&next, depth - 1)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn depth_one_search_visits_every_perft_node() {
+        let (values, tables) = (PieceValues::default(), PieceSquareTables::default());
+        // The start position, and "Kiwipete", whose perft counts are well known
+        for (fen, known) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                20,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                48,
+            ),
+        ] {
+            let game_state = from_fen(fen).unwrap();
+            assert_eq!(perft(&game_state, 1), known, "{fen}");
+            // Nothing can be cut off at depth one, so each move's position is visited once
+            let mut search = Search::new(&values, &tables).quiescence(false);
+            search.best_move(&game_state, 1).unwrap();
+            assert_eq!(search.nodes(), known, "{fen}");
+        }
+        let start = from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&start, 2), 400);
+    }
+
+    #[test]
+    fn search_stats_count_the_ai_search() {
+        let mut app = test_app::game_app();
+        app.add_plugin(AiPlugin);
+        test_app::update(&mut app);
+        // Only handed to the AI once it's set up, so it doesn't start searching with the defaults
+        app.insert_resource(AiSettings {
+            max_depth: 1,
+            think_time: Duration::from_secs(60),
+            blunder_chance: 0.0,
+        })
+        .insert_resource(PlayerConfig {
+            white: PlayerKind::Ai,
+            black: PlayerKind::Human,
+        });
+        for _ in 0..20_000 {
+            test_app::update(&mut app);
+            if app.world.resource::<GameState>().plies > 0 {
+                break;
+            }
+        }
+        assert_eq!(app.world.resource::<GameState>().plies, 1);
+
+        // The same search as the AI's, with the quiescence search looking once more at each position
+        let (values, tables) = (PieceValues::default(), PieceSquareTables::default());
+        let start = from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let mut search = Search::new(&values, &tables);
+        search.best_move_within(&start, 1, || false);
+        assert_eq!(app.world.resource::<SearchStats>().nodes, search.nodes());
+        assert_eq!(search.nodes(), 2 * perft(&start, 1));
+    }
+
+    #[test]
+    fn random_players_finish_the_game() {
+        let mut app = test_app::game_app();
+        app.insert_resource(GameRng::new(884))
+            .insert_resource(PlayerConfig {
+                white: PlayerKind::Random,
+                black: PlayerKind::Random,
+            })
+            .add_plugin(AiPlugin);
+
+        for _ in 0..20_000 {
+            test_app::update(&mut app);
+            if app.world.resource::<GameState>().game_over.is_some() {
+                break;
+            }
+        }
+        let game_state = app.world.resource::<GameState>();
+        assert!(game_state.game_over.is_some(), "{game_state}");
+        assert_eq!(
+            app.world.resource::<MoveHistory>().moves().len(),
+            game_state.plies as usize
+        );
+    }
+}
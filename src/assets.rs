@@ -0,0 +1,174 @@
+use bevy::{asset::LoadState, prelude::*};
+
+use crate::pieces::PieceSet;
+
+pub const FONT_PATH: &str = "fonts/FiraSans-Bold.ttf";
+
+/// Where loading the assets the game can't do without has got to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AssetCheck {
+    Loading,
+    Loaded,
+    Failed(Vec<String>), // Paths of the assets that didn't load
+}
+
+/// Sums up the load states of the critical assets, by path. Any failure counts as soon as it's known,
+/// even with others still loading, so the player isn't left looking at a blank board in the meantime.
+pub fn check_assets<'a>(states: impl IntoIterator<Item = (&'a str, LoadState)>) -> AssetCheck {
+    let mut failed = Vec::new();
+    let mut loading = false;
+    for (path, state) in states {
+        match state {
+            LoadState::Loaded => {}
+            LoadState::NotLoaded | LoadState::Loading => loading = true,
+            LoadState::Failed | LoadState::Unloaded => failed.push(path.to_string()),
+        }
+    }
+
+    if !failed.is_empty() {
+        AssetCheck::Failed(failed)
+    } else if loading {
+        AssetCheck::Loading
+    } else {
+        AssetCheck::Loaded
+    }
+}
+
+// Without the piece set nothing shows on the board, and without the font none of the text does
+struct CriticalAssets(Vec<(String, HandleUntyped)>);
+
+#[derive(Component)]
+struct AssetErrorBanner;
+
+#[derive(Component)]
+struct AssetErrorText;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, piece_set: Res<PieceSet>) {
+    let paths = [piece_set.path.as_str(), FONT_PATH];
+    commands.insert_resource(CriticalAssets(
+        paths
+            .into_iter()
+            .map(|path| (path.to_string(), asset_server.load_untyped(path)))
+            .collect(),
+    ));
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(0.0),
+                    right: Val::Px(0.0),
+                    top: Val::Percent(45.0),
+                    ..default()
+                },
+                padding: UiRect::all(Val::Px(10.0)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::rgb(0.5, 0.05, 0.05).into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(AssetErrorBanner)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(AssetErrorText);
+        });
+}
+
+// Once something critical has failed there's no recovering, so say what and stop checking. If it was
+// the font the banner can't show any text, which is why the window title says it as well.
+fn report_failed_assets(
+    mut done: Local<bool>,
+    asset_server: Res<AssetServer>,
+    critical_assets: Res<CriticalAssets>,
+    mut windows: ResMut<Windows>,
+    mut banner_query: Query<&mut Visibility, With<AssetErrorBanner>>,
+    mut text_query: Query<&mut Text, With<AssetErrorText>>,
+) {
+    if *done {
+        return;
+    }
+
+    let states = critical_assets
+        .0
+        .iter()
+        .map(|(path, handle)| (path.as_str(), asset_server.get_load_state(handle)));
+    let failed = match check_assets(states) {
+        AssetCheck::Loading => return,
+        AssetCheck::Loaded => Vec::new(),
+        AssetCheck::Failed(failed) => failed,
+    };
+    *done = true;
+    if failed.is_empty() {
+        return;
+    }
+
+    let message = format!("Couldn't load {}", failed.join(", "));
+    error!("{message}");
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(format!("Schach! - {message}"));
+    }
+    banner_query.single_mut().is_visible = true;
+    text_query.single_mut().sections[0].value = message;
+}
+
+pub struct AssetCheckPlugin;
+
+impl Plugin for AssetCheckPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PieceSet>()
+            .add_startup_system(setup)
+            .add_system(report_failed_assets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIECES: &str = "models/pieces.glb";
+
+    #[test]
+    fn critical_assets_are_checked_together() {
+        assert_eq!(check_assets([]), AssetCheck::Loaded);
+        assert_eq!(
+            check_assets([(PIECES, LoadState::Loaded), (FONT_PATH, LoadState::Loaded)]),
+            AssetCheck::Loaded
+        );
+        for still_going in [LoadState::NotLoaded, LoadState::Loading] {
+            assert_eq!(
+                check_assets([(PIECES, LoadState::Loaded), (FONT_PATH, still_going)]),
+                AssetCheck::Loading
+            );
+        }
+    }
+
+    #[test]
+    fn failures_are_named_without_waiting_for_the_rest() {
+        assert_eq!(
+            check_assets([(PIECES, LoadState::Failed), (FONT_PATH, LoadState::Loading)]),
+            AssetCheck::Failed(vec![PIECES.to_string()])
+        );
+        assert_eq!(
+            check_assets([
+                (PIECES, LoadState::Loaded),
+                (FONT_PATH, LoadState::Unloaded)
+            ]),
+            AssetCheck::Failed(vec![FONT_PATH.to_string()])
+        );
+        assert_eq!(
+            check_assets([(PIECES, LoadState::Failed), (FONT_PATH, LoadState::Failed)]),
+            AssetCheck::Failed(vec![PIECES.to_string(), FONT_PATH.to_string()])
+        );
+    }
+}
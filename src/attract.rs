@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, window::CursorMoved};
+
+use crate::game::{GameState, MoveHistory, NewGameEvent, PlayerConfig, PlayerKind};
+
+// How long a finished demo game stays on the board before the next one starts
+const RESULT_DELAY: Duration = Duration::from_secs(5);
+
+// Only present when running as a demo (`--attract <seconds>`). After a while without any input, when
+// there's no game going on, the AI plays itself over and over until someone touches the controls.
+pub struct AttractMode {
+    pub timeout: Duration, // How long without input before the demo starts
+    idle: Duration,
+    game_over_for: Duration,
+    players: Option<PlayerConfig>, // The real players, set aside while the demo is running
+}
+
+impl AttractMode {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            idle: Duration::ZERO,
+            game_over_for: Duration::ZERO,
+            players: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.players.is_some()
+    }
+
+    // Whether to start the demo after `delta` more time without input. Games that someone's in the
+    // middle of are left alone, only one that hasn't started yet or has finished gets replaced.
+    fn idle_for(&mut self, delta: Duration, game_in_progress: bool) -> bool {
+        self.idle += delta;
+        !self.is_running() && !game_in_progress && self.idle >= self.timeout
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_attract_mode(
+    time: Res<Time>,
+    attract_mode: Option<ResMut<AttractMode>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut players: ResMut<PlayerConfig>,
+    game_state: Res<GameState>,
+    move_history: Res<MoveHistory>,
+    mut new_game_events: EventWriter<NewGameEvent>,
+) {
+    let mut attract_mode = match attract_mode {
+        Some(attract_mode) => attract_mode,
+        None => return,
+    };
+
+    let input = keyboard_input.get_just_pressed().next().is_some()
+        || mouse_button_input.get_just_pressed().next().is_some()
+        || cursor_moved_events.iter().count() > 0;
+    if input {
+        // Hand a fresh game back to the real players
+        attract_mode.idle = Duration::ZERO;
+        if let Some(real_players) = attract_mode.players.take() {
+            *players = real_players;
+            new_game_events.send(NewGameEvent);
+        }
+        return;
+    }
+
+    let game_in_progress = game_state.game_over.is_none() && !move_history.moves().is_empty();
+    if attract_mode.idle_for(time.delta(), game_in_progress) {
+        attract_mode.players = Some(*players);
+        *players = PlayerConfig {
+            white: PlayerKind::Ai,
+            black: PlayerKind::Ai,
+        };
+        new_game_events.send(NewGameEvent);
+    } else if attract_mode.is_running() && game_state.game_over.is_some() {
+        attract_mode.game_over_for += time.delta();
+        if attract_mode.game_over_for >= RESULT_DELAY {
+            attract_mode.game_over_for = Duration::ZERO;
+            new_game_events.send(NewGameEvent);
+        }
+    }
+}
+
+pub struct AttractPlugin;
+
+impl Plugin for AttractPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(run_attract_mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{keyboard::KeyboardInput, ButtonState};
+
+    use super::*;
+    use crate::test_app::{self, game_app};
+
+    #[test]
+    fn demo_starts_after_the_timeout_without_input() {
+        let mut attract_mode = AttractMode::new(Duration::from_secs(30));
+        assert!(!attract_mode.idle_for(Duration::from_secs(20), false));
+        assert!(attract_mode.idle_for(Duration::from_secs(10), false));
+
+        // Not while someone's playing, however long they take
+        let mut attract_mode = AttractMode::new(Duration::from_secs(30));
+        assert!(!attract_mode.idle_for(Duration::from_secs(60), true));
+        assert!(attract_mode.idle_for(Duration::ZERO, false));
+    }
+
+    #[test]
+    fn input_hands_the_game_back_to_the_players() {
+        let mut app = game_app();
+        app.add_event::<CursorMoved>()
+            .insert_resource(AttractMode::new(Duration::ZERO))
+            .add_plugin(AttractPlugin);
+        test_app::update(&mut app);
+        assert!(app.world.resource::<AttractMode>().is_running());
+        let players = *app.world.resource::<PlayerConfig>();
+        assert_eq!(
+            (players.white, players.black),
+            (PlayerKind::Ai, PlayerKind::Ai)
+        );
+
+        // Checked straight away, since with no timeout the demo would start again the next frame
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(KeyCode::Space),
+            state: ButtonState::Pressed,
+        });
+        test_app::update(&mut app);
+        assert!(!app.world.resource::<AttractMode>().is_running());
+        let players = *app.world.resource::<PlayerConfig>();
+        assert_eq!(
+            (players.white, players.black),
+            (PlayerKind::Human, PlayerKind::Human)
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    board::BoardPosition,
+    pieces::{Piece, PieceColor, PieceKind},
+};
+
+// Sets of squares as 64-bit masks, bit `row * 8 + col` for each square (so a1 is bit 0 and h8 is
+// bit 63). GameState keeps its pieces as one of these per color and kind, and generates moves from the
+// attack tables and rays here rather than looking at the squares one at a time. Legal move filtering
+// checks each candidate move on a copy moved with move_piece(), rather than making the move on a copy
+// of the whole GameState.
+
+#[rustfmt::skip]
+pub const KNIGHT_OFFSETS: [(i8, i8); 8] = [(-2, -1), (-1, -2), (-2, 1), (-1, 2), (2, -1), (1, -2), (2, 1), (1, 2)];
+#[rustfmt::skip]
+pub const KING_OFFSETS: [(i8, i8); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+// The squares a piece jumping by the offsets reaches from each square, worked out at compile time
+const fn step_attacks(offsets: &[(i8, i8); 8]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let (row, col) = ((square / 8) as i8, (square % 8) as i8);
+        let mut i = 0;
+        while i < offsets.len() {
+            let (r, c) = (row + offsets[i].0, col + offsets[i].1);
+            if r >= 0 && r < 8 && c >= 0 && c < 8 {
+                table[square] |= 1 << (r * 8 + c);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+pub const KNIGHT_ATTACKS: [u64; 64] = step_attacks(&KNIGHT_OFFSETS);
+pub const KING_ATTACKS: [u64; 64] = step_attacks(&KING_OFFSETS);
+
+// Only for squares on the board
+pub fn square_index(pos: BoardPosition) -> usize {
+    (pos.row * 8 + pos.col) as usize
+}
+
+pub fn square_bit(pos: BoardPosition) -> u64 {
+    1 << square_index(pos)
+}
+
+/// The squares in a mask, from a1 to h8.
+pub fn squares(mut bits: u64) -> impl Iterator<Item = BoardPosition> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let index = bits.trailing_zeros() as i8;
+        bits &= bits - 1;
+        Some(BoardPosition {
+            row: index / 8,
+            col: index % 8,
+        })
+    })
+}
+
+/// The squares a pawn of the color attacks from the square, diagonally forward.
+pub fn pawn_attacks(color: PieceColor, pos: BoardPosition) -> u64 {
+    let forward = match color {
+        PieceColor::White => 1,
+        PieceColor::Black => -1,
+    };
+    [(forward, -1), (forward, 1)]
+        .into_iter()
+        .map(|offset| pos + offset)
+        .filter(|pos| pos.is_in_bounds())
+        .fold(0, |bits, pos| bits | square_bit(pos))
+}
+
+fn kind_index(kind: PieceKind) -> usize {
+    match kind {
+        PieceKind::King => 0,
+        PieceKind::Queen => 1,
+        PieceKind::Rook => 2,
+        PieceKind::Bishop => 3,
+        PieceKind::Knight => 4,
+        PieceKind::Pawn(_) => 5,
+    }
+}
+
+const KING: usize = 0;
+const QUEEN: usize = 1;
+const ROOK: usize = 2;
+const BISHOP: usize = 3;
+const KNIGHT: usize = 4;
+const PAWN: usize = 5;
+
+/// Where each kind of piece is for each side, plus every occupied square. Pawns that have moved are
+/// also kept track of, since only those that haven't can make the two-square move.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Bitboards {
+    pieces: [[u64; 6]; 2], // By color, then kind
+    occupied: u64,
+    moved_pawns: u64,
+}
+
+impl Bitboards {
+    pub fn from_board(board: &[[Option<Piece>; 8]; 8]) -> Self {
+        let mut bitboards = Self::default();
+        for (row, pieces) in board.iter().enumerate() {
+            for (col, piece) in pieces.iter().enumerate() {
+                let pos = BoardPosition {
+                    row: row as i8,
+                    col: col as i8,
+                };
+                bitboards.set(pos, *piece);
+            }
+        }
+        bitboards
+    }
+
+    /// The pieces as rows of squares, the first row being a1 to h1.
+    pub fn to_board(&self) -> [[Option<Piece>; 8]; 8] {
+        let mut board = [[None; 8]; 8];
+        for pos in squares(self.occupied) {
+            board[pos.row as usize][pos.col as usize] = self.get(pos);
+        }
+        board
+    }
+
+    // Only for squares on the board
+    pub fn get(&self, pos: BoardPosition) -> Option<Piece> {
+        let bit = square_bit(pos);
+        if self.occupied & bit == 0 {
+            return None;
+        }
+        for color in [PieceColor::White, PieceColor::Black] {
+            for (index, bits) in self.pieces[color as usize].iter().enumerate() {
+                if bits & bit != 0 {
+                    let kind = match index {
+                        KING => PieceKind::King,
+                        QUEEN => PieceKind::Queen,
+                        ROOK => PieceKind::Rook,
+                        BISHOP => PieceKind::Bishop,
+                        KNIGHT => PieceKind::Knight,
+                        _ => PieceKind::Pawn(self.moved_pawns & bit != 0),
+                    };
+                    return Some(Piece { color, kind });
+                }
+            }
+        }
+        None
+    }
+
+    /// Puts the piece on the square, or empties it, returning whatever was there. Only for squares on
+    /// the board.
+    pub fn set(&mut self, pos: BoardPosition, piece: Option<Piece>) -> Option<Piece> {
+        let old = self.get(pos);
+        let bit = square_bit(pos);
+        for bits in self.pieces.iter_mut().flatten() {
+            *bits &= !bit;
+        }
+        self.occupied &= !bit;
+        self.moved_pawns &= !bit;
+        if let Some(piece) = piece {
+            self.pieces[piece.color as usize][kind_index(piece.kind)] |= bit;
+            self.occupied |= bit;
+            if piece.kind == PieceKind::Pawn(true) {
+                self.moved_pawns |= bit;
+            }
+        }
+        old
+    }
+
+    pub fn pieces(&self, color: PieceColor, kind: PieceKind) -> u64 {
+        self.pieces[color as usize][kind_index(kind)]
+    }
+
+    /// Every square with one of the side's pieces on it.
+    pub fn color(&self, color: PieceColor) -> u64 {
+        self.pieces[color as usize]
+            .iter()
+            .fold(0, |all, bits| all | bits)
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.occupied
+    }
+
+    /// Moves whatever is on `from` to `to`, taking anything that was there. Castling, en passant and
+    /// Atomic explosions change other squares too, which this doesn't know about.
+    pub fn move_piece(&mut self, from: BoardPosition, to: BoardPosition) {
+        let (from_bit, to_bit) = (square_bit(from), square_bit(to));
+        if self.occupied & from_bit == 0 {
+            return;
+        }
+        for bits in self
+            .pieces
+            .iter_mut()
+            .flatten()
+            .chain([&mut self.moved_pawns])
+        {
+            let moving = *bits & from_bit != 0;
+            *bits &= !(from_bit | to_bit);
+            if moving {
+                *bits |= to_bit;
+            }
+        }
+        self.occupied = (self.occupied & !from_bit) | to_bit;
+    }
+
+    // Everything a slider on the square reaches along the directions, up to and including the first
+    // piece in the way
+    fn ray_attacks(&self, from: BoardPosition, directions: &[(i8, i8)]) -> u64 {
+        let mut attacks = 0;
+        for &direction in directions {
+            let mut pos = from + direction;
+            while pos.is_in_bounds() {
+                let bit = square_bit(pos);
+                attacks |= bit;
+                if self.occupied & bit != 0 {
+                    break;
+                }
+                pos += direction;
+            }
+        }
+        attacks
+    }
+
+    /// The squares a piece of this kind attacks from the square, stopping at (and including) the first
+    /// piece in the way for sliders. Pawns attack differently for each side, see pawn_attacks().
+    pub fn attacks(&self, kind: PieceKind, pos: BoardPosition) -> u64 {
+        match kind {
+            PieceKind::King => KING_ATTACKS[square_index(pos)],
+            PieceKind::Queen => {
+                self.ray_attacks(pos, &ROOK_DIRECTIONS) | self.ray_attacks(pos, &BISHOP_DIRECTIONS)
+            }
+            PieceKind::Rook => self.ray_attacks(pos, &ROOK_DIRECTIONS),
+            PieceKind::Bishop => self.ray_attacks(pos, &BISHOP_DIRECTIONS),
+            PieceKind::Knight => KNIGHT_ATTACKS[square_index(pos)],
+            PieceKind::Pawn(_) => 0,
+        }
+    }
+
+    /// The pieces of the color that attack the square, whether or not taking on it would be legal.
+    pub fn attackers(&self, pos: BoardPosition, by: PieceColor) -> u64 {
+        let theirs = &self.pieces[by as usize];
+        let straight = theirs[ROOK] | theirs[QUEEN];
+        let diagonal = theirs[BISHOP] | theirs[QUEEN];
+
+        // A pawn attacks diagonally forward, so its attackers are where one of the other side's pawns
+        // on the square would attack
+        (self.attacks(PieceKind::Knight, pos) & theirs[KNIGHT])
+            | (self.attacks(PieceKind::King, pos) & theirs[KING])
+            | (pawn_attacks(by.next(), pos) & theirs[PAWN])
+            | (self.attacks(PieceKind::Rook, pos) & straight)
+            | (self.attacks(PieceKind::Bishop, pos) & diagonal)
+    }
+
+    pub fn is_attacked(&self, pos: BoardPosition, by: PieceColor) -> bool {
+        self.attackers(pos, by) != 0
+    }
+}
@@ -1,51 +1,252 @@
-use std::ops::{Add, AddAssign};
+use std::{
+    iter,
+    ops::{Add, AddAssign},
+    time::Duration,
+};
 
 use bevy::prelude::*;
 use bevy_mod_picking::{HoverEvent, PickableBundle, PickingEvent};
 
 use crate::{
-    game::{TurnData, ValidMove},
-    pieces::PieceMoveEvent,
+    coach::CoachHints,
+    display::CameraTransition,
+    game::{GameState, IllegalMove, IllegalMoveEvent, MateSquares, TurnData, ValidMove},
+    pieces::{Piece, PieceMoveEvent},
 };
 
+/// The colors squares are highlighted in while playing, which can be changed while the game's running.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HighlightColors {
+    pub selected: Color,
+    pub valid_move: Color,
+    pub hovered: Color,         // With nothing selected
+    pub hovered_legal: Color,   // Where the selected piece can go
+    pub hovered_illegal: Color, // Where it can't
+    pub shadow: Color,          // Where the last move went to
+    pub shadow_origin: Color,   // Where it came from
+}
+
+impl Default for HighlightColors {
+    fn default() -> Self {
+        Self {
+            selected: Color::rgb(0.9, 0.1, 0.1),
+            valid_move: Color::rgb(0.3, 0.8, 0.3),
+            hovered: Color::rgb(0.6, 0.3, 0.3),
+            hovered_legal: Color::rgb(0.5, 1.0, 0.5),
+            hovered_illegal: Color::rgb(0.35, 0.1, 0.1),
+            shadow: Color::rgb(0.6, 0.6, 0.2),
+            shadow_origin: Color::rgb(0.4, 0.4, 0.15),
+        }
+    }
+}
+
 struct SquaresRenderData {
+    flash_color: Handle<StandardMaterial>,
+    mated_king_color: Handle<StandardMaterial>,
+    mating_piece_color: Handle<StandardMaterial>,
     hovered_color: Handle<StandardMaterial>,
+    hovered_legal_color: Handle<StandardMaterial>,
+    hovered_illegal_color: Handle<StandardMaterial>,
+    threatened_color: Handle<StandardMaterial>,
     selected_color: Handle<StandardMaterial>,
     valid_move_color: Handle<StandardMaterial>,
     shadow_color: Handle<StandardMaterial>,
+    origin_color: Handle<StandardMaterial>,
     black_color: Handle<StandardMaterial>,
     white_color: Handle<StandardMaterial>,
     background_color: Handle<StandardMaterial>,
+    frame_color: Handle<StandardMaterial>,
+    felt_color: Handle<StandardMaterial>,
 }
 
 impl FromWorld for SquaresRenderData {
     fn from_world(world: &mut World) -> Self {
+        let colors = *world.get_resource_or_insert_with(HighlightColors::default);
         let mut materials = world
             .get_resource_mut::<Assets<StandardMaterial>>()
             .unwrap();
         Self {
-            hovered_color: materials.add(Color::rgb(0.6, 0.3, 0.3).into()),
-            selected_color: materials.add(Color::rgb(0.9, 0.1, 0.1).into()),
-            valid_move_color: materials.add(Color::rgb(0.3, 0.8, 0.3).into()),
-            shadow_color: materials.add(Color::rgb(0.6, 0.6, 0.2).into()),
+            flash_color: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
+            mated_king_color: materials.add(Color::rgb(0.7, 0.0, 0.0).into()),
+            mating_piece_color: materials.add(Color::rgb(1.0, 0.65, 0.0).into()),
+            hovered_color: materials.add(colors.hovered.into()),
+            hovered_legal_color: materials.add(colors.hovered_legal.into()),
+            hovered_illegal_color: materials.add(colors.hovered_illegal.into()),
+            threatened_color: materials.add(Color::rgb(0.55, 0.2, 0.7).into()),
+            selected_color: materials.add(colors.selected.into()),
+            valid_move_color: materials.add(colors.valid_move.into()),
+            shadow_color: materials.add(colors.shadow.into()),
+            origin_color: materials.add(colors.shadow_origin.into()),
             black_color: materials.add(Color::rgb(0.1, 0.1, 0.1).into()),
             white_color: materials.add(Color::rgb(0.9, 0.9, 0.9).into()),
-            background_color: materials.add(Color::rgb(0.5, 0.5, 0.5).into()),
+            // Unlit so the backdrop is the same flat grey all over, rather than falling off into the dark
+            // away from the light over the board
+            background_color: materials.add(StandardMaterial {
+                base_color: Color::rgb(0.5, 0.5, 0.5),
+                unlit: true,
+                ..default()
+            }),
+            frame_color: materials.add(Color::rgb(0.35, 0.2, 0.1).into()),
+            felt_color: materials.add(StandardMaterial {
+                base_color: Color::rgb(0.1, 0.3, 0.15),
+                perceptual_roughness: 1.0,
+                ..default()
+            }),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+impl SquaresRenderData {
+    fn material(&self, highlight: SquareHighlight) -> &Handle<StandardMaterial> {
+        match highlight {
+            SquareHighlight::Flash => &self.flash_color,
+            SquareHighlight::MatedKing => &self.mated_king_color,
+            SquareHighlight::MatingPiece => &self.mating_piece_color,
+            SquareHighlight::Selected => &self.selected_color,
+            SquareHighlight::Hovered(HoverTarget::NoSelection) => &self.hovered_color,
+            SquareHighlight::Hovered(HoverTarget::Legal) => &self.hovered_legal_color,
+            SquareHighlight::Hovered(HoverTarget::Illegal) => &self.hovered_illegal_color,
+            SquareHighlight::Threatened => &self.threatened_color,
+            SquareHighlight::ValidMove => &self.valid_move_color,
+            SquareHighlight::LastMoveOrigin => &self.origin_color,
+            SquareHighlight::LastMoveTarget => &self.shadow_color,
+            SquareHighlight::Plain(SquareColor::White) => &self.white_color,
+            SquareHighlight::Plain(SquareColor::Black) => &self.black_color,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum SquareColor {
     White,
     Black,
 }
 
+// Briefly marks a square, e.g. the square of a pinned piece that the player just tried to move
+#[derive(Component)]
+struct SquareFlash(Timer);
+
+fn flash_pinned_pieces(
+    mut commands: Commands,
+    mut illegal_move_events: EventReader<IllegalMoveEvent>,
+    square_query: Query<(Entity, &BoardPosition), With<Square>>,
+) {
+    for event in illegal_move_events.iter() {
+        if event.reason != IllegalMove::Pinned {
+            continue;
+        }
+        for (entity, pos) in &square_query {
+            if *pos == event.from {
+                commands
+                    .entity(entity)
+                    .insert(SquareFlash(Timer::from_seconds(0.3, false)));
+            }
+        }
+    }
+}
+
+fn end_square_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flash_query: Query<(Entity, &mut SquareFlash)>,
+) {
+    for (entity, mut flash) in &mut flash_query {
+        if flash.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<SquareFlash>();
+        }
+    }
+}
+
+// How a square should be drawn, highest priority first. Squares keep the one they're drawn with as a
+// component, so their material only gets touched when it actually changes.
+#[derive(Clone, Component, Copy, Debug, Eq, PartialEq)]
+enum SquareHighlight {
+    Flash,
+    MatedKing,   // Shown once the game's over, so nothing's ever selected then anyway
+    MatingPiece, // Likewise
+    Selected,
+    Hovered(HoverTarget),
+    Threatened,
+    ValidMove,
+    LastMoveOrigin,
+    LastMoveTarget,
+    Plain(SquareColor),
+}
+
+/// What the square under the cursor would mean as a destination for the selected piece.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HoverTarget {
+    NoSelection, // Nothing's selected, or the square has another of the player's pieces to switch to
+    Legal,
+    Illegal,
+}
+
+/// Whether moving the selected piece (if any) to the hovered square would be legal, worked out as the
+/// cursor moves so the player knows before clicking.
+pub fn hover_target(
+    game_state: &GameState,
+    selected_pos: Option<BoardPosition>,
+    hovered_pos: BoardPosition,
+) -> HoverTarget {
+    let selected_pos = match selected_pos {
+        Some(pos) => pos,
+        None => return HoverTarget::NoSelection,
+    };
+    // Checked before the player's own pieces since castling in Chess960 can mean moving onto one
+    if game_state
+        .illegal_move_reason(selected_pos, hovered_pos)
+        .is_none()
+    {
+        HoverTarget::Legal
+    } else if game_state
+        .get(hovered_pos)
+        .is_some_and(|piece| piece.color == game_state.curr_player)
+    {
+        HoverTarget::NoSelection
+    } else {
+        HoverTarget::Illegal
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn square_highlight(
+    pos: BoardPosition,
+    flashing: bool,
+    selected_pos: Option<BoardPosition>,
+    hovered: Option<HoverTarget>,
+    threatened: bool,
+    valid_move: bool,
+    shadow_squares: &ShadowSquares,
+    mate: Option<&MateSquares>,
+) -> SquareHighlight {
+    if flashing {
+        SquareHighlight::Flash
+    } else if mate.is_some_and(|mate| mate.king == pos) {
+        SquareHighlight::MatedKing
+    } else if mate.is_some_and(|mate| mate.attackers.contains(&pos)) {
+        SquareHighlight::MatingPiece
+    } else if Some(pos) == selected_pos {
+        SquareHighlight::Selected
+    } else if let Some(target) = hovered {
+        SquareHighlight::Hovered(target)
+    } else if threatened {
+        SquareHighlight::Threatened
+    } else if valid_move {
+        SquareHighlight::ValidMove
+    } else if Some(pos) == shadow_squares.source {
+        SquareHighlight::LastMoveOrigin
+    } else if Some(pos) == shadow_squares.target {
+        SquareHighlight::LastMoveTarget
+    } else {
+        SquareHighlight::Plain(pos.square_color())
+    }
+}
+
 #[derive(Clone, Component, Copy, Debug)]
 pub struct Square;
 
 // (0, 0) is A1, (0, 7) is A8
-#[derive(Clone, Component, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Component, Copy, Debug, Default, Eq, PartialEq)]
 pub struct BoardPosition {
     pub row: i8,
     pub col: i8,
@@ -71,6 +272,35 @@ impl BoardPosition {
         Vec3::new(x, y, z)
     }
 
+    pub fn to_scaled_translation(self, scale: &BoardScale) -> Vec3 {
+        self.to_translation() * scale.0
+    }
+
+    // e.g. "e4", or None if off the board
+    pub fn to_algebraic(self) -> Option<String> {
+        self.is_in_bounds()
+            .then(|| format!("{}{}", (b'a' + self.col as u8) as char, self.row + 1))
+    }
+
+    // Reads a square name like "e4" (or "E4"), None if it isn't one
+    pub fn from_algebraic(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        let file = chars.next()?.to_ascii_lowercase();
+        let rank = chars.next()?;
+        if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return None;
+        }
+        Some(Self {
+            row: rank as i8 - b'1' as i8,
+            col: file as i8 - b'a' as i8,
+        })
+    }
+
+    /// Every square on the board, from a1 to h8 going along each rank in turn (a1, b1, ..., h1, a2, ...).
+    pub fn all() -> impl Iterator<Item = Self> {
+        iter::successors(Some(Self::new()), |pos| pos.next())
+    }
+
     pub fn is_in_bounds(self) -> bool {
         (0..8).contains(&self.row) && (0..8).contains(&self.col)
     }
@@ -113,41 +343,178 @@ impl AddAssign<(i8, i8)> for BoardPosition {
     }
 }
 
+// Uniform scale applied to the whole board and all the pieces on it
+#[derive(Clone, Copy, Debug)]
+pub struct BoardScale(pub f32);
+
+impl Default for BoardScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// How the squares are laid into the board. With a gap each square is shrunk by that much (as a fraction
+/// of a square) and sits in a slightly lower bed that shows between them, like an inlaid board. The
+/// squares stay the same distance apart, so pieces stand where they always did.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoardStyle {
+    pub gap: f32,
+}
+
+impl BoardStyle {
+    /// The shape of every square, centred on its position across the board and rising up from its base.
+    pub fn square_box(&self) -> shape::Box {
+        let half = (1.0 - self.gap.clamp(0.0, 0.5)) / 2.0;
+        shape::Box {
+            min_x: -half,
+            max_x: half,
+            min_y: 0.0,
+            max_y: 0.25,
+            min_z: -half,
+            max_z: half,
+        }
+    }
+}
+
+// Under a square when there's a gap around it, reaching out to halfway to its neighbours so a click in the
+// gap still counts for the nearest square. The square is its parent.
+#[derive(Component)]
+struct SquareBed;
+
+// Whether the decorative frame and base are drawn around the squares
+#[derive(Clone, Copy, Debug)]
+pub struct ShowBoardFrame(pub bool);
+
+impl Default for ShowBoardFrame {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+// The frame around the squares, and the felt under them. Never pickable, so clicks on it count as being
+// off the board.
+#[derive(Component)]
+pub struct BoardFrame;
+
+// How far the frame reaches out from the edge of the squares, and how far the felt reaches past that
+const FRAME_WIDTH: f32 = 0.35;
+const FELT_MARGIN: f32 = 0.1;
+
+fn spawn_board_frame(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &SquaresRenderData,
+    board_scale: &BoardScale,
+    show_board_frame: &ShowBoardFrame,
+) {
+    // The squares cover -4 to 4 across the board and each way from the camera, and sit from 0.25 to 0.5 up
+    let (inner, outer) = (4.0, 4.0 + FRAME_WIDTH);
+    let rail = |min_x, max_x, min_z, max_z| shape::Box {
+        min_x,
+        max_x,
+        min_y: 0.0,
+        max_y: 0.55, // Just proud of the squares
+        min_z,
+        max_z,
+    };
+    let rails = [
+        rail(-outer, outer, inner, outer),
+        rail(-outer, outer, -outer, -inner),
+        rail(inner, outer, -inner, inner),
+        rail(-outer, -inner, -inner, inner),
+    ];
+    let felt_size = outer + FELT_MARGIN;
+    let felt = shape::Box {
+        min_x: -felt_size,
+        max_x: felt_size,
+        min_y: 0.0,
+        max_y: 0.02, // Clear of the background plane
+        min_z: -felt_size,
+        max_z: felt_size,
+    };
+
+    commands
+        .spawn_bundle(SpatialBundle {
+            visibility: Visibility {
+                is_visible: show_board_frame.0,
+            },
+            transform: Transform::from_scale(Vec3::splat(board_scale.0)),
+            ..default()
+        })
+        .insert(BoardFrame)
+        .with_children(|parent| {
+            for rail in rails {
+                parent.spawn_bundle(PbrBundle {
+                    mesh: meshes.add(Mesh::from(rail)),
+                    material: materials.frame_color.clone(),
+                    ..default()
+                });
+            }
+            parent.spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(felt)),
+                material: materials.felt_color.clone(),
+                ..default()
+            });
+        });
+}
+
 fn create_board(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     materials: Res<SquaresRenderData>,
+    board_scale: Res<BoardScale>,
+    board_style: Res<BoardStyle>,
+    show_board_frame: Res<ShowBoardFrame>,
 ) {
     // Every square on the board is the same shape - a square with some depth
-    let square_mesh = meshes.add(Mesh::from(shape::Box {
-        min_x: -0.5,
-        max_x: 0.5,
-        min_y: 0.0,
-        max_y: 0.25,
-        min_z: -0.5,
-        max_z: 0.5,
-    }));
-
-    for row in 0..8 {
-        for col in 0..8 {
-            let pos = BoardPosition { row, col };
-            let material = match pos.square_color() {
-                SquareColor::White => materials.white_color.clone(), // TODO: do we need to clone here? Creating too many handles?
-                SquareColor::Black => materials.black_color.clone(),
-            };
-            commands
-                .spawn_bundle(PbrBundle {
-                    mesh: square_mesh.clone(),
-                    material,
-                    transform: Transform::from_translation(pos.to_translation()),
-                    ..default()
-                })
-                .insert_bundle(PickableBundle::default())
-                .insert(Square)
-                .insert(pos);
+    let square_mesh = meshes.add(Mesh::from(board_style.square_box()));
+    let bed_mesh = (board_style.gap > 0.0).then(|| {
+        meshes.add(Mesh::from(shape::Box {
+            min_x: -0.5,
+            max_x: 0.5,
+            min_y: 0.0,
+            max_y: 0.2, // Below the square, so the square's always what's clicked where it's showing
+            min_z: -0.5,
+            max_z: 0.5,
+        }))
+    });
+
+    for pos in BoardPosition::all() {
+        let highlight = SquareHighlight::Plain(pos.square_color());
+        let mut square = commands.spawn_bundle(PbrBundle {
+            mesh: square_mesh.clone(),
+            material: materials.material(highlight).clone(),
+            transform: Transform::from_translation(pos.to_scaled_translation(&board_scale))
+                .with_scale(Vec3::splat(board_scale.0)),
+            ..default()
+        });
+        square
+            .insert_bundle(PickableBundle::default())
+            .insert(Square)
+            .insert(pos)
+            .insert(highlight);
+        if let Some(bed_mesh) = &bed_mesh {
+            square.with_children(|parent| {
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: bed_mesh.clone(),
+                        material: materials.frame_color.clone(),
+                        ..default()
+                    })
+                    .insert_bundle(PickableBundle::default())
+                    .insert(SquareBed);
+            });
         }
     }
 
+    spawn_board_frame(
+        &mut commands,
+        &mut meshes,
+        &materials,
+        &board_scale,
+        &show_board_frame,
+    );
+
     // Create a back plane entity. This is needed to allow clicking on something that's not the board.
     commands
         .spawn_bundle(PbrBundle {
@@ -158,7 +525,7 @@ fn create_board(
         .insert_bundle(PickableBundle::default());
 }
 
-#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn render_board(
     hovered_square: Res<HoveredSquare>,
     turn_data: Res<TurnData>,
@@ -168,99 +535,354 @@ fn render_board(
             Entity,
             &BoardPosition,
             Option<&ValidMove>,
+            Option<&SquareFlash>,
+            &mut SquareHighlight,
             &mut Handle<StandardMaterial>,
         ),
         With<Square>,
     >,
+    piece_query: Query<&BoardPosition, (With<Piece>, Without<Square>)>,
     shadow_squares: Res<ShadowSquares>,
+    game_state: Res<GameState>,
+    added_valid_moves: Query<(), Added<ValidMove>>,
+    removed_valid_moves: RemovedComponents<ValidMove>,
+    moved_pieces: Query<(), (With<Piece>, Changed<BoardPosition>)>,
+    show_move_hints: Res<ShowMoveHints>,
+    added_flashes: Query<(), Added<SquareFlash>>,
+    removed_flashes: RemovedComponents<SquareFlash>,
+    coach_hints: Res<CoachHints>,
 ) {
-    let piece_pos = turn_data.move_piece.and_then(|piece_ent| {
-        square_query
-            .get_component::<BoardPosition>(piece_ent)
-            .ok()
-            .copied()
-    });
+    // Nothing to recolor on an idle frame
+    let changed = hovered_square.is_changed()
+        || turn_data.is_changed()
+        || shadow_squares.is_changed()
+        || game_state.is_changed()
+        || !added_valid_moves.is_empty()
+        || removed_valid_moves.iter().next().is_some()
+        || !moved_pieces.is_empty() // The selected piece's square moves with it
+        || show_move_hints.is_changed()
+        || !added_flashes.is_empty()
+        || removed_flashes.iter().next().is_some()
+        || coach_hints.is_changed();
+    if !changed {
+        return;
+    }
 
-    for (entity, pos, valid_move, mut material) in &mut square_query {
-        if Some(*pos) == piece_pos {
-            *material = materials.selected_color.clone();
-        } else if Some(entity) == hovered_square.entity {
-            *material = materials.hovered_color.clone();
-        } else if valid_move.is_some() {
-            *material = materials.valid_move_color.clone();
-        } else if shadow_squares.0.contains(pos) {
-            *material = materials.shadow_color.clone();
-        } else {
-            match pos.square_color() {
-                SquareColor::White => *material = materials.white_color.clone(), // TODO: don't clone materials?
-                SquareColor::Black => *material = materials.black_color.clone(),
-            }
+    // The selection goes as soon as the move's made, so it doesn't follow the piece to its new square
+    let selected_pos = turn_data
+        .selected_piece()
+        .and_then(|piece_ent| piece_query.get(piece_ent).ok().copied());
+    let mate = game_state.mate_squares();
+
+    for (entity, pos, valid_move, flash, mut curr_highlight, mut material) in &mut square_query {
+        // Only while the player's still deciding, not once the move's being made. Once the game's over
+        // there's nothing to decide, so the board doesn't react at all.
+        let is_hovered = Some(entity) == hovered_square.entity && game_state.game_over.is_none();
+        let hovered = is_hovered.then(|| hover_target(&game_state, selected_pos, *pos));
+        let highlight = square_highlight(
+            *pos,
+            flash.is_some(),
+            selected_pos,
+            hovered,
+            coach_hints.threatened.contains(pos),
+            valid_move.is_some() && show_move_hints.0,
+            &shadow_squares,
+            mate.as_ref(),
+        );
+        if highlight != *curr_highlight {
+            *curr_highlight = highlight;
+            *material = materials.material(highlight).clone();
         }
     }
 }
 
+// Whether the squares a selected piece can move to are highlighted
+#[derive(Clone, Copy, Debug)]
+pub struct ShowMoveHints(pub bool);
+
+impl Default for ShowMoveHints {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+fn toggle_move_hints(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut show_move_hints: ResMut<ShowMoveHints>,
+) {
+    if keyboard_input.just_pressed(KeyCode::H) {
+        show_move_hints.0 = !show_move_hints.0;
+    }
+}
+
+fn toggle_board_frame(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut show_board_frame: ResMut<ShowBoardFrame>,
+) {
+    if keyboard_input.just_pressed(KeyCode::B) {
+        show_board_frame.0 = !show_board_frame.0;
+    }
+}
+
+fn apply_board_frame(
+    show_board_frame: Res<ShowBoardFrame>,
+    mut frame_query: Query<&mut Visibility, With<BoardFrame>>,
+) {
+    if !show_board_frame.is_changed() {
+        return;
+    }
+    for mut visibility in &mut frame_query {
+        visibility.is_visible = show_board_frame.0;
+    }
+}
+
+// The squares share their materials, so changing a material's color recolors every square using it
+fn apply_highlight_colors(
+    colors: Res<HighlightColors>,
+    render_data: Res<SquaresRenderData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !colors.is_changed() {
+        return;
+    }
+    for (handle, color) in [
+        (&render_data.selected_color, colors.selected),
+        (&render_data.valid_move_color, colors.valid_move),
+        (&render_data.hovered_color, colors.hovered),
+        (&render_data.hovered_legal_color, colors.hovered_legal),
+        (&render_data.hovered_illegal_color, colors.hovered_illegal),
+        (&render_data.shadow_color, colors.shadow),
+        (&render_data.origin_color, colors.shadow_origin),
+    ] {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = color;
+        }
+    }
+}
+
+fn change_board_scale(keyboard_input: Res<Input<KeyCode>>, mut board_scale: ResMut<BoardScale>) {
+    let step = 0.1;
+    if keyboard_input.just_pressed(KeyCode::Equals) {
+        board_scale.0 = (board_scale.0 + step).min(2.0);
+    } else if keyboard_input.just_pressed(KeyCode::Minus) {
+        board_scale.0 = (board_scale.0 - step).max(0.5);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_board_scale(
+    board_scale: Res<BoardScale>,
+    mut square_query: Query<(&BoardPosition, &mut Transform), With<Square>>,
+    mut piece_query: Query<&mut Transform, (With<Piece>, Without<Square>)>,
+    mut frame_query: Query<&mut Transform, (With<BoardFrame>, Without<Square>, Without<Piece>)>,
+) {
+    if !board_scale.is_changed() {
+        return;
+    }
+
+    for (pos, mut transform) in &mut square_query {
+        transform.translation = pos.to_scaled_translation(&board_scale);
+        transform.scale = Vec3::splat(board_scale.0);
+    }
+
+    for mut transform in &mut frame_query {
+        transform.scale = Vec3::splat(board_scale.0);
+    }
+
+    // Pieces slide to their new scaled positions in animate_pieces()
+    for mut transform in &mut piece_query {
+        transform.scale = Vec3::splat(board_scale.0);
+    }
+}
+
 #[derive(Debug, Default)]
-struct HoveredSquare {
-    entity: Option<Entity>,
+pub struct HoveredSquare {
+    pub entity: Option<Entity>,
+}
+
+/// What a click on the board is for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ClickAction {
+    Select,  // Pick a piece to move, or where to move it
+    Inspect, // Peek at where a piece could go without selecting it
+    Cancel,  // Drop the selection, by clicking the selected piece
+}
+
+/// Which mouse button does what on the board. Buttons can be shared between actions, see `action()`.
+#[derive(Clone, Copy, Debug)]
+pub struct InputBindings {
+    pub select: MouseButton,
+    pub inspect: MouseButton,
+    pub cancel: MouseButton,
+    pub debounce: Duration, // A second click of the same button on the same square within this is ignored
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            select: MouseButton::Left,
+            inspect: MouseButton::Right,
+            cancel: MouseButton::Right,
+            debounce: Duration::from_millis(150),
+        }
+    }
+}
+
+impl InputBindings {
+    /// What a click with the button does, where `on_selected` is whether it landed on the piece that's
+    /// already selected. That's the only place a cancel counts, and there it wins over everything else.
+    /// Otherwise selecting wins over inspecting when they share a button.
+    pub fn action(&self, button: MouseButton, on_selected: bool) -> Option<ClickAction> {
+        if on_selected && button == self.cancel {
+            Some(ClickAction::Cancel)
+        } else if button == self.select {
+            Some(ClickAction::Select)
+        } else if button == self.inspect {
+            Some(ClickAction::Inspect)
+        } else {
+            None
+        }
+    }
+}
+
+// The last click that counted, for telling a deliberate click from the second half of a double-click
+#[derive(Clone, Copy, Debug)]
+struct LastClick {
+    kind: MouseButton,
+    board_pos: Option<BoardPosition>,
+    time: f64, // Seconds since startup
+}
+
+impl LastClick {
+    fn is_repeated_by(&self, click: &LastClick, debounce: Duration) -> bool {
+        click.kind == self.kind
+            && click.board_pos == self.board_pos
+            && click.time - self.time < debounce.as_secs_f64()
+    }
 }
 
 #[derive(Debug)]
 pub struct ClickSquareEvent {
     pub kind: MouseButton,
     pub board_pos: Option<BoardPosition>,
+    pub shift: bool, // Shift-clicks pick squares for the analysis selection, not moves
+}
+
+// How a hover event changes what's under the cursor
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum HoverChange {
+    EnteredSquare(Entity),
+    EnteredOther, // The background plane, a piece etc
+    Left(Entity),
+}
+
+// The square under the cursor after this frame's hover changes. When several things are entered in the
+// same frame a square wins, since at grazing camera angles the background plane can get hit first.
+fn resolve_hover(hovered: Option<Entity>, changes: &[HoverChange]) -> Option<Entity> {
+    let entered_square = changes.iter().rev().find_map(|change| match change {
+        HoverChange::EnteredSquare(entity) => Some(*entity),
+        _ => None,
+    });
+    if entered_square.is_some() {
+        entered_square
+    } else if changes.contains(&HoverChange::EnteredOther) {
+        None
+    } else {
+        hovered.filter(|entity| !changes.contains(&HoverChange::Left(*entity)))
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn click_square(
     mut pick_events: EventReader<PickingEvent>,
     mouse_button_inputs: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
     squares_query: Query<&Square>,
+    bed_query: Query<&Parent, With<SquareBed>>,
     board_pos_query: Query<&BoardPosition>,
     mut hovered_square: ResMut<HoveredSquare>,
     mut click_square_events: EventWriter<ClickSquareEvent>,
+    ui_query: Query<&Interaction, With<Node>>,
+    transition_query: Query<(), With<CameraTransition>>,
+    time: Res<Time>,
+    bindings: Res<InputBindings>,
+    mut last_click: Local<Option<LastClick>>,
 ) {
-    for event in pick_events.iter() {
-        match event {
-            PickingEvent::Hover(HoverEvent::JustEntered(e)) => {
-                if squares_query.contains(*e) {
-                    hovered_square.entity = Some(*e);
-                } else {
-                    hovered_square.entity = None;
-                }
-            }
+    // The bed in the gap around a square stands in for the square itself
+    let square_of = |e: Entity| {
+        if squares_query.contains(e) {
+            Some(e)
+        } else {
+            bed_query.get(e).ok().map(|parent| parent.get())
+        }
+    };
+    let changes: Vec<_> = pick_events
+        .iter()
+        .filter_map(|event| match event {
+            PickingEvent::Hover(HoverEvent::JustEntered(e)) => Some(match square_of(*e) {
+                Some(square) => HoverChange::EnteredSquare(square),
+                None => HoverChange::EnteredOther,
+            }),
             PickingEvent::Hover(HoverEvent::JustLeft(e)) => {
-                if let Some(prev_ent) = hovered_square.entity {
-                    if *e == prev_ent {
-                        hovered_square.entity = None;
-                    }
-                }
+                Some(HoverChange::Left(square_of(*e).unwrap_or(*e)))
             }
-            _ => (),
-        }
+            _ => None,
+        })
+        .collect();
+    if !changes.is_empty() {
+        hovered_square.entity = resolve_hover(hovered_square.entity, &changes);
     }
 
     let board_pos = hovered_square
         .entity
         .and_then(|sq_ent| board_pos_query.get(sq_ent).ok().copied());
 
+    // Clicks on the UI aren't meant for the board underneath it, and while the camera's moving it's
+    // hard to say what's being clicked
+    let on_ui = ui_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if on_ui || !transition_query.is_empty() {
+        return;
+    }
+
+    let shift = keyboard_input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
     let button_kinds = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
     for kind in button_kinds {
-        if mouse_button_inputs.just_pressed(kind) {
-            click_square_events.send(ClickSquareEvent { kind, board_pos });
+        if !mouse_button_inputs.just_pressed(kind) {
+            continue;
+        }
+        let click = LastClick {
+            kind,
+            board_pos,
+            time: time.seconds_since_startup(),
+        };
+        if last_click.is_some_and(|last| last.is_repeated_by(&click, bindings.debounce)) {
+            continue;
         }
+        *last_click = Some(click);
+        click_square_events.send(ClickSquareEvent {
+            kind,
+            board_pos,
+            shift,
+        });
     }
 }
 
+// Where the last move came from and went to
 #[derive(Component, Default)]
-struct ShadowSquares(Vec<BoardPosition>);
+pub struct ShadowSquares {
+    pub source: Option<BoardPosition>,
+    pub target: Option<BoardPosition>,
+}
 
 fn leave_shadow(
     mut events: EventReader<PieceMoveEvent>,
     mut shadow_squares: ResMut<ShadowSquares>,
 ) {
     for event in events.iter() {
-        shadow_squares.0.clear();
-        shadow_squares.0.push(event.source);
-        shadow_squares.0.push(event.target);
+        shadow_squares.source = Some(event.source);
+        shadow_squares.target = Some(event.target);
     }
 }
 
@@ -269,12 +891,625 @@ pub struct BoardPlugin;
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(create_board)
-            .add_system(render_board)
+            .add_system_to_stage(CoreStage::PostUpdate, render_board) // Sees ValidMove removals from this frame
+            .init_resource::<HighlightColors>()
             .init_resource::<SquaresRenderData>()
+            .add_system(apply_highlight_colors)
             .add_system(click_square)
             .init_resource::<HoveredSquare>()
+            .init_resource::<InputBindings>()
             .add_event::<ClickSquareEvent>()
             .add_system(leave_shadow)
-            .init_resource::<ShadowSquares>();
+            .init_resource::<ShadowSquares>()
+            .add_system(flash_pinned_pieces)
+            .add_system(end_square_flashes)
+            .add_system(toggle_move_hints)
+            .init_resource::<ShowMoveHints>()
+            .add_system(toggle_board_frame)
+            .add_system(apply_board_frame)
+            .init_resource::<ShowBoardFrame>()
+            .add_system(change_board_scale)
+            .add_system(apply_board_scale)
+            .init_resource::<BoardScale>()
+            .init_resource::<BoardStyle>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bevy::input::{mouse::MouseButtonInput, ButtonState};
+    use bevy_mod_picking::PickableMesh;
+
+    use super::*;
+    use crate::{
+        game::TurnPhase,
+        notation,
+        test_app::{self, square},
+    };
+
+    #[test]
+    fn scaled_translation_is_the_base_scaled() {
+        for scale in [0.5, 1.0, 1.5, 2.0] {
+            for pos in [
+                BoardPosition::new(),
+                BoardPosition { row: 3, col: 6 },
+                BoardPosition { row: 7, col: 7 },
+            ] {
+                let scaled = pos.to_scaled_translation(&BoardScale(scale));
+                assert_eq!(scaled, pos.to_translation() * scale);
+            }
+        }
+    }
+
+    #[test]
+    fn algebraic_names_of_squares() {
+        assert_eq!(BoardPosition::new().to_algebraic().as_deref(), Some("a1"));
+        assert_eq!(
+            BoardPosition { row: 7, col: 7 }.to_algebraic().as_deref(),
+            Some("h8")
+        );
+        assert_eq!(
+            BoardPosition { row: 3, col: 4 }.to_algebraic().as_deref(),
+            Some("e4")
+        );
+        for pos in [
+            BoardPosition { row: -1, col: 0 },
+            BoardPosition { row: 0, col: 8 },
+            BoardPosition { row: 8, col: 7 },
+        ] {
+            assert_eq!(pos.to_algebraic(), None);
+        }
+    }
+
+    #[test]
+    fn parsing_square_names() {
+        assert_eq!(
+            BoardPosition::from_algebraic("e4"),
+            Some(BoardPosition { row: 3, col: 4 })
+        );
+        assert_eq!(
+            BoardPosition::from_algebraic("E4"),
+            Some(BoardPosition { row: 3, col: 4 })
+        );
+        assert_eq!(
+            BoardPosition::from_algebraic("a1"),
+            Some(BoardPosition::new())
+        );
+        assert_eq!(
+            BoardPosition::from_algebraic("h8"),
+            Some(BoardPosition { row: 7, col: 7 })
+        );
+        for s in ["", "e", "e0", "e9", "i9", "i4", "4e", "e44", " e4", "ee"] {
+            assert_eq!(BoardPosition::from_algebraic(s), None, "{s:?}");
+        }
+
+        // Every square's name reads back as the same square
+        for pos in BoardPosition::all() {
+            let name = pos.to_algebraic().unwrap();
+            assert_eq!(BoardPosition::from_algebraic(&name), Some(pos));
+        }
+    }
+
+    #[test]
+    fn square_highlight_priority() {
+        let e2 = BoardPosition { row: 1, col: 4 };
+        let e4 = BoardPosition { row: 3, col: 4 };
+        let shadow_squares = ShadowSquares {
+            source: Some(e2),
+            target: Some(e4),
+        };
+        let highlight = |pos, selected_pos, hovered, valid_move| {
+            square_highlight(
+                pos,
+                false,
+                selected_pos,
+                hovered,
+                false,
+                valid_move,
+                &shadow_squares,
+                None,
+            )
+        };
+
+        // The last move's origin and destination are told apart, unless something more important is shown
+        assert_eq!(
+            highlight(e2, None, None, false),
+            SquareHighlight::LastMoveOrigin
+        );
+        assert_eq!(
+            highlight(e4, None, None, false),
+            SquareHighlight::LastMoveTarget
+        );
+        assert_eq!(
+            highlight(e4, Some(e4), None, false),
+            SquareHighlight::Selected
+        );
+        assert_eq!(highlight(e2, None, None, true), SquareHighlight::ValidMove);
+        assert_eq!(
+            highlight(e2, None, Some(HoverTarget::Legal), true),
+            SquareHighlight::Hovered(HoverTarget::Legal)
+        );
+        assert_eq!(
+            highlight(BoardPosition::new(), None, None, false),
+            SquareHighlight::Plain(SquareColor::Black)
+        );
+        assert_eq!(
+            square_highlight(
+                e4,
+                true,
+                Some(e4),
+                None,
+                false,
+                false,
+                &shadow_squares,
+                None
+            ),
+            SquareHighlight::Flash
+        );
+    }
+
+    #[test]
+    fn hover_prefers_a_square_over_the_background() {
+        let square = Entity::from_raw(1);
+        let other_square = Entity::from_raw(2);
+        let background = Entity::from_raw(3);
+
+        // Entered in the same frame, whichever order they come in
+        let both = [
+            HoverChange::EnteredOther,
+            HoverChange::EnteredSquare(square),
+        ];
+        assert_eq!(resolve_hover(None, &both), Some(square));
+        let both = [
+            HoverChange::EnteredSquare(square),
+            HoverChange::EnteredOther,
+        ];
+        assert_eq!(resolve_hover(None, &both), Some(square));
+
+        // Only the background means being off the board
+        assert_eq!(
+            resolve_hover(Some(square), &[HoverChange::EnteredOther]),
+            None
+        );
+
+        // Moving between squares, or leaving something else, keeps the right one
+        let moved = [
+            HoverChange::Left(square),
+            HoverChange::EnteredSquare(other_square),
+        ];
+        assert_eq!(resolve_hover(Some(square), &moved), Some(other_square));
+        assert_eq!(
+            resolve_hover(Some(square), &[HoverChange::Left(background)]),
+            Some(square)
+        );
+        assert_eq!(
+            resolve_hover(Some(square), &[HoverChange::Left(square)]),
+            None
+        );
+    }
+
+    // The squares whose material was written, as seen at the end of each frame
+    #[derive(Default)]
+    struct MaterialWrites(Vec<BoardPosition>);
+
+    fn record_material_writes(
+        mut writes: ResMut<MaterialWrites>,
+        square_query: Query<&BoardPosition, (With<Square>, Changed<Handle<StandardMaterial>>)>,
+    ) {
+        writes.0.extend(square_query.iter());
+    }
+
+    fn material_writes_app() -> App {
+        let mut app = test_app::board_app();
+        app.init_resource::<MaterialWrites>()
+            .add_system_to_stage(CoreStage::Last, record_material_writes);
+        test_app::update(&mut app);
+        app.world.resource_mut::<MaterialWrites>().0.clear();
+        app
+    }
+
+    #[test]
+    fn only_squares_whose_highlight_changes_get_a_new_material() {
+        let mut app = material_writes_app();
+        test_app::click(&mut app, MouseButton::Left, "g1");
+        test_app::update(&mut app);
+
+        let mut written: Vec<_> = app
+            .world
+            .resource::<MaterialWrites>()
+            .0
+            .iter()
+            .map(|pos| pos.to_algebraic().unwrap())
+            .collect();
+        written.sort();
+        assert_eq!(written, ["f3", "g1", "h3"]);
+    }
+
+    #[test]
+    fn idle_frames_write_no_materials() {
+        let mut app = material_writes_app();
+        for _ in 0..3 {
+            test_app::update(&mut app);
+        }
+        assert!(app.world.resource::<MaterialWrites>().0.is_empty());
+
+        // A selection made after idling still shows up
+        test_app::click(&mut app, MouseButton::Left, "b1");
+        test_app::update(&mut app);
+        assert_eq!(app.world.resource::<MaterialWrites>().0.len(), 3);
+        app.world.resource_mut::<MaterialWrites>().0.clear();
+        for _ in 0..3 {
+            test_app::update(&mut app);
+        }
+        assert!(app.world.resource::<MaterialWrites>().0.is_empty());
+    }
+
+    fn flashing_squares(app: &mut App) -> Vec<String> {
+        app.world
+            .query_filtered::<&BoardPosition, With<SquareFlash>>()
+            .iter(&app.world)
+            .map(|pos| pos.to_algebraic().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn moving_a_pinned_piece_flashes_its_square() {
+        let mut app = test_app::board_app();
+        test_app::load(&mut app, "4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1");
+        test_app::click(&mut app, MouseButton::Left, "e2");
+        test_app::update(&mut app);
+        test_app::click(&mut app, MouseButton::Left, "c3");
+        test_app::update(&mut app);
+        assert_eq!(flashing_squares(&mut app), ["e2"]);
+        assert_eq!(app.world.resource::<TurnData>().selected_piece(), None);
+
+        // Other illegal moves don't
+        let mut app = test_app::board_app();
+        test_app::load(&mut app, "4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1");
+        test_app::click(&mut app, MouseButton::Left, "e1");
+        test_app::update(&mut app);
+        test_app::click(&mut app, MouseButton::Left, "e3");
+        test_app::update(&mut app);
+        assert!(flashing_squares(&mut app).is_empty());
+    }
+
+    fn highlight_at(app: &mut App, name: &str) -> SquareHighlight {
+        let square = square(name);
+        app.world
+            .query_filtered::<(&BoardPosition, &SquareHighlight), With<Square>>()
+            .iter(&app.world)
+            .find(|(pos, _)| **pos == square)
+            .map(|(_, highlight)| *highlight)
+            .unwrap()
+    }
+
+    #[test]
+    fn back_rank_mate_highlights_the_rook_and_king() {
+        let mut app = test_app::board_app();
+        test_app::load(&mut app, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        test_app::play(&mut app, &["a1a8"]);
+
+        let mate = app.world.resource::<GameState>().mate_squares().unwrap();
+        assert_eq!(mate.attackers, [square("a8")]);
+        assert_eq!(highlight_at(&mut app, "a8"), SquareHighlight::MatingPiece);
+        assert_eq!(highlight_at(&mut app, "g8"), SquareHighlight::MatedKing);
+        assert_eq!(
+            highlight_at(&mut app, "b8"),
+            SquareHighlight::Plain(SquareColor::Black)
+        );
+    }
+
+    #[test]
+    fn repeated_clicks_within_the_debounce() {
+        let e2 = BoardPosition::from_algebraic("e2");
+        let click = |kind, board_pos, time| LastClick {
+            kind,
+            board_pos,
+            time,
+        };
+        let debounce = Duration::from_millis(150);
+        let first = click(MouseButton::Left, e2, 1.0);
+        assert!(first.is_repeated_by(&click(MouseButton::Left, e2, 1.1), debounce));
+        assert!(!first.is_repeated_by(&click(MouseButton::Left, e2, 1.2), debounce));
+        assert!(!first.is_repeated_by(&click(MouseButton::Right, e2, 1.1), debounce));
+        let e3 = BoardPosition::from_algebraic("e3");
+        assert!(!first.is_repeated_by(&click(MouseButton::Left, e3, 1.1), debounce));
+    }
+
+    // Clicks the left button on a square twice in quick succession, returning how many clicks counted
+    fn double_click(debounce: Duration) -> usize {
+        let mut app = test_app::board_app();
+        app.insert_resource(InputBindings {
+            debounce,
+            ..default()
+        });
+        let e2 = square("e2");
+        let square = app
+            .world
+            .query_filtered::<(Entity, &BoardPosition), With<Square>>()
+            .iter(&app.world)
+            .find(|(_, pos)| **pos == e2)
+            .map(|(entity, _)| entity);
+        app.world.resource_mut::<HoveredSquare>().entity = square;
+
+        let mut reader = app
+            .world
+            .resource::<Events<ClickSquareEvent>>()
+            .get_reader();
+        let mut clicks = 0;
+        for state in [
+            ButtonState::Pressed,
+            ButtonState::Released,
+            ButtonState::Pressed,
+            ButtonState::Released,
+        ] {
+            app.world.send_event(MouseButtonInput {
+                button: MouseButton::Left,
+                state,
+            });
+            test_app::update(&mut app);
+            let events = app.world.resource::<Events<ClickSquareEvent>>();
+            clicks += reader.iter(events).count();
+        }
+        clicks
+    }
+
+    #[test]
+    fn double_click_counts_once() {
+        assert_eq!(double_click(Duration::from_secs(10)), 1);
+        assert_eq!(double_click(Duration::ZERO), 2);
+    }
+
+    #[test]
+    fn all_squares_in_order() {
+        let squares: Vec<BoardPosition> = BoardPosition::all().collect();
+        assert_eq!(squares.len(), 64);
+        assert!(squares.iter().all(|pos| pos.is_in_bounds()));
+        let unique: HashSet<(i8, i8)> = squares.iter().map(|pos| (pos.row, pos.col)).collect();
+        assert_eq!(unique.len(), 64);
+
+        // Along each rank from a1, so the index is row * 8 + col
+        for (i, pos) in squares.iter().enumerate() {
+            assert_eq!((pos.row as usize, pos.col as usize), (i / 8, i % 8));
+        }
+        let names: Vec<String> = squares[6..10]
+            .iter()
+            .map(|pos| pos.to_algebraic().unwrap())
+            .collect();
+        assert_eq!(names, ["g1", "h1", "a2", "b2"]);
+        assert_eq!(squares[63].next(), None);
+    }
+
+    #[test]
+    fn frame_is_never_pickable() {
+        let mut app = test_app::board_app();
+        let squares: Vec<Entity> = app
+            .world
+            .query_filtered::<Entity, With<Square>>()
+            .iter(&app.world)
+            .collect();
+        let frames: Vec<(Entity, &Children)> = app
+            .world
+            .query_filtered::<(Entity, &Children), With<BoardFrame>>()
+            .iter(&app.world)
+            .collect();
+        assert_eq!(frames.len(), 1);
+        let (frame, children) = frames[0];
+        assert_eq!(children.len(), 5); // Four rails and the felt
+
+        let pickable = |entity| app.world.get::<PickableMesh>(entity).is_some();
+        assert!(!pickable(frame));
+        assert!(children.iter().all(|&child| !pickable(child)));
+
+        // Unlike the squares
+        assert_eq!(squares.len(), 64);
+        assert!(squares.into_iter().all(pickable));
+    }
+
+    #[test]
+    fn frame_can_be_hidden() {
+        let mut app = test_app::board_app();
+        let visible = |app: &mut App| {
+            app.world
+                .query_filtered::<&Visibility, With<BoardFrame>>()
+                .single(&app.world)
+                .is_visible
+        };
+        assert!(visible(&mut app));
+        test_app::press(&mut app, KeyCode::B);
+        assert!(!visible(&mut app));
+        test_app::press(&mut app, KeyCode::B);
+        assert!(visible(&mut app));
+    }
+
+    #[test]
+    fn hovered_square_as_a_target() {
+        let game_state =
+            notation::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(
+            hover_target(&game_state, None, square("e4")),
+            HoverTarget::NoSelection
+        );
+
+        let e2 = Some(square("e2"));
+        assert_eq!(
+            hover_target(&game_state, e2, square("e4")),
+            HoverTarget::Legal
+        );
+        assert_eq!(
+            hover_target(&game_state, e2, square("e5")),
+            HoverTarget::Illegal
+        );
+        assert_eq!(
+            hover_target(&game_state, e2, square("e7")),
+            HoverTarget::Illegal
+        );
+        // Clicking another of the player's pieces selects it instead
+        assert_eq!(
+            hover_target(&game_state, e2, square("d2")),
+            HoverTarget::NoSelection
+        );
+
+        // Except when it's the king castling onto its own rook
+        let game_state = notation::from_fen("4k3/8/8/8/8/8/8/5KR1 w K - 0 1").unwrap();
+        assert_eq!(
+            hover_target(&game_state, Some(square("f1")), square("g1")),
+            HoverTarget::Legal
+        );
+    }
+
+    #[test]
+    fn background_is_unlit() {
+        let app = test_app::board_app();
+        let render_data = app.world.resource::<SquaresRenderData>();
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        let unlit = |handle: &Handle<StandardMaterial>| materials.get(handle).unwrap().unlit;
+        assert!(unlit(&render_data.background_color));
+        // The board itself still takes the light
+        assert!(!unlit(&render_data.white_color));
+        assert!(!unlit(&render_data.black_color));
+    }
+
+    #[test]
+    fn selection_is_gone_while_the_move_is_animated() {
+        let mut app = test_app::board_app();
+        test_app::load(
+            &mut app,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        );
+        test_app::click(&mut app, MouseButton::Left, "e2");
+        test_app::update(&mut app);
+        assert_eq!(highlight_at(&mut app, "e2"), SquareHighlight::Selected);
+
+        // Clicked without finishing the animation it starts
+        app.world.send_event(ClickSquareEvent {
+            kind: MouseButton::Left,
+            board_pos: BoardPosition::from_algebraic("e4"),
+            shift: false,
+        });
+        for _ in 0..3 {
+            app.update();
+            assert_eq!(
+                app.world.resource::<TurnData>().phase(),
+                TurnPhase::AnimateMove
+            );
+            let highlights: Vec<SquareHighlight> = app
+                .world
+                .query_filtered::<&SquareHighlight, With<Square>>()
+                .iter(&app.world)
+                .copied()
+                .collect();
+            assert!(!highlights.contains(&SquareHighlight::Selected));
+            assert!(!highlights.contains(&SquareHighlight::ValidMove));
+        }
+        assert_eq!(
+            highlight_at(&mut app, "e2"),
+            SquareHighlight::LastMoveOrigin
+        );
+        assert_eq!(
+            highlight_at(&mut app, "e4"),
+            SquareHighlight::LastMoveTarget
+        );
+    }
+
+    fn square_color(app: &mut App, name: &str) -> Color {
+        let square = square(name);
+        let handle = app
+            .world
+            .query_filtered::<(&BoardPosition, &Handle<StandardMaterial>), With<Square>>()
+            .iter(&app.world)
+            .find(|(pos, _)| **pos == square)
+            .map(|(_, handle)| handle.clone())
+            .unwrap();
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        materials.get(&handle).unwrap().base_color
+    }
+
+    #[test]
+    fn changing_a_highlight_color_recolors_its_material() {
+        let mut app = test_app::board_app();
+        test_app::click(&mut app, MouseButton::Left, "e2");
+        test_app::update(&mut app);
+        let defaults = HighlightColors::default();
+        assert_eq!(square_color(&mut app, "e2"), defaults.selected);
+        assert_eq!(square_color(&mut app, "e4"), defaults.valid_move);
+
+        let blue = Color::rgb(0.1, 0.2, 0.9);
+        app.world.resource_mut::<HighlightColors>().selected = blue;
+        test_app::update(&mut app);
+        let selected = app
+            .world
+            .resource::<SquaresRenderData>()
+            .selected_color
+            .clone();
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        assert_eq!(materials.get(&selected).unwrap().base_color, blue);
+        assert_eq!(square_color(&mut app, "e2"), blue);
+        // The others stay as they were
+        assert_eq!(square_color(&mut app, "e4"), defaults.valid_move);
+        assert_eq!(square_color(&mut app, "a1"), Color::rgb(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn gap_spaces_the_squares_edges_apart() {
+        let style = BoardStyle { gap: 0.1 };
+        let square = style.square_box();
+        assert!((square.max_x - square.min_x - 0.9).abs() < 1e-6);
+        assert!((square.max_z - square.min_z - 0.9).abs() < 1e-6);
+
+        // Squares are still a whole square apart, so the gap is what's between their edges
+        let scale = BoardScale(2.0);
+        let at = |name| {
+            BoardPosition::from_algebraic(name)
+                .unwrap()
+                .to_scaled_translation(&scale)
+        };
+        let width = (square.max_x - square.min_x) * scale.0;
+        let depth = (square.max_z - square.min_z) * scale.0;
+        let across = (at("f4") - at("e4")).x.abs() - width;
+        let up = (at("e5") - at("e4")).z.abs() - depth;
+        assert!((across - 0.1 * scale.0).abs() < 1e-5, "{across}");
+        assert!((up - 0.1 * scale.0).abs() < 1e-5, "{up}");
+
+        // Flush without one, and never more than half a square
+        let flush = BoardStyle::default().square_box();
+        assert_eq!((flush.min_x, flush.max_x), (-0.5, 0.5));
+        let wide = BoardStyle { gap: 2.0 }.square_box();
+        assert_eq!((wide.min_x, wide.max_x), (-0.25, 0.25));
+    }
+
+    #[test]
+    fn hovering_the_gap_hovers_the_nearest_square() {
+        let mut app = test_app::board_app_with(|app| {
+            app.insert_resource(BoardStyle { gap: 0.1 });
+        });
+        let beds: Vec<(Entity, Entity)> = app
+            .world
+            .query_filtered::<(Entity, &Parent), With<SquareBed>>()
+            .iter(&app.world)
+            .map(|(bed, parent)| (bed, parent.get()))
+            .collect();
+        assert_eq!(beds.len(), 64);
+        let (bed, square) = beds[0];
+        assert!(app.world.get::<Square>(square).is_some());
+
+        let hover = |app: &mut App, event| {
+            app.world.send_event(PickingEvent::Hover(event));
+            test_app::update(app);
+            app.world.resource::<HoveredSquare>().entity
+        };
+        assert_eq!(hover(&mut app, HoverEvent::JustEntered(bed)), Some(square));
+        assert_eq!(hover(&mut app, HoverEvent::JustLeft(bed)), None);
+
+        // Without a gap there are no beds
+        let mut app = test_app::board_app();
+        let beds = app
+            .world
+            .query_filtered::<(), With<SquareBed>>()
+            .iter(&app.world)
+            .count();
+        assert_eq!(beds, 0);
     }
 }
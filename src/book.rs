@@ -0,0 +1,142 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+
+use crate::{
+    game::{GameState, Move},
+    notation::{fen_board, parse_san},
+};
+
+/// Moves worth playing in known positions, each weighted by how often it should be picked.
+///
+/// Books are text with one position per line: `<FEN> ; <SAN> <weight> ; <SAN> <weight> ...`. Only the
+/// piece placement and side to move fields of the FEN are used, so the rest may be left off. A missing
+/// weight counts as 1. Blank lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct OpeningBook {
+    positions: HashMap<String, Vec<(String, u32)>>, // Keyed by fen_board()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BookError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl FromStr for OpeningBook {
+    type Err = BookError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut book = Self::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |reason| BookError {
+                line: i + 1,
+                reason,
+            };
+
+            let mut parts = line.split(';');
+            let fen: Vec<_> = parts
+                .next()
+                .unwrap_or_default()
+                .split_whitespace()
+                .take(2)
+                .collect();
+            if fen.len() < 2 {
+                return Err(err("position needs at least a board and side to move"));
+            }
+
+            let mut moves = Vec::new();
+            for part in parts {
+                let mut fields = part.split_whitespace();
+                let san = fields.next().ok_or_else(|| err("missing move"))?;
+                let weight = match fields.next() {
+                    Some(w) => w.parse().map_err(|_| err("invalid weight"))?,
+                    None => 1,
+                };
+                if fields.next().is_some() {
+                    return Err(err("expected only a move and a weight"));
+                }
+                moves.push((san.to_string(), weight));
+            }
+            if moves.is_empty() {
+                return Err(err("no moves for position"));
+            }
+
+            book.positions
+                .entry(fen.join(" "))
+                .or_default()
+                .extend(moves);
+        }
+        Ok(book)
+    }
+}
+
+impl OpeningBook {
+    /// Picks one of the book moves for the position at random, by weight. None if the position isn't in
+    /// the book or none of its moves are legal.
+    pub fn pick(&self, game_state: &GameState, rng: &mut impl Rng) -> Option<Move> {
+        let entries = self.positions.get(&fen_board(game_state))?;
+        let moves: Vec<_> = entries
+            .iter()
+            .filter_map(|(san, weight)| Some((parse_san(game_state, san).ok()?, *weight)))
+            .collect();
+        let weights = WeightedIndex::new(moves.iter().map(|(_, weight)| *weight)).ok()?;
+        Some(moves[weights.sample(rng)].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::notation::{from_fen, parse_uci};
+
+    const BOOK: &str = "\
+# A tiny book
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w ; e4 3 ; d4 1
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 ; c5
+";
+
+    #[test]
+    fn picks_only_book_moves_in_book_positions() {
+        let book: OpeningBook = BOOK.parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut game_state =
+            from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let book_moves = [parse_uci("e2e4").unwrap(), parse_uci("d2d4").unwrap()];
+        for _ in 0..20 {
+            let mv = book.pick(&game_state, &mut rng).unwrap();
+            assert!(book_moves.contains(&mv));
+        }
+
+        game_state.make_move(book_moves[0]);
+        assert_eq!(book.pick(&game_state, &mut rng), parse_uci("c7c5"));
+        game_state.make_move(parse_uci("e7e5").unwrap());
+        assert_eq!(book.pick(&game_state, &mut rng), None);
+    }
+
+    #[test]
+    fn book_errors_give_the_line() {
+        let error = "# comment\n\n8/8/8/8/8/8/8/8 w ; e4 x"
+            .parse::<OpeningBook>()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            BookError {
+                line: 3,
+                reason: "invalid weight"
+            }
+        );
+    }
+}
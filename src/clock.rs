@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    game::{GameOver, GameState, InvalidBoard, NewGameEvent},
+    pieces::PieceColor,
+};
+
+// Only present when playing with a time control
+#[derive(Clone, Copy, Debug)]
+pub struct ChessClock {
+    pub time_per_player: Duration, // What each player starts the game with
+    pub white: Duration,
+    pub black: Duration,
+    pub warning_threshold: Duration, // Below this the clock turns red and starts ticking
+}
+
+impl ChessClock {
+    pub fn new(time_per_player: Duration) -> Self {
+        Self {
+            time_per_player,
+            white: time_per_player,
+            black: time_per_player,
+            warning_threshold: Duration::from_secs(10),
+        }
+    }
+
+    pub fn remaining(&self, player: PieceColor) -> Duration {
+        match player {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+
+    fn remaining_mut(&mut self, player: PieceColor) -> &mut Duration {
+        match player {
+            PieceColor::White => &mut self.white,
+            PieceColor::Black => &mut self.black,
+        }
+    }
+
+    pub fn is_low(&self, player: PieceColor) -> bool {
+        self.remaining(player) < self.warning_threshold
+    }
+}
+
+// Tick once per second while under the warning threshold
+fn should_tick(before: Duration, after: Duration, threshold: Duration) -> bool {
+    after < threshold && (after.as_secs() != before.as_secs() || before >= threshold)
+}
+
+fn run_clock(
+    time: Res<Time>,
+    clock: Option<ResMut<ChessClock>>,
+    mut game_state: ResMut<GameState>,
+    invalid_board: Res<InvalidBoard>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    let mut clock = match clock {
+        Some(clock) => clock,
+        None => return,
+    };
+    if game_state.game_over.is_some() || invalid_board.0.is_some() {
+        return;
+    }
+
+    // Only the player to move is on the clock, so the warning stops as soon as the turn passes
+    let player = game_state.curr_player;
+    let threshold = clock.warning_threshold;
+    let remaining = clock.remaining_mut(player);
+    let before = *remaining;
+    *remaining = remaining.saturating_sub(time.delta());
+    let after = *remaining;
+
+    if after.is_zero() {
+        game_state.game_over = Some(GameOver::Timeout(player.next()));
+    } else if should_tick(before, after, threshold) {
+        audio.play(asset_server.load("sounds/tick.wav"));
+    }
+}
+
+fn reset_clock(clock: Option<ResMut<ChessClock>>, mut new_game_events: EventReader<NewGameEvent>) {
+    if new_game_events.iter().count() == 0 {
+        return;
+    }
+    if let Some(mut clock) = clock {
+        clock.white = clock.time_per_player;
+        clock.black = clock.time_per_player;
+    }
+}
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(run_clock).add_system(reset_clock);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_on_crossing_the_threshold_then_once_a_second() {
+        let secs = Duration::from_secs_f32;
+        let threshold = secs(10.0);
+        assert!(!should_tick(secs(10.5), secs(10.1), threshold));
+        assert!(should_tick(secs(10.1), secs(9.9), threshold));
+        assert!(should_tick(secs(10.0), secs(9.99), threshold));
+        assert!(!should_tick(secs(9.9), secs(9.5), threshold));
+        assert!(should_tick(secs(9.1), secs(8.9), threshold));
+    }
+
+    #[test]
+    fn warning_is_only_for_the_player_low_on_time() {
+        let mut clock = ChessClock::new(Duration::from_secs(60));
+        clock.white = Duration::from_secs(5);
+        assert!(clock.is_low(PieceColor::White));
+        assert!(!clock.is_low(PieceColor::Black));
+    }
+}
@@ -0,0 +1,279 @@
+use bevy::prelude::*;
+
+use crate::{
+    ai::PieceValues,
+    board::BoardPosition,
+    game::{
+        ConfirmMoveEvent, GameState, Move, MoveMadeEvent, NewGameEvent, PlayerConfig, PlayerKind,
+        RequestMoveEvent, TurnData, UndoEvent,
+    },
+};
+
+// Teaching aids for someone playing against the AI, toggled with T
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CoachMode(pub bool);
+
+// The hints currently on the board
+#[derive(Debug, Default)]
+pub struct CoachHints {
+    pub threatened: Vec<BoardPosition>, // The player's pieces that the AI's last move attacks
+}
+
+// A move held back by the beginner guard until the player says whether they really meant it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GuardedMove(pub Option<Move>);
+
+/// Whether the move leaves the piece making it where the opponent can take it and come out more than a
+/// pawn ahead, counting whatever the move itself took and the player taking back. Only looks one capture
+/// and one recapture deep.
+pub fn loses_material(game_state: &GameState, mv: Move, values: &PieceValues) -> bool {
+    let won = game_state
+        .get(mv.to)
+        .filter(|piece| piece.color != game_state.curr_player)
+        .map_or(0, |piece| values.value(piece.kind));
+
+    let mut after = game_state.clone();
+    after.make_move(mv);
+    let mover = match after.get(mv.to) {
+        Some(piece) => piece,
+        None => return false, // e.g. blown up in Atomic
+    };
+
+    let lost = after
+        .legal_moves()
+        .into_iter()
+        .filter(|reply| reply.to == mv.to)
+        .map(|capture| {
+            let mut after_capture = after.clone();
+            after_capture.make_move(capture);
+            let recaptured = match after_capture.get(mv.to) {
+                Some(taker)
+                    if after_capture
+                        .legal_moves()
+                        .iter()
+                        .any(|mv2| mv2.to == mv.to) =>
+                {
+                    values.value(taker.kind)
+                }
+                _ => 0,
+            };
+            values.value(mover.kind) - recaptured
+        })
+        .max()
+        .unwrap_or(0);
+    lost - won > values.pawn
+}
+
+fn toggle_coach_mode(keyboard_input: Res<Input<KeyCode>>, mut coach_mode: ResMut<CoachMode>) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        coach_mode.0 = !coach_mode.0;
+    }
+}
+
+// After the AI moves, points out which of the player's pieces the moved piece is now attacking. The
+// hint goes again as soon as the player picks a piece to move.
+#[allow(clippy::too_many_arguments)]
+fn show_threats(
+    coach_mode: Res<CoachMode>,
+    players: Res<PlayerConfig>,
+    game_state: Res<GameState>,
+    turn_data: Res<TurnData>,
+    mut hints: ResMut<CoachHints>,
+    mut move_made_events: EventReader<MoveMadeEvent>,
+    mut undo_events: EventReader<UndoEvent>,
+    mut new_game_events: EventReader<NewGameEvent>,
+) {
+    let rewound = undo_events.iter().count() + new_game_events.iter().count() > 0;
+    let picking_move = turn_data.selected_piece().is_some();
+    let stale = rewound || !coach_mode.0 || picking_move;
+    if stale && !hints.threatened.is_empty() {
+        hints.threatened.clear();
+    }
+
+    for ev in move_made_events.iter() {
+        let by_ai_against_human = players.get(ev.piece.color) == PlayerKind::Ai
+            && players.get(ev.piece.color.next()) == PlayerKind::Human;
+        hints.threatened = if coach_mode.0 && by_ai_against_human {
+            game_state.threatened_by(ev.to)
+        } else {
+            Vec::new()
+        };
+    }
+}
+
+// Plays the guarded move once the player confirms it with Y, or forgets it on N or Escape. Picking
+// another move, or the game moving on, forgets it too.
+#[allow(clippy::too_many_arguments)]
+fn confirm_guarded_move(
+    keyboard_input: Res<Input<KeyCode>>,
+    turn_data: Res<TurnData>,
+    mut guarded_move: ResMut<GuardedMove>,
+    mut confirm_move_events: EventReader<ConfirmMoveEvent>,
+    mut move_made_events: EventReader<MoveMadeEvent>,
+    mut undo_events: EventReader<UndoEvent>,
+    mut new_game_events: EventReader<NewGameEvent>,
+    mut request_move_events: EventWriter<RequestMoveEvent>,
+) {
+    let moved_on = move_made_events.iter().count()
+        + undo_events.iter().count()
+        + new_game_events.iter().count()
+        > 0;
+    if moved_on || turn_data.move_piece.is_some() {
+        guarded_move.0 = None;
+    }
+    if let Some(ev) = confirm_move_events.iter().last() {
+        guarded_move.0 = Some(ev.0);
+    }
+
+    let mv = match guarded_move.0 {
+        Some(mv) => mv,
+        None => return,
+    };
+    if keyboard_input.just_pressed(KeyCode::Y) {
+        request_move_events.send(RequestMoveEvent(mv));
+        guarded_move.0 = None;
+    } else if keyboard_input.any_just_pressed([KeyCode::N, KeyCode::Escape]) {
+        guarded_move.0 = None;
+    }
+}
+
+pub struct CoachPlugin;
+
+impl Plugin for CoachPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CoachMode>()
+            .init_resource::<CoachHints>()
+            .init_resource::<GuardedMove>()
+            .add_system(confirm_guarded_move)
+            .add_system(toggle_coach_mode)
+            .add_system(show_threats.after(toggle_coach_mode));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        notation,
+        pieces::{Piece, PieceColor, PieceKind},
+        test_app::{self, board_app, square},
+    };
+
+    #[test]
+    fn ai_move_threatening_the_queen_flags_it() {
+        let mut app = board_app();
+        app.insert_resource(CoachMode(true))
+            .insert_resource(PlayerConfig {
+                white: PlayerKind::Human,
+                black: PlayerKind::Ai,
+            });
+        test_app::load(
+            &mut app,
+            "rnbqkbnr/pppppppp/8/8/3Q4/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1",
+        );
+        test_app::play(&mut app, &["b8c6"]);
+        assert_eq!(
+            app.world.resource::<CoachHints>().threatened,
+            [square("d4")]
+        );
+
+        // Gone once the player starts on their move
+        test_app::click(&mut app, MouseButton::Left, "d4");
+        test_app::update(&mut app);
+        assert!(app.world.resource::<CoachHints>().threatened.is_empty());
+    }
+
+    #[test]
+    fn no_threats_flagged_without_coach_mode() {
+        let mut app = board_app();
+        app.insert_resource(PlayerConfig {
+            white: PlayerKind::Human,
+            black: PlayerKind::Ai,
+        });
+        test_app::load(
+            &mut app,
+            "rnbqkbnr/pppppppp/8/8/3Q4/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1",
+        );
+        test_app::play(&mut app, &["b8c6"]);
+        assert!(app.world.resource::<CoachHints>().threatened.is_empty());
+    }
+
+    // The c5 pawn takes a queen on d4 but not on d3
+    const HANGING_QUEEN: &str = "4k3/8/8/2p5/8/8/8/3QK3 w - - 0 1";
+
+    fn queen_at(app: &mut App, name: &str) -> bool {
+        let pos = square(name);
+        app.world.resource::<GameState>().get(pos)
+            == Some(Piece {
+                kind: PieceKind::Queen,
+                color: PieceColor::White,
+            })
+    }
+
+    // Both sides human, so nothing replies to the move
+    fn guard_app(coach_mode: bool) -> App {
+        let mut app = board_app();
+        app.insert_resource(CoachMode(coach_mode))
+            .insert_resource(PlayerConfig {
+                white: PlayerKind::Human,
+                black: PlayerKind::Human,
+            });
+        test_app::load(&mut app, HANGING_QUEEN);
+        app
+    }
+
+    fn click_move(app: &mut App, from: &str, to: &str) {
+        test_app::click(app, MouseButton::Left, from);
+        test_app::update(app);
+        test_app::click(app, MouseButton::Left, to);
+        for _ in 0..5 {
+            test_app::update(app);
+        }
+    }
+
+    #[test]
+    fn hanging_the_queen_loses_material() {
+        let game_state = notation::from_fen(HANGING_QUEEN).unwrap();
+        let values = PieceValues::default();
+        let mv = |uci| notation::parse_uci(uci).unwrap();
+        assert!(loses_material(&game_state, mv("d1d4"), &values));
+        assert!(!loses_material(&game_state, mv("d1d3"), &values));
+    }
+
+    #[test]
+    fn guard_holds_back_a_move_hanging_the_queen() {
+        let mut app = guard_app(true);
+
+        click_move(&mut app, "d1", "d4");
+        assert!(queen_at(&mut app, "d1"));
+        assert_eq!(
+            app.world.resource::<GuardedMove>().0,
+            Some(notation::parse_uci("d1d4").unwrap())
+        );
+
+        test_app::press(&mut app, KeyCode::Y);
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+        assert!(queen_at(&mut app, "d4"));
+        assert_eq!(app.world.resource::<GuardedMove>().0, None);
+    }
+
+    #[test]
+    fn guard_lets_a_safe_move_through() {
+        let mut app = guard_app(true);
+
+        click_move(&mut app, "d1", "d3");
+        assert!(queen_at(&mut app, "d3"));
+        assert_eq!(app.world.resource::<GuardedMove>().0, None);
+    }
+
+    #[test]
+    fn guard_is_off_without_coach_mode() {
+        let mut app = guard_app(false);
+
+        click_move(&mut app, "d1", "d4");
+        assert!(queen_at(&mut app, "d4"));
+        assert_eq!(app.world.resource::<GuardedMove>().0, None);
+    }
+}
@@ -0,0 +1,266 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    board::{BoardPosition, BoardScale},
+    game::{GameOver, GameRng, GameState},
+    pieces::PieceColor,
+};
+
+pub const CONFETTI_COUNT: usize = 150;
+pub const CONFETTI_LIFETIME: f32 = 3.0; // Seconds before each piece is cleaned up
+const GRAVITY: f32 = 4.0; // Gentler than the real thing so it drifts down rather than drops
+const DRAG: f32 = 0.8; // Fraction of the speed lost per second, like paper fluttering
+
+const CONFETTI_COLORS: [Color; 5] = [
+    Color::rgb(0.95, 0.25, 0.25),
+    Color::rgb(0.25, 0.6, 0.95),
+    Color::rgb(0.95, 0.85, 0.2),
+    Color::rgb(0.3, 0.85, 0.35),
+    Color::rgb(0.85, 0.4, 0.9),
+];
+
+/// Whether a checkmate sets off a burst of confetti over the winner's side of the board.
+#[derive(Clone, Copy, Debug)]
+pub struct ShowConfetti(pub bool);
+
+impl Default for ShowConfetti {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// One piece of confetti in flight.
+#[derive(Clone, Component, Copy, Debug, PartialEq)]
+pub struct Confetti {
+    pub velocity: Vec3,
+    pub spin: Vec3, // Axis and speed (radians per second) it tumbles at
+    pub age: f32,
+}
+
+impl Confetti {
+    /// Moves the piece on by `dt` seconds. Returns whether it's still around afterwards.
+    pub fn advance(&mut self, transform: &mut Transform, dt: f32) -> bool {
+        self.age += dt;
+        self.velocity.y -= GRAVITY * dt;
+        self.velocity *= (1.0 - DRAG * dt).max(0.0);
+        transform.translation += self.velocity * dt;
+        if self.spin != Vec3::ZERO {
+            let angle = self.spin.length() * dt;
+            transform.rotate(Quat::from_axis_angle(self.spin.normalize(), angle));
+        }
+        self.age < CONFETTI_LIFETIME
+    }
+}
+
+/// A burst of confetti thrown up from `center`, as where each piece starts, how it moves and its color
+/// (an index into the confetti colors). `scale` is the board's, which the burst is spread to fit.
+pub fn confetti_burst(
+    center: Vec3,
+    scale: f32,
+    rng: &mut impl Rng,
+) -> Vec<(Vec3, Confetti, usize)> {
+    (0..CONFETTI_COUNT)
+        .map(|_| {
+            let offset = Vec3::new(rng.gen_range(-2.0..2.0), 0.0, rng.gen_range(-0.8..0.8));
+            let velocity = Vec3::new(
+                rng.gen_range(-1.5..1.5),
+                rng.gen_range(3.0..6.0),
+                rng.gen_range(-1.5..1.5),
+            );
+            let spin = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            ) * 8.0;
+            let confetti = Confetti {
+                velocity: velocity * scale,
+                spin,
+                age: 0.0,
+            };
+            (
+                center + offset * scale,
+                confetti,
+                rng.gen_range(0..CONFETTI_COLORS.len()),
+            )
+        })
+        .collect()
+}
+
+// Above the middle of the winner's first two ranks
+fn burst_center(winner: PieceColor, board_scale: &BoardScale) -> Vec3 {
+    let row = match winner {
+        PieceColor::White => 1,
+        PieceColor::Black => 6,
+    };
+    let left = BoardPosition { row, col: 3 }.to_scaled_translation(board_scale);
+    let right = BoardPosition { row, col: 4 }.to_scaled_translation(board_scale);
+    (left + right) / 2.0 + Vec3::Y * board_scale.0
+}
+
+struct ConfettiRenderData {
+    mesh: Handle<Mesh>,
+    materials: Vec<Handle<StandardMaterial>>, // One per confetti color
+}
+
+impl FromWorld for ConfettiRenderData {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Mesh::from(shape::Quad::new(Vec2::new(0.08, 0.12))));
+
+        // Unlit and two-sided so the colors stay bright whichever way a piece has tumbled
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let materials = CONFETTI_COLORS
+            .iter()
+            .map(|color| {
+                materials.add(StandardMaterial {
+                    base_color: *color,
+                    unlit: true,
+                    double_sided: true,
+                    cull_mode: None,
+                    ..default()
+                })
+            })
+            .collect();
+
+        Self { mesh, materials }
+    }
+}
+
+// Only goes off as the game ends in checkmate, not for a game that was already over when it was loaded
+fn start_confetti(
+    mut commands: Commands,
+    mut was_over: Local<Option<bool>>,
+    show_confetti: Res<ShowConfetti>,
+    game_state: Res<GameState>,
+    board_scale: Res<BoardScale>,
+    render_data: Res<ConfettiRenderData>,
+    mut rng: ResMut<GameRng>,
+) {
+    let is_over = game_state.game_over.is_some();
+    let just_ended = *was_over == Some(false) && is_over;
+    *was_over = Some(is_over);
+    let winner = match game_state.game_over {
+        Some(GameOver::Checkmate(winner)) if just_ended => winner,
+        _ => return,
+    };
+    if !show_confetti.0 {
+        return;
+    }
+
+    let center = burst_center(winner, &board_scale);
+    for (translation, confetti, color) in confetti_burst(center, board_scale.0, rng.rng()) {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: render_data.mesh.clone(),
+                material: render_data.materials[color].clone(),
+                transform: Transform::from_translation(translation)
+                    .with_scale(Vec3::splat(board_scale.0)),
+                ..default()
+            })
+            .insert(confetti);
+    }
+}
+
+fn animate_confetti(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Confetti, &mut Transform)>,
+) {
+    for (entity, mut confetti, mut transform) in &mut query {
+        if !confetti.advance(&mut transform, time.delta_seconds()) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub struct ConfettiPlugin;
+
+impl Plugin for ConfettiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShowConfetti>()
+            .init_resource::<ConfettiRenderData>()
+            .add_system(start_confetti)
+            .add_system(animate_confetti);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::test_app::{self, game_app};
+
+    const FOOLS_MATE: [&str; 4] = ["f2f3", "e7e5", "g2g4", "d8h4"];
+
+    fn confetti_count(app: &mut App) -> usize {
+        app.world.query::<&Confetti>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn a_burst_is_the_full_count_spread_over_the_board() {
+        let center = Vec3::new(3.5, 1.0, 1.0);
+        let burst = confetti_burst(center, 2.0, &mut StdRng::seed_from_u64(1));
+        assert_eq!(burst.len(), CONFETTI_COUNT);
+        for (translation, confetti, color) in burst {
+            assert!((translation.x - center.x).abs() <= 4.0);
+            assert_eq!(translation.y, center.y);
+            assert!(confetti.velocity.y > 0.0); // Thrown upwards
+            assert_eq!(confetti.age, 0.0);
+            assert!(color < CONFETTI_COLORS.len());
+        }
+    }
+
+    #[test]
+    fn confetti_falls_and_lasts_its_lifetime() {
+        let mut confetti = Confetti {
+            velocity: Vec3::ZERO,
+            spin: Vec3::ZERO,
+            age: 0.0,
+        };
+        let mut transform = Transform::default();
+        let mut frames = 0;
+        while confetti.advance(&mut transform, 0.25) {
+            frames += 1;
+        }
+        assert_eq!(frames, 11); // Gone on the frame it turns 3 seconds old
+        assert_eq!(confetti.age, CONFETTI_LIFETIME);
+        assert!(transform.translation.y < 0.0);
+    }
+
+    #[test]
+    fn checkmate_throws_confetti_that_clears_up() {
+        let mut app = game_app();
+        app.add_plugin(ConfettiPlugin);
+        test_app::update(&mut app);
+        test_app::play(&mut app, &FOOLS_MATE[..3]);
+        assert_eq!(confetti_count(&mut app), 0);
+        test_app::play(&mut app, &FOOLS_MATE[3..]);
+        test_app::update(&mut app); // The game may have ended after the confetti looked
+        assert_eq!(confetti_count(&mut app), CONFETTI_COUNT);
+
+        // Only the once
+        test_app::update(&mut app);
+        assert_eq!(confetti_count(&mut app), CONFETTI_COUNT);
+
+        let mut clock = test_app::Clock::new(&mut app);
+        for _ in 0..10 {
+            clock.update(&mut app, CONFETTI_LIFETIME / 5.0);
+        }
+        assert_eq!(confetti_count(&mut app), 0);
+    }
+
+    #[test]
+    fn no_confetti_when_turned_off() {
+        let mut app = game_app();
+        app.add_plugin(ConfettiPlugin)
+            .insert_resource(ShowConfetti(false));
+        test_app::update(&mut app);
+        test_app::play(&mut app, &FOOLS_MATE);
+        test_app::update(&mut app);
+        assert!(app.world.resource::<GameState>().game_over.is_some());
+        assert_eq!(confetti_count(&mut app), 0);
+    }
+}
@@ -0,0 +1,147 @@
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{ai::SearchStats, game::GameState, notation::rule_state};
+
+// Whether the performance numbers are shown, toggled with F3
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShowDiagnostics(pub bool);
+
+// Whether the castling rights, en passant square and so on are shown, toggled with F4
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShowRuleState(pub bool);
+
+#[derive(Component)]
+struct DiagnosticsText;
+
+#[derive(Component)]
+struct RuleStateText;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::YELLOW,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(50.0), // Above the review text
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(DiagnosticsText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::YELLOW,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(140.0), // Above the performance numbers
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(RuleStateText);
+}
+
+fn toggle_diagnostics(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut show_diagnostics: ResMut<ShowDiagnostics>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        show_diagnostics.0 = !show_diagnostics.0;
+    }
+}
+
+fn toggle_rule_state(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut show_rule_state: ResMut<ShowRuleState>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        show_rule_state.0 = !show_rule_state.0;
+    }
+}
+
+fn update_diagnostics_ui(
+    show_diagnostics: Res<ShowDiagnostics>,
+    diagnostics: Res<Diagnostics>,
+    search_stats: Res<SearchStats>,
+    mut query: Query<&mut Text, With<DiagnosticsText>>,
+) {
+    let mut text = query.get_single_mut().unwrap();
+    if !show_diagnostics.0 {
+        if show_diagnostics.is_changed() {
+            text.sections[0].value.clear();
+        }
+        return;
+    }
+
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    let fps = average(FrameTimeDiagnosticsPlugin::FPS);
+    let frame_time = average(FrameTimeDiagnosticsPlugin::FRAME_TIME) * 1000.0; // Measured in seconds
+    text.sections[0].value = format!(
+        "FPS: {fps:.0}\nFrame: {frame_time:.1} ms\nLast AI search: {} nodes in {:.2}s",
+        search_stats.nodes,
+        search_stats.time.as_secs_f32()
+    );
+}
+
+// Only changes when a move is made (or the panel is toggled), unlike the performance numbers
+fn update_rule_state_ui(
+    show_rule_state: Res<ShowRuleState>,
+    game_state: Res<GameState>,
+    mut query: Query<&mut Text, With<RuleStateText>>,
+) {
+    if !show_rule_state.is_changed() && !game_state.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    text.sections[0].value = if show_rule_state.0 {
+        rule_state(&game_state)
+    } else {
+        String::new()
+    };
+}
+
+pub struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .init_resource::<ShowDiagnostics>()
+            .init_resource::<ShowRuleState>()
+            .init_resource::<SearchStats>()
+            .add_startup_system(setup)
+            .add_system(toggle_diagnostics)
+            .add_system(toggle_rule_state)
+            .add_system(update_diagnostics_ui.after(toggle_diagnostics))
+            .add_system(update_rule_state_ui.after(toggle_rule_state));
+    }
+}
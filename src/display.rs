@@ -0,0 +1,462 @@
+use bevy::{
+    prelude::*,
+    render::camera::Projection,
+    window::{WindowMode, WindowResized},
+};
+
+use std::f32::consts::TAU;
+
+use crate::{
+    board::BoardScale,
+    game::{PlayerConfig, PlayerKind},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct DisplaySettings {
+    pub width: f32,
+    pub height: f32,
+    pub mode: WindowMode,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            width: 1200.0,
+            height: 800.0,
+            mode: WindowMode::Windowed,
+        }
+    }
+}
+
+impl DisplaySettings {
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "Schach!".to_string(),
+            width: self.width,
+            height: self.height,
+            mode: self.mode,
+            ..default()
+        }
+    }
+}
+
+// Where the camera sits relative to the centre of the board, when the window is wide enough to see all of it
+const CAMERA_OFFSET: Vec3 = Vec3::new(0.0, 12.0, 8.0);
+
+// Half the width the camera needs to keep in view at the usual board scale: the board plus a little margin
+const FRAMED_HALF_WIDTH: f32 = 4.5;
+
+// Camera position for a window with the given aspect ratio (width / height). The camera always looks at
+// the centre of the board, but backs away along the same line when the window gets too narrow to fit
+// the board's width, since the field of view is fixed vertically. Everything is further away for a bigger
+// board, so it looks the same at any scale. A flipped board is seen from the other side.
+pub fn camera_framing(aspect_ratio: f32, fov: f32, flipped: bool, board_scale: f32) -> Transform {
+    let half_fov_x = ((fov / 2.0).tan() * aspect_ratio).atan();
+    let fit_distance = FRAMED_HALF_WIDTH / half_fov_x.tan();
+    let distance = CAMERA_OFFSET.length().max(fit_distance) * board_scale;
+    let offset = if flipped {
+        CAMERA_OFFSET * Vec3::new(1.0, 1.0, -1.0)
+    } else {
+        CAMERA_OFFSET
+    };
+    Transform::from_translation(offset.normalize() * distance).looking_at(Vec3::ZERO, Vec3::Y)
+}
+
+// Whether the board is seen from the other side to usual. When flipping, this only changes once the
+// camera has finished swinging around.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoardFlipped(pub bool);
+
+pub struct FlipBoardEvent;
+
+/// The camera swinging around the board from one side to the other. Clicks on the board are ignored
+/// until it's done.
+#[derive(Component)]
+pub struct CameraTransition {
+    pub timer: Timer,
+    pub start: Transform,
+    pub end: Transform,
+    pub flipped: bool, // What BoardFlipped becomes at the end
+}
+
+impl CameraTransition {
+    /// Where the camera is when the given fraction (0 to 1) of the way through. It circles around the
+    /// centre of the board rather than cutting straight across, easing in and out.
+    pub fn transform_at(&self, fraction: f32) -> Transform {
+        let t = fraction.clamp(0.0, 1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+
+        let angle = |pos: Vec3| pos.x.atan2(pos.z);
+        let rotation = Quat::from_rotation_y(
+            (angle(self.end.translation) - angle(self.start.translation)) * eased,
+        );
+        let (start_distance, end_distance) = (
+            self.start.translation.length(),
+            self.end.translation.length(),
+        );
+        let distance = start_distance + (end_distance - start_distance) * eased;
+        Transform {
+            translation: rotation * self.start.translation.normalize() * distance,
+            rotation: rotation * self.start.rotation,
+            scale: self.start.scale,
+        }
+    }
+}
+
+fn aspect_ratio(windows: &Windows) -> Option<f32> {
+    match windows.get_primary() {
+        Some(window) if window.height() > 0.0 => Some(window.width() / window.height()),
+        _ => None, // No window, or it's minimised
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn flip_board(
+    mut commands: Commands,
+    mut flip_board_events: EventReader<FlipBoardEvent>,
+    board_flipped: Res<BoardFlipped>,
+    board_scale: Res<BoardScale>,
+    windows: Res<Windows>,
+    camera_query: Query<
+        (Entity, &Transform, &Projection),
+        (With<Camera3d>, Without<CameraTransition>),
+    >,
+) {
+    // A flip asked for while the camera's still moving gets dropped
+    if flip_board_events.iter().count() == 0 {
+        return;
+    }
+    let aspect_ratio = match aspect_ratio(&windows) {
+        Some(aspect_ratio) => aspect_ratio,
+        None => return,
+    };
+
+    for (entity, transform, projection) in &camera_query {
+        if let Projection::Perspective(perspective) = projection {
+            let flipped = !board_flipped.0;
+            commands.entity(entity).insert(CameraTransition {
+                timer: Timer::from_seconds(0.5, false),
+                start: *transform,
+                end: camera_framing(aspect_ratio, perspective.fov, flipped, board_scale.0),
+                flipped,
+            });
+        }
+    }
+}
+
+fn run_camera_transitions(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut board_flipped: ResMut<BoardFlipped>,
+    mut camera_query: Query<(Entity, &mut Transform, &mut CameraTransition)>,
+) {
+    for (entity, mut transform, mut transition) in &mut camera_query {
+        if transition.timer.tick(time.delta()).finished() {
+            *transform = transition.end;
+            board_flipped.0 = transition.flipped;
+            commands.entity(entity).remove::<CameraTransition>();
+        } else {
+            *transform = transition.transform_at(transition.timer.percent());
+        }
+    }
+}
+
+fn toggle_fullscreen(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<DisplaySettings>) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        settings.mode = match settings.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        };
+    }
+}
+
+fn apply_display_settings(settings: Res<DisplaySettings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() || settings.is_added() {
+        return; // The window was created with the starting settings
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_mode(settings.mode);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn frame_camera(
+    windows: Res<Windows>,
+    board_flipped: Res<BoardFlipped>,
+    board_scale: Res<BoardScale>,
+    mut resized_events: EventReader<WindowResized>,
+    mut camera_query: Query<
+        (&mut Transform, &Projection),
+        (With<Camera3d>, Without<CameraTransition>),
+    >,
+    added_query: Query<(), Added<Camera3d>>,
+) {
+    let resized = resized_events.iter().count() > 0;
+    let changed = board_flipped.is_changed() || board_scale.is_changed();
+    if !resized && !changed && added_query.is_empty() {
+        return;
+    }
+
+    let aspect_ratio = match aspect_ratio(&windows) {
+        Some(aspect_ratio) => aspect_ratio,
+        None => return,
+    };
+
+    for (mut transform, projection) in &mut camera_query {
+        if let Projection::Perspective(perspective) = projection {
+            *transform = camera_framing(
+                aspect_ratio,
+                perspective.fov,
+                board_flipped.0,
+                board_scale.0,
+            );
+        }
+    }
+}
+
+/// The camera slowly circling the board while nobody's playing, e.g. AI against AI or the attract mode
+/// demo. Off while speed is zero (the default), and back to the usual view as soon as a human plays.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PresentationSpin {
+    pub speed: f32, // Radians per second, anticlockwise seen from above
+    angle: f32,     // How far round from the usual view, from 0 to a full turn
+}
+
+impl PresentationSpin {
+    pub fn new(speed: f32) -> Self {
+        Self { speed, angle: 0.0 }
+    }
+
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// Moves the camera on by `dt` seconds' worth of turning.
+    pub fn advance(&mut self, dt: f32) {
+        self.angle = (self.angle + self.speed * dt).rem_euclid(TAU);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn spin_camera(
+    time: Res<Time>,
+    mut spin: ResMut<PresentationSpin>,
+    players: Res<PlayerConfig>,
+    windows: Res<Windows>,
+    board_flipped: Res<BoardFlipped>,
+    board_scale: Res<BoardScale>,
+    mut camera_query: Query<
+        (&mut Transform, &Projection),
+        (With<Camera3d>, Without<CameraTransition>),
+    >,
+) {
+    let nobody_playing = players.white != PlayerKind::Human && players.black != PlayerKind::Human;
+    if spin.speed != 0.0 && nobody_playing {
+        spin.advance(time.delta_seconds());
+    } else if spin.angle != 0.0 {
+        spin.angle = 0.0; // Framed the usual way once more below
+    } else {
+        return;
+    }
+
+    let aspect_ratio = match aspect_ratio(&windows) {
+        Some(aspect_ratio) => aspect_ratio,
+        None => return,
+    };
+    for (mut transform, projection) in &mut camera_query {
+        if let Projection::Perspective(perspective) = projection {
+            let framing = camera_framing(
+                aspect_ratio,
+                perspective.fov,
+                board_flipped.0,
+                board_scale.0,
+            );
+            *transform = Transform::from_rotation(Quat::from_rotation_y(spin.angle)) * framing;
+        }
+    }
+}
+
+pub struct DisplayPlugin;
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DisplaySettings>()
+            .add_system(toggle_fullscreen)
+            .add_system(apply_display_settings.after(toggle_fullscreen))
+            .init_resource::<BoardFlipped>()
+            .add_event::<FlipBoardEvent>()
+            .add_system(flip_board)
+            .add_system(run_camera_transitions.after(flip_board))
+            .add_system(frame_camera.after(run_camera_transitions))
+            .init_resource::<PresentationSpin>()
+            .add_system(spin_camera.after(frame_camera));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    use super::*;
+
+    // The camera's always looking straight at the middle of the board
+    fn assert_centred(transform: Transform) {
+        let forward = transform.forward();
+        let to_centre = -transform.translation.normalize();
+        assert!(
+            forward.abs_diff_eq(to_centre, 1e-5),
+            "{forward} {to_centre}"
+        );
+    }
+
+    #[test]
+    fn camera_backs_off_for_narrow_windows() {
+        let wide = camera_framing(16.0 / 9.0, FRAC_PI_4, false, 1.0);
+        assert_centred(wide);
+        assert!((wide.translation - CAMERA_OFFSET).length() < 1e-4);
+
+        let narrow = camera_framing(0.5, FRAC_PI_4, false, 1.0);
+        assert_centred(narrow);
+        assert!(narrow.translation.length() > wide.translation.length());
+        assert!(narrow
+            .translation
+            .normalize()
+            .abs_diff_eq(CAMERA_OFFSET.normalize(), 1e-5));
+
+        let flipped = camera_framing(16.0 / 9.0, FRAC_PI_4, true, 1.0);
+        assert_centred(flipped);
+        assert!(flipped.translation.z < 0.0);
+    }
+
+    #[test]
+    fn flip_transition_reaches_the_flipped_framing() {
+        let start = camera_framing(16.0 / 9.0, FRAC_PI_4, false, 1.0);
+        let end = camera_framing(16.0 / 9.0, FRAC_PI_4, true, 1.0);
+        let transition = CameraTransition {
+            timer: Timer::from_seconds(0.5, false),
+            start,
+            end,
+            flipped: true,
+        };
+        let close = |a: Transform, b: Transform| {
+            a.translation.abs_diff_eq(b.translation, 1e-4)
+                && a.rotation.abs_diff_eq(b.rotation, 1e-4)
+        };
+        assert!(close(transition.transform_at(0.0), start));
+        assert!(close(transition.transform_at(1.0), end));
+
+        // Still looking at the board on the way round, at the same distance from it
+        let half_way = transition.transform_at(0.5);
+        assert_centred(half_way);
+        assert!((half_way.translation.length() - start.translation.length()).abs() < 1e-4);
+        assert!(!close(half_way, start) && !close(half_way, end));
+    }
+
+    #[test]
+    fn board_flips_once_the_transition_ends() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<BoardFlipped>()
+            .add_system(run_camera_transitions);
+        let end = camera_framing(16.0 / 9.0, FRAC_PI_4, true, 1.0);
+        let camera = app
+            .world
+            .spawn()
+            .insert(Transform::default())
+            .insert(CameraTransition {
+                timer: Timer::from_seconds(0.0, false),
+                start: Transform::default(),
+                end,
+                flipped: true,
+            })
+            .id();
+        app.update();
+
+        assert!(app.world.resource::<BoardFlipped>().0);
+        assert_eq!(*app.world.get::<Transform>(camera).unwrap(), end);
+        assert!(app.world.get::<CameraTransition>(camera).is_none());
+    }
+
+    #[test]
+    fn whole_board_is_in_view_at_any_size() {
+        for aspect_ratio in [0.3, 0.5, 1.0, 16.0 / 9.0, 3.0] {
+            for board_scale in [0.5, 1.0, 2.0] {
+                let framing = camera_framing(aspect_ratio, FRAC_PI_4, false, board_scale);
+                assert_centred(framing);
+
+                // Every corner of the board lands inside the field of view
+                let view = framing.compute_matrix().inverse();
+                let half_height = (FRAC_PI_4 / 2.0).tan();
+                for (x, z) in [(-4.0, -4.0), (-4.0, 4.0), (4.0, -4.0), (4.0, 4.0)] {
+                    let corner = view.transform_point3(Vec3::new(x, 0.0, z) * board_scale);
+                    let depth = -corner.z;
+                    assert!(depth > 0.0);
+                    assert!(
+                        corner.x.abs() / depth <= half_height * aspect_ratio,
+                        "{aspect_ratio} {board_scale} ({x}, {z})"
+                    );
+                    assert!(
+                        corner.y.abs() / depth <= half_height,
+                        "{aspect_ratio} {board_scale} ({x}, {z})"
+                    );
+                }
+
+                // And it looks the same whatever the scale
+                let usual = camera_framing(aspect_ratio, FRAC_PI_4, false, 1.0);
+                assert!(framing
+                    .translation
+                    .abs_diff_eq(usual.translation * board_scale, 1e-4));
+            }
+        }
+    }
+
+    #[test]
+    fn spin_advances_with_time_and_wraps() {
+        let mut spin = PresentationSpin::new(FRAC_PI_2);
+        assert_eq!(spin.angle(), 0.0);
+        spin.advance(1.0);
+        assert!((spin.angle() - FRAC_PI_2).abs() < 1e-6);
+        spin.advance(0.5);
+        assert!((spin.angle() - 3.0 * FRAC_PI_4).abs() < 1e-6);
+        // Round past the start again
+        spin.advance(2.5);
+        assert!(spin.angle().abs() < 1e-5 || (TAU - spin.angle()).abs() < 1e-5);
+
+        // Turning the other way stays within a turn too
+        let mut spin = PresentationSpin::new(-FRAC_PI_2);
+        spin.advance(1.0);
+        assert!((spin.angle() - 3.0 * FRAC_PI_2).abs() < 1e-5);
+
+        let mut spin = PresentationSpin::default();
+        spin.advance(10.0);
+        assert_eq!(spin.angle(), 0.0);
+    }
+
+    #[test]
+    fn spin_stops_once_a_human_plays() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Windows>()
+            .init_resource::<BoardFlipped>()
+            .init_resource::<BoardScale>()
+            .insert_resource(PresentationSpin::new(1.0))
+            .insert_resource(PlayerConfig {
+                white: PlayerKind::Ai,
+                black: PlayerKind::Ai,
+            })
+            .add_system(spin_camera);
+        while app.world.resource::<PresentationSpin>().angle() == 0.0 {
+            app.update();
+        }
+
+        app.insert_resource(PlayerConfig {
+            white: PlayerKind::Human,
+            black: PlayerKind::Ai,
+        });
+        app.update();
+        assert_eq!(app.world.resource::<PresentationSpin>().angle(), 0.0);
+        app.update();
+        assert_eq!(app.world.resource::<PresentationSpin>().angle(), 0.0);
+    }
+}
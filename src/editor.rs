@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{BoardPosition, ClickAction, ClickSquareEvent, InputBindings, ShadowSquares},
+    game::{GameState, StartFromBoardEvent, TurnData, ValidMove},
+    pieces::{Piece, PieceColor, PieceKind, RebuildPiecesEvent},
+    review::ReviewState,
+};
+
+// Setting up a position by hand, e.g. for a puzzle. Toggled with E: while it's on the game is paused and
+// clicking a square changes what's on it, then turning it off starts a new game from the board as it is.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Editor {
+    pub active: bool,
+    pub to_move: PieceColor, // Who moves first once editing is done, switched with Tab
+}
+
+const EDITOR_KINDS: [PieceKind; 6] = [
+    PieceKind::King,
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+    PieceKind::Pawn(false),
+];
+
+/// What clicking a square in the editor puts on it: after an empty square comes each White piece, then
+/// each Black piece, then empty again. A pawn counts as having moved unless it's on its starting rank.
+pub fn next_piece(piece: Option<Piece>, pos: BoardPosition) -> Option<Piece> {
+    let same_kind = |a: PieceKind, b: PieceKind| {
+        matches!((a, b), (PieceKind::Pawn(_), PieceKind::Pawn(_))) || a == b
+    };
+    let mut cycle = [PieceColor::White, PieceColor::Black]
+        .into_iter()
+        .flat_map(|color| EDITOR_KINDS.map(|kind| Piece { kind, color }));
+    let mut next = match piece {
+        Some(piece) => cycle
+            .skip_while(|p| p.color != piece.color || !same_kind(p.kind, piece.kind))
+            .nth(1),
+        None => cycle.next(),
+    };
+
+    if let Some(Piece {
+        kind: PieceKind::Pawn(has_moved),
+        color,
+    }) = &mut next
+    {
+        let start_row = match color {
+            PieceColor::White => 1,
+            PieceColor::Black => 6,
+        };
+        *has_moved = pos.row != start_row;
+    }
+    next
+}
+
+#[allow(clippy::too_many_arguments)]
+fn toggle_editor(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    game_state: Res<GameState>,
+    review_state: Res<ReviewState>,
+    mut turn_data: ResMut<TurnData>,
+    valid_moves_query: Query<Entity, With<ValidMove>>,
+    mut start_events: EventWriter<StartFromBoardEvent>,
+) {
+    if editor.active && keyboard_input.just_pressed(KeyCode::Tab) {
+        editor.to_move = editor.to_move.next();
+    }
+    if !keyboard_input.just_pressed(KeyCode::E) {
+        return;
+    }
+
+    if editor.active {
+        editor.active = false;
+        start_events.send(StartFromBoardEvent(editor.to_move));
+    } else if review_state.ply.is_none() && turn_data.move_target.is_none() {
+        // Drop any half-made move, the same as when entering review
+        turn_data.reset();
+        for entity in &valid_moves_query {
+            commands.entity(entity).remove::<ValidMove>();
+        }
+        *editor = Editor {
+            active: true,
+            to_move: game_state.curr_player,
+        };
+    }
+}
+
+fn edit_squares(
+    editor: Res<Editor>,
+    bindings: Res<InputBindings>,
+    mut game_state: ResMut<GameState>,
+    mut click_square_events: EventReader<ClickSquareEvent>,
+    mut shadow_squares: ResMut<ShadowSquares>,
+    mut rebuild_events: EventWriter<RebuildPiecesEvent>,
+) {
+    if !editor.active {
+        return;
+    }
+
+    let mut edited = false;
+    for ev in click_square_events.iter().filter(|ev| !ev.shift) {
+        let pos = match ev.board_pos {
+            Some(pos) if bindings.action(ev.kind, false) == Some(ClickAction::Select) => pos,
+            _ => continue,
+        };
+        let piece = next_piece(game_state.get(pos), pos);
+        game_state.set(pos, piece);
+        edited = true;
+    }
+
+    if edited {
+        // The last move doesn't mean anything on an edited board
+        shadow_squares.source = None;
+        shadow_squares.target = None;
+        rebuild_events.send(RebuildPiecesEvent(game_state.board()));
+    }
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_system(toggle_editor)
+            .add_system(edit_squares);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_app::{self, game_app};
+
+    #[test]
+    fn clicks_put_a_white_queen_on_the_board() {
+        let mut app = game_app();
+        app.add_plugin(EditorPlugin);
+        test_app::press(&mut app, KeyCode::E);
+        assert!(app.world.resource::<Editor>().active);
+
+        // An empty square goes to a King first, then a Queen
+        for _ in 0..2 {
+            test_app::click(&mut app, MouseButton::Left, "e4");
+            test_app::update(&mut app);
+        }
+        let queen = Some(Piece {
+            kind: PieceKind::Queen,
+            color: PieceColor::White,
+        });
+        assert_eq!(app.world.resource::<GameState>().board()[3][4], queen);
+        assert_eq!(test_app::entity_board(&mut app)[3][4], queen);
+
+        // And it's still there in the game started from the board
+        test_app::press(&mut app, KeyCode::E);
+        test_app::update(&mut app);
+        assert!(!app.world.resource::<Editor>().active);
+        assert_eq!(app.world.resource::<GameState>().board()[3][4], queen);
+        assert_eq!(test_app::entity_board(&mut app)[3][4], queen);
+    }
+}
@@ -0,0 +1,297 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    assets::FONT_PATH,
+    editor::Editor,
+    game::{GameState, LoadPositionEvent},
+    notation::{from_fen, FenError},
+};
+
+/// A text box for starting a game from a position's FEN. F opens it, Enter loads what's been typed and
+/// Escape closes it again. A FEN that doesn't read is kept so it can be fixed, with the error under it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FenInput {
+    pub active: bool,
+    pub buffer: String,
+    pub error: Option<FenError>, // From the last time Enter was pressed, until the text is changed
+}
+
+impl FenInput {
+    pub fn open(&mut self) {
+        self.active = true;
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Adds typed text to the end of the box. Control characters, like the ones some
+    /// platforms send for Enter and Backspace, are left out.
+    pub fn type_text(&mut self, text: &str) {
+        let len = self.buffer.len();
+        self.buffer.extend(text.chars().filter(|c| !c.is_control()));
+        if self.buffer.len() != len {
+            self.error = None;
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if self.buffer.pop().is_some() {
+            self.error = None;
+        }
+    }
+
+    /// Reads the box as a FEN. If it's a position it's returned and the box is closed, otherwise the box
+    /// stays open with the error.
+    pub fn submit(&mut self) -> Option<GameState> {
+        match from_fen(&self.buffer) {
+            Ok(game_state) => {
+                self.close();
+                Some(game_state)
+            }
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct FenInputPanel;
+
+#[derive(Component)]
+struct FenInputText;
+
+#[derive(Component)]
+struct FenErrorText;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = |color| TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: 24.0,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(0.0),
+                    right: Val::Px(0.0),
+                    top: Val::Percent(40.0),
+                    ..default()
+                },
+                padding: UiRect::all(Val::Px(10.0)),
+                flex_direction: FlexDirection::ColumnReverse, // Top to bottom
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(FenInputPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section("", style(Color::WHITE)))
+                .insert(FenInputText);
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    style(Color::rgb(0.9, 0.2, 0.2)),
+                ))
+                .insert(FenErrorText);
+        });
+}
+
+// Runs straight after the input is read, and while the box is open it keeps every key press to itself so
+// typing doesn't set off any of the game's shortcuts
+fn edit_fen_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut char_events: EventReader<ReceivedCharacter>,
+    editor: Res<Editor>,
+    mut fen_input: ResMut<FenInput>,
+    mut load_events: EventWriter<LoadPositionEvent>,
+) {
+    // Read every frame, so nothing typed before the box was opened turns up in it
+    let typed: String = char_events.iter().map(|ev| ev.char).collect();
+
+    if !fen_input.active {
+        if keyboard_input.just_pressed(KeyCode::F) && !editor.active {
+            fen_input.open();
+        }
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        fen_input.close();
+    } else {
+        fen_input.type_text(&typed);
+        if keyboard_input.just_pressed(KeyCode::Back) {
+            fen_input.backspace();
+        }
+        if keyboard_input.just_pressed(KeyCode::Return) {
+            if let Some(game_state) = fen_input.submit() {
+                load_events.send(LoadPositionEvent(game_state));
+            }
+        }
+    }
+    keyboard_input.clear();
+}
+
+fn update_fen_input_ui(
+    fen_input: Res<FenInput>,
+    mut panel_query: Query<&mut Visibility, With<FenInputPanel>>,
+    mut input_query: Query<&mut Text, (With<FenInputText>, Without<FenErrorText>)>,
+    mut error_query: Query<&mut Text, (With<FenErrorText>, Without<FenInputText>)>,
+) {
+    if !fen_input.is_changed() {
+        return;
+    }
+
+    // Hiding the panel doesn't hide the text in it, which is why that's emptied as well
+    panel_query.single_mut().is_visible = fen_input.active;
+    input_query.single_mut().sections[0].value = if fen_input.active {
+        format!("FEN: {}_", fen_input.buffer)
+    } else {
+        String::new()
+    };
+    error_query.single_mut().sections[0].value = match fen_input.error {
+        Some(error) => format!("Invalid FEN: {error}"),
+        None => String::new(),
+    };
+}
+
+pub struct FenInputPlugin;
+
+impl Plugin for FenInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FenInput>()
+            .add_startup_system(setup)
+            .add_system_to_stage(CoreStage::PreUpdate, edit_fen_input.after(InputSystem))
+            .add_system(update_fen_input_ui);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::window::WindowId;
+
+    use super::*;
+    use crate::{
+        notation,
+        test_app::{self, game_app},
+    };
+
+    const FEN: &str = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+
+    #[test]
+    fn typing_backspacing_and_submitting() {
+        let mut fen_input = FenInput::default();
+        fen_input.open();
+        fen_input.type_text("4k3/8/8/8/8/8/8/4K2R w Q - 0 1x\r");
+        fen_input.backspace();
+        assert_eq!(fen_input.buffer, "4k3/8/8/8/8/8/8/4K2R w Q - 0 1");
+
+        // A bad FEN stays in the box with its error, until it's changed
+        assert!(fen_input.submit().is_none());
+        assert!(fen_input.active);
+        assert_eq!(fen_input.error, Some(FenError::Castling));
+        fen_input.backspace();
+        assert_eq!(fen_input.error, None);
+
+        fen_input.buffer = FEN.to_string();
+        let game_state = fen_input.submit().unwrap();
+        assert_eq!(notation::to_fen(&game_state), FEN);
+        assert_eq!(fen_input, FenInput::default());
+    }
+
+    fn fen_input_app() -> App {
+        let mut app = game_app();
+        app.add_event::<ReceivedCharacter>()
+            .add_plugin(FenInputPlugin);
+        // The app's already started, so the plugin's startup system won't get to make these
+        app.world
+            .spawn()
+            .insert(Visibility { is_visible: false })
+            .insert(FenInputPanel);
+        app.world
+            .spawn()
+            .insert(Text::from_section("", default()))
+            .insert(FenInputText);
+        app.world
+            .spawn()
+            .insert(Text::from_section("", default()))
+            .insert(FenErrorText);
+        app
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for char in text.chars() {
+            app.world.send_event(ReceivedCharacter {
+                id: WindowId::primary(),
+                char,
+            });
+        }
+        test_app::update(app);
+    }
+
+    fn text<T: Component>(app: &mut App) -> String {
+        app.world
+            .query_filtered::<&Text, With<T>>()
+            .single(&app.world)
+            .sections[0]
+            .value
+            .clone()
+    }
+
+    #[test]
+    fn a_typed_fen_starts_a_game_and_a_bad_one_shows_an_error() {
+        let mut app = fen_input_app();
+        test_app::press(&mut app, KeyCode::F);
+        assert!(app.world.resource::<FenInput>().active);
+
+        type_text(&mut app, "4k3/8/8/8/8/8/8/4K2R w Q - 0 1");
+        test_app::press(&mut app, KeyCode::Return);
+        assert_eq!(
+            text::<FenErrorText>(&mut app),
+            "Invalid FEN: bad castling rights"
+        );
+        assert!(app.world.resource::<FenInput>().active);
+        // The game's left alone
+        assert_eq!(app.world.resource::<GameState>().plies, 0);
+        assert_ne!(notation::to_fen(app.world.resource::<GameState>()), FEN);
+
+        // Changing the text takes the error away
+        for _ in 0.."Q - 0 1".len() {
+            test_app::press(&mut app, KeyCode::Back);
+        }
+        assert_eq!(text::<FenErrorText>(&mut app), "");
+        type_text(&mut app, "K - 0 1");
+        assert_eq!(text::<FenInputText>(&mut app), format!("FEN: {FEN}_"));
+
+        test_app::press(&mut app, KeyCode::Return);
+        for _ in 0..3 {
+            test_app::update(&mut app);
+        }
+        assert!(!app.world.resource::<FenInput>().active);
+        assert_eq!(text::<FenInputText>(&mut app), "");
+        assert_eq!(notation::to_fen(app.world.resource::<GameState>()), FEN);
+        test_app::play(&mut app, &["e1g1"]);
+    }
+
+    #[test]
+    fn escape_closes_the_box_without_loading() {
+        let mut app = fen_input_app();
+        let before = notation::to_fen(app.world.resource::<GameState>());
+        test_app::press(&mut app, KeyCode::F);
+        type_text(&mut app, FEN);
+        test_app::press(&mut app, KeyCode::Escape);
+        for _ in 0..3 {
+            test_app::update(&mut app);
+        }
+        assert_eq!(*app.world.resource::<FenInput>(), FenInput::default());
+        assert_eq!(notation::to_fen(app.world.resource::<GameState>()), before);
+    }
+}
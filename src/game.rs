@@ -1,42 +1,454 @@
-use std::mem;
+use std::{collections::VecDeque, fmt, marker::PhantomData, str::FromStr, time::Duration};
 
-use bevy::prelude::*;
+use bevy::{
+    ecs::{schedule::ShouldRun, system::SystemParam},
+    prelude::*,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
-    board::{BoardPosition, ClickSquareEvent, Square},
-    pieces::{Piece, PieceAnimCompleteEvent, PieceColor, PieceKind, PieceMoveEvent},
+    ai::PieceValues,
+    bitboard::{self, Bitboards, KING_OFFSETS},
+    board::{
+        BoardPosition, ClickAction, ClickSquareEvent, InputBindings, ShadowSquares, ShowMoveHints,
+        Square,
+    },
+    coach::{self, CoachMode},
+    editor::Editor,
+    notation::{self, UciError},
+    pieces::{
+        CaptureStyle, Piece, PieceAnimCompleteEvent, PieceColor, PieceKind, PieceMoveEvent,
+        RebuildPiecesEvent,
+    },
+    review::ReviewState,
 };
 
-enum MoveCapture {
-    Move,
-    Capture,
-}
-
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum GameOver {
     Checkmate(PieceColor), // Winner
     Stalemate,
+    Draw(DrawReason),          // Claimed by one of the players, or agreed
+    DrawAutomatic(DrawReason), // Ended by the rules straight away, without anyone claiming it
+    DrawInsufficientMaterial,  // Neither player has anything left to mate with
+    Variant(PieceColor),       // Winner by a variant-specific rule
+    Timeout(PieceColor),       // Winner, because the other player ran out of time
+    Resignation(PieceColor),   // Winner, because the other player resigned
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MateSquares {
+    pub king: BoardPosition,
+    pub attackers: Vec<BoardPosition>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DrawReason {
+    FiftyMove,
+    ThreefoldRepetition,
+    Agreement,
+    SeventyFiveMove,
+    FivefoldRepetition,
+}
+
+impl DrawReason {
+    pub fn description(self) -> &'static str {
+        match self {
+            DrawReason::FiftyMove => "fifty-move rule",
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::Agreement => "by agreement",
+            DrawReason::SeventyFiveMove => "seventy-five-move rule",
+            DrawReason::FivefoldRepetition => "fivefold repetition",
+        }
+    }
+}
+
+/// What to tell the players once the game's over, e.g. "CHECKMATE!\nWhite wins!". A win by a variant's
+/// own rule is announced in that variant's terms.
+pub fn game_over_message(game_over: &GameOver, variant: Variant) -> String {
+    match game_over {
+        GameOver::Checkmate(winner) => format!("CHECKMATE!\n{winner} wins!"),
+        GameOver::Stalemate => String::from("STALEMATE\nNo legal moves, it's a draw"),
+        GameOver::Draw(reason) | GameOver::DrawAutomatic(reason) => {
+            format!("DRAW\n{}", reason.description())
+        }
+        GameOver::DrawInsufficientMaterial => {
+            String::from("DRAW\nNot enough material left to checkmate")
+        }
+        GameOver::Timeout(winner) => format!("TIME'S UP!\n{winner} wins!"),
+        GameOver::Resignation(winner) => format!("{} RESIGNS\n{winner} wins!", winner.next()),
+        GameOver::Variant(winner) => {
+            let rule = match variant {
+                Variant::Standard => "VICTORY",
+                Variant::KingOfTheHill => "KING OF THE HILL",
+                Variant::ThreeCheck => "THREE CHECKS",
+                Variant::Atomic => "KABOOM",
+            };
+            format!("{rule}!\n{winner} wins!")
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IllegalMove {
+    NotADestination,   // The piece can't get there at all
+    LeavesKingInCheck, // It could get there, but that would leave its king in check
+    Pinned,            // As above, but specifically because the piece is pinned to its king
+    DestroysOwnKing,   // It could get there, but the explosion would take out its own king (Atomic)
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Variant {
+    #[default]
+    Standard,
+    KingOfTheHill, // Getting your king to the center wins
+    ThreeCheck,    // Giving check three times wins
+    Atomic,        // Captures explode, taking out everything nearby except pawns
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "king-of-the-hill" => Ok(Self::KingOfTheHill),
+            "three-check" => Ok(Self::ThreeCheck),
+            "atomic" => Ok(Self::Atomic),
+            _ => Err(format!("Unknown variant: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum StartPosition {
+    #[default]
+    Standard,
+    Chess960(u64), // Seed used to pick one of the 960 Fischer Random back ranks
 }
 
+impl StartPosition {
+    // Position number in the standard (Scharnagl) numbering, where 518 is the usual setup
+    pub fn chess960_number(self) -> Option<u16> {
+        match self {
+            Self::Standard => None,
+            Self::Chess960(seed) => Some(StdRng::seed_from_u64(seed).gen_range(0..960)),
+        }
+    }
+
+    fn board(self) -> [[Option<Piece>; 8]; 8] {
+        match self.chess960_number() {
+            None => STARTING_BOARD,
+            Some(number) => {
+                let back_rank = chess960_back_rank(number);
+                let mut board = STARTING_BOARD;
+                for (col, kind) in back_rank.into_iter().enumerate() {
+                    board[0][col] = Some(Piece {
+                        color: PieceColor::White,
+                        kind,
+                    });
+                    board[7][col] = Some(Piece {
+                        color: PieceColor::Black,
+                        kind,
+                    });
+                }
+                board
+            }
+        }
+    }
+}
+
+// Puts a piece on the nth remaining empty square of a back rank
+fn place_on_empty(rank: &mut [Option<PieceKind>; 8], n: usize, kind: PieceKind) {
+    let col = (0..8).filter(|col| rank[*col].is_none()).nth(n).unwrap();
+    rank[col] = Some(kind);
+}
+
+// Bishops on opposite colors and the king somewhere between the rooks
+fn chess960_back_rank(number: u16) -> [PieceKind; 8] {
+    let mut rank = [None; 8];
+    let mut n = number as usize % 960;
+
+    rank[(n % 4) * 2 + 1] = Some(PieceKind::Bishop); // Light square
+    n /= 4;
+    rank[(n % 4) * 2] = Some(PieceKind::Bishop); // Dark square
+    n /= 4;
+
+    place_on_empty(&mut rank, n % 6, PieceKind::Queen);
+    n /= 6;
+
+    #[rustfmt::skip]
+    const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+    let (first, second) = KNIGHTS[n];
+    place_on_empty(&mut rank, second, PieceKind::Knight); // Second one first so the first index is unaffected
+    place_on_empty(&mut rank, first, PieceKind::Knight);
+
+    place_on_empty(&mut rank, 0, PieceKind::Rook);
+    place_on_empty(&mut rank, 0, PieceKind::King);
+    place_on_empty(&mut rank, 0, PieceKind::Rook);
+
+    rank.map(Option::unwrap)
+}
+
+/// Which of the pawn's special moves are allowed, for simplified teaching variants. All of them are by
+/// default, as in standard chess.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PawnRules {
+    pub double_move: bool,
+    pub en_passant: bool,
+    pub promotion: bool, // Without it, a pawn that reaches the last rank is stuck there
+}
+
+impl Default for PawnRules {
+    fn default() -> Self {
+        Self {
+            double_move: true,
+            en_passant: true,
+            promotion: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GameRules {
+    pub variant: Variant,
+    pub start: StartPosition,
+    pub pawns: PawnRules,
+}
+
+// A single move, as made by a player. Promotion is only set when a pawn reaches the last rank.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Move {
+    pub from: BoardPosition,
+    pub to: BoardPosition,
+    pub promotion: Option<PieceKind>,
+}
+
+const PROMOTIONS: [PieceKind; 4] = [
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+];
+
 #[derive(Clone, Copy, Debug)]
 pub struct EnPassant {
-    capture_pos: BoardPosition, // The position that the capture occurs on
-    piece_pos: BoardPosition,   // The piece that may get captured is here
+    pub capture_pos: BoardPosition, // The position that the capture occurs on
+    pub piece_pos: BoardPosition,   // The piece that may get captured is here
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CastlingSide {
+    King,  // Towards the h-file
+    Queen, // Towards the a-file
+}
+
+impl CastlingSide {
+    // Where the king and rook end up, which is the same in Chess960 as in standard chess
+    fn castled_cols(self) -> (i8, i8) {
+        match self {
+            Self::King => (6, 5),  // G, F
+            Self::Queen => (2, 3), // C, D
+        }
+    }
+}
+
+// The rooks each player can still castle with, by column. Chess960 can start them anywhere on the back
+// rank. A side's right is lost for good once its king moves, or its rook leaves (or is taken on) its
+// starting square.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CastlingRights {
+    white: [Option<i8>; 2], // King-side, queen-side
+    black: [Option<i8>; 2],
+}
+
+impl CastlingRights {
+    /// Full rights for every king on its back rank, with the outermost rook on each side of it.
+    pub fn from_board(board: &[[Option<Piece>; 8]; 8]) -> Self {
+        let mut rights = Self::default();
+        for color in [PieceColor::White, PieceColor::Black] {
+            let rank = &board[back_row(color) as usize];
+            let is = |col: usize, kind| rank[col] == Some(Piece { color, kind });
+            let king_col = match (0..8).find(|col| is(*col, PieceKind::King)) {
+                Some(col) => col,
+                None => continue,
+            };
+            let sides = rights.sides_mut(color);
+            sides[0] = (king_col + 1..8)
+                .rev()
+                .find(|col| is(*col, PieceKind::Rook))
+                .map(|col| col as i8);
+            sides[1] = (0..king_col)
+                .find(|col| is(*col, PieceKind::Rook))
+                .map(|col| col as i8);
+        }
+        rights
+    }
+
+    /// Column of the rook the player can castle with on that side, if they still can.
+    pub fn rook_col(&self, player: PieceColor, side: CastlingSide) -> Option<i8> {
+        let sides = match player {
+            PieceColor::White => &self.white,
+            PieceColor::Black => &self.black,
+        };
+        sides[side as usize]
+    }
+
+    /// Drops every right that `keep` says no to, e.g. to leave only the ones a FEN lists.
+    pub fn retain(&mut self, mut keep: impl FnMut(PieceColor, CastlingSide) -> bool) {
+        for color in [PieceColor::White, PieceColor::Black] {
+            for side in [CastlingSide::King, CastlingSide::Queen] {
+                if !keep(color, side) {
+                    self.sides_mut(color)[side as usize] = None;
+                }
+            }
+        }
+    }
+
+    fn sides_mut(&mut self, player: PieceColor) -> &mut [Option<i8>; 2] {
+        match player {
+            PieceColor::White => &mut self.white,
+            PieceColor::Black => &mut self.black,
+        }
+    }
+}
+
+/// Where the pieces go in a castling move.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Castling {
+    pub side: CastlingSide,
+    pub king_to: BoardPosition,
+    pub rook_from: BoardPosition,
+    pub rook_to: BoardPosition,
+}
+
+fn castling_for_side(row: i8, side: CastlingSide, rook_col: i8) -> Castling {
+    let (king_col, rook_to_col) = side.castled_cols();
+    Castling {
+        side,
+        king_to: BoardPosition { row, col: king_col },
+        rook_from: BoardPosition { row, col: rook_col },
+        rook_to: BoardPosition {
+            row,
+            col: rook_to_col,
+        },
+    }
+}
+
+// The square the king is moved to in order to castle. See GameState::castling().
+fn castling_target(king_from: BoardPosition, castling: Castling) -> BoardPosition {
+    if i8::abs_diff(king_from.col, castling.king_to.col) >= 2 {
+        castling.king_to
+    } else {
+        castling.rook_from
+    }
+}
+
+fn back_row(color: PieceColor) -> i8 {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 7,
+    }
+}
+
+// Number of checks each player has delivered
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CheckCount {
+    pub white: u32,
+    pub black: u32,
+}
+
+impl CheckCount {
+    pub fn get(&self, player: PieceColor) -> u32 {
+        match player {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+
+    fn increment(&mut self, player: PieceColor) {
+        match player {
+            PieceColor::White => self.white += 1,
+            PieceColor::Black => self.black += 1,
+        }
+    }
 }
 
 #[derive(Clone, Component, Debug, Default)]
 pub struct GameState {
-    pub board: [[Option<Piece>; 8]; 8], // Set of rows (first row is A1-A8, etc)
+    bitboards: Bitboards, // The pieces, only reached through get(), pieces(), board() and the setters
     pub curr_player: PieceColor,
     pub game_over: Option<GameOver>,
     pub en_passant: Option<EnPassant>,
+    pub halfmove_clock: u32, // Half-moves since the last capture or pawn move
+    pub plies: u32,          // Half-moves played since the start of the game
+    pub checks: CheckCount,
+    pub castling: CastlingRights,
+    pub rules: GameRules,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BoardError {
+    KingCount(PieceColor, usize),
+    PawnOnBackRank(BoardPosition),
+    TooManyPieces(PieceColor, usize),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::KingCount(color, count) => write!(f, "{color} has {count} kings"),
+            Self::PawnOnBackRank(pos) => {
+                write!(f, "Pawn on the back rank at {:?}", (pos.row, pos.col))
+            }
+            Self::TooManyPieces(color, count) => write!(f, "{color} has {count} pieces"),
+        }
+    }
+}
+
+// The board as text, from Black's side at the top down to White's, e.g. for the start position:
+// 8 r n b q k b n r
+// 7 p p p p p p p p
+// 6 . . . . . . . .
+// ...
+// 1 R N B Q K B N R
+//   a b c d e f g h
+// White to move
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (row, rank) in self.board().iter().enumerate().rev() {
+            write!(f, "{}", row + 1)?;
+            for piece in rank {
+                let c = piece.map_or('.', notation::piece_char);
+                write!(f, " {c}")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "  a b c d e f g h")?;
+        write!(f, "{} to move", self.curr_player)
+    }
+}
+
+// Everything that makes two positions "the same" for the purposes of repetition
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PositionKey {
+    bitboards: Bitboards,
+    curr_player: PieceColor,
+    en_passant: Option<BoardPosition>,
+    castling: CastlingRights,
+}
+
+// How many candidate moves have been tested for leaving the king in trouble, so tests can see how much
+// work a search for moves does
+#[cfg(test)]
+thread_local! {
+    static MOVE_CHECKS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
 }
 
 impl GameState {
     fn get_pos(&self, pos: BoardPosition) -> Option<Piece> {
         // TODO: should this return Result<Option<Piece>, ()> to indicate if something is out of bounds vs just empty?
         if pos.is_in_bounds() {
-            self.board[pos.row as usize][pos.col as usize]
+            self.bitboards.get(pos)
         } else {
             None
         }
@@ -44,24 +456,53 @@ impl GameState {
 
     fn set_pos(&mut self, pos: BoardPosition, piece: Option<Piece>) -> Option<Piece> {
         if pos.is_in_bounds() {
-            mem::replace(&mut self.board[pos.row as usize][pos.col as usize], piece)
+            self.bitboards.set(pos, piece)
         } else {
             None
         }
     }
 
-    fn iter_pieces(&self) -> PieceIter {
-        PieceIter {
-            game_state: self,
-            curr_pos: Some(BoardPosition::new()),
-        }
+    /// Every piece on the board along with its position, in order A1, B1 ... H1, A2 ... H8.
+    ///
+    /// ```
+    /// use schach::{board::BoardPosition, notation::from_fen};
+    ///
+    /// let start = from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    /// assert_eq!(start.pieces().count(), 32);
+    /// assert_eq!(start.pieces().next().map(|(_, pos)| pos), Some(BoardPosition::new()));
+    /// ```
+    pub fn pieces(&self) -> impl Iterator<Item = (Piece, BoardPosition)> + '_ {
+        bitboard::squares(self.bitboards.occupied())
+            .filter_map(|pos| self.bitboards.get(pos).map(|piece| (piece, pos)))
+    }
+
+    /// The piece at a position, or None if the square is empty or off the board.
+    pub fn get(&self, pos: BoardPosition) -> Option<Piece> {
+        self.get_pos(pos)
+    }
+
+    /// Puts a piece on a position, or clears it, returning whatever was there. Nothing else about the
+    /// game is updated, e.g. castling rights, so this is for setting up a position rather than playing.
+    pub fn set(&mut self, pos: BoardPosition, piece: Option<Piece>) -> Option<Piece> {
+        self.set_pos(pos, piece)
+    }
+
+    /// The pieces as rows of squares (the first row is A1-H1, etc), e.g. for rendering or FEN.
+    pub fn board(&self) -> [[Option<Piece>; 8]; 8] {
+        self.bitboards.to_board()
+    }
+
+    /// Replaces every piece with the ones on the board, given as rows as board() returns them.
+    pub fn set_board(&mut self, board: &[[Option<Piece>; 8]; 8]) {
+        self.bitboards = Bitboards::from_board(board);
     }
 
+    // Returns every piece that was removed from the board by this move
     fn apply_movement(
         &mut self,
         from_pos: BoardPosition,
         to_pos: BoardPosition,
-    ) -> Option<(Piece, BoardPosition)> {
+    ) -> Vec<(Piece, BoardPosition)> {
         assert!(
             from_pos.is_in_bounds(),
             "Moved from out of bounds position: {:?}",
@@ -74,7 +515,14 @@ impl GameState {
         );
 
         let mut moving_piece = self.get_pos(from_pos);
-        assert!(moving_piece.is_some(), "Moving a non-existent piece");
+        if moving_piece.is_none() {
+            return Vec::new(); // Nothing to move, so nothing happens
+        }
+
+        if let Some(castling) = self.castling(from_pos, to_pos) {
+            self.apply_castling(from_pos, castling);
+            return Vec::new();
+        }
 
         // Update moving piece to indicate that it has moved
         {
@@ -90,6 +538,7 @@ impl GameState {
         // A 2-move pawn leaves behind an en passant marker
         let ep = self.en_passant; // Save previous turn's data for later
         if matches!(moving_piece.unwrap().kind, PieceKind::Pawn(_))
+            && self.rules.pawns.en_passant
             && from_pos.col == to_pos.col
             && i8::abs_diff(from_pos.row, to_pos.row) == 2
         {
@@ -119,7 +568,8 @@ impl GameState {
         }
 
         // Update board
-        if en_passant_capture {
+        let is_pawn = matches!(moving_piece.unwrap().kind, PieceKind::Pawn(_));
+        let taken_piece = if en_passant_capture {
             let ep = ep.unwrap();
             let taken_piece = self.get_pos(ep.piece_pos);
             assert!(
@@ -135,501 +585,3711 @@ impl GameState {
             self.set_pos(from_pos, None);
             self.set_pos(to_pos, moving_piece);
             taken_piece.map(|piece| (piece, to_pos))
+        };
+        let mut taken: Vec<_> = taken_piece.into_iter().collect();
+
+        // In Atomic a capture explodes, also removing the capturing piece and every non-pawn around it
+        if self.rules.variant == Variant::Atomic && !taken.is_empty() {
+            taken.extend(self.set_pos(to_pos, None).map(|piece| (piece, to_pos)));
+            for offset in KING_OFFSETS {
+                let pos = to_pos + offset;
+                if let Some(piece) = self.get_pos(pos) {
+                    if !matches!(piece.kind, PieceKind::Pawn(_)) {
+                        self.set_pos(pos, None);
+                        taken.push((piece, pos));
+                    }
+                }
+            }
         }
-    }
 
-    fn moves_and_captures(
-        &self,
-        piece: Piece,
-        piece_pos: BoardPosition,
-    ) -> (Vec<BoardPosition>, Vec<BoardPosition>) {
-        let (mut moves, mut captures) = self.pseudo_moves_and_captures(piece, piece_pos);
+        // Pawn moves and captures are irreversible so they reset the fifty-move count
+        if is_pawn || !taken.is_empty() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
 
-        moves.retain(|pos| {
-            let mut new_state = self.clone();
-            new_state.apply_movement(piece_pos, *pos);
-            new_state.advance_turn(); // TODO: is it needed? don't think we use the turn state anymore.
-            !new_state.is_in_check(piece.color)
-        });
+        self.update_castling_rights(moving_piece.unwrap());
+        taken
+    }
 
-        captures.retain(|pos| {
-            let mut new_state = self.clone();
-            new_state.apply_movement(piece_pos, *pos);
-            new_state.advance_turn();
-            !new_state.is_in_check(piece.color)
-        });
+    fn apply_castling(&mut self, king_from: BoardPosition, castling: Castling) {
+        // Lift both pieces first, since in Chess960 either may land where the other started
+        let king = self.set_pos(king_from, None);
+        let rook = self.set_pos(castling.rook_from, None);
+        self.set_pos(castling.king_to, king);
+        self.set_pos(castling.rook_to, rook);
 
-        (moves, captures)
+        self.en_passant = None;
+        self.halfmove_clock += 1;
+        *self.castling.sides_mut(king.unwrap().color) = [None, None];
     }
 
-    fn pseudo_moves_and_captures(
-        &self,
-        piece: Piece,
-        piece_pos: BoardPosition,
-    ) -> (Vec<BoardPosition>, Vec<BoardPosition>) {
-        // TODO: handle check, en passant, castling, pawn 2-moves
-        let mut moves = Vec::new();
-        let mut captures = Vec::new();
-
-        match piece.kind {
-            PieceKind::King => {
-                #[rustfmt::skip]
-                let offsets = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
-                for offset in offsets {
-                    let new_pos = piece_pos + offset;
-                    self.save_moves_captures(piece, new_pos, &mut moves, &mut captures);
-                }
-            }
-            PieceKind::Queen => {
-                #[rustfmt::skip]
-                let directions = [(-1, 0), (1, 0), (0, 1), (0, -1), (-1, -1), (-1, 1), (1, -1), (1, 1)];
-                for dir in directions {
-                    let (m, c) = self.check_line(piece, piece_pos, dir);
-                    moves.extend(m);
-                    captures.extend(c);
-                }
-            }
-            PieceKind::Rook => {
-                let directions = [(-1, 0), (1, 0), (0, 1), (0, -1)];
-                for dir in directions {
-                    let (m, c) = self.check_line(piece, piece_pos, dir);
-                    moves.extend(m);
-                    captures.extend(c);
-                }
-            }
-            PieceKind::Bishop => {
-                let directions = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-                for dir in directions {
-                    let (m, c) = self.check_line(piece, piece_pos, dir);
-                    moves.extend(m);
-                    captures.extend(c);
+    // Drops the rights that this move used up: all of them if the king moved, otherwise any whose rook
+    // is no longer on its starting square, whether it moved away or was taken there
+    fn update_castling_rights(&mut self, moved: Piece) {
+        if moved.kind == PieceKind::King {
+            *self.castling.sides_mut(moved.color) = [None, None];
+        }
+        for color in [PieceColor::White, PieceColor::Black] {
+            let row = back_row(color);
+            let rook = Some(Piece {
+                color,
+                kind: PieceKind::Rook,
+            });
+            let bitboards = self.bitboards;
+            for col in self.castling.sides_mut(color) {
+                if col.is_some_and(|col| bitboards.get(BoardPosition { row, col }) != rook) {
+                    *col = None;
                 }
             }
-            PieceKind::Knight => {
-                let diags = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-                for diag in diags {
-                    let diag_pos = piece_pos + diag;
+        }
+    }
 
-                    let new_pos = diag_pos + (diag.0, 0);
-                    self.save_moves_captures(piece, new_pos, &mut moves, &mut captures);
+    /// If moving the piece at `from` to `to` castles, where everything goes. A castling move is written
+    /// as the king moving to its destination, or onto its own rook when that's only one step away or
+    /// no step at all, which can happen in Chess960.
+    pub fn castling(&self, from: BoardPosition, to: BoardPosition) -> Option<Castling> {
+        let king = self.get_pos(from)?;
+        if king.kind != PieceKind::King || from.row != back_row(king.color) || to.row != from.row {
+            return None;
+        }
+        [CastlingSide::King, CastlingSide::Queen]
+            .into_iter()
+            .find_map(|side| {
+                let rook_col = self.castling.rook_col(king.color, side)?;
+                let castling = castling_for_side(from.row, side, rook_col);
+                (castling_target(from, castling) == to).then_some(castling)
+            })
+    }
 
-                    let new_pos = diag_pos + (0, diag.1);
-                    self.save_moves_captures(piece, new_pos, &mut moves, &mut captures);
-                }
-            }
-            PieceKind::Pawn(has_moved) => {
-                let next_row = match piece.color {
-                    PieceColor::White => 1,
-                    PieceColor::Black => -1,
-                };
+    // Castling moves for the king, which aren't in pseudo_moves_and_captures since they depend on which
+    // squares are attacked, and attacks are worked out from pseudo_moves_and_captures
+    fn castling_moves(&self, king: Piece, king_pos: BoardPosition) -> Vec<BoardPosition> {
+        let mut moves = Vec::new();
+        if king.kind != PieceKind::King || king_pos.row != back_row(king.color) {
+            return moves;
+        }
 
-                // 1-move
-                let new_pos = piece_pos + (next_row, 0);
-                if new_pos.is_in_bounds() && self.get_pos(new_pos).is_none() {
-                    moves.push(new_pos);
-                }
+        let row = king_pos.row;
+        let cols = |a: i8, b: i8| a.min(b)..=a.max(b);
+        for side in [CastlingSide::King, CastlingSide::Queen] {
+            let rook_col = match self.castling.rook_col(king.color, side) {
+                Some(col) => col,
+                None => continue,
+            };
+            let castling = castling_for_side(row, side, rook_col);
 
-                // 2-move
-                if !has_moved {
-                    let new_pos = piece_pos + (next_row * 2, 0);
-                    if new_pos.is_in_bounds() && self.get_pos(new_pos).is_none() {
-                        moves.push(new_pos);
-                    }
-                }
+            // Everything the king and rook cross or land on must be empty, apart from the two of them
+            let is_clear = cols(king_pos.col, castling.king_to.col)
+                .chain(cols(rook_col, castling.rook_to.col))
+                .all(|col| {
+                    col == king_pos.col
+                        || col == rook_col
+                        || self.get_pos(BoardPosition { row, col }).is_none()
+                });
 
-                // Captures
-                for col in [-1, 1] {
-                    let new_pos = piece_pos + (next_row, col);
-                    if new_pos.is_in_bounds() {
-                        if let Some(Piece { color, .. }) = self.get_pos(new_pos) {
-                            if color != piece.color {
-                                captures.push(new_pos);
-                            }
-                        } else if let Some(en_passant) = self.en_passant {
-                            if new_pos == en_passant.capture_pos {
-                                captures.push(new_pos);
-                            }
-                        }
-                    }
-                }
+            // The king can't castle out of, through or into check
+            let is_safe = || {
+                cols(king_pos.col, castling.king_to.col).all(|col| {
+                    let mut state = self.clone();
+                    state.set_pos(king_pos, None);
+                    state.set_pos(BoardPosition { row, col }, Some(king));
+                    !state.is_in_check(king.color)
+                })
+            };
+
+            if is_clear && is_safe() {
+                moves.push(castling_target(king_pos, castling));
             }
         }
-
-        (moves, captures)
+        moves
     }
 
-    fn is_move_or_capture(&self, piece: Piece, pos: BoardPosition) -> Option<MoveCapture> {
-        if !pos.is_in_bounds() {
-            None
-        } else if let Some(Piece { color, .. }) = self.get_pos(pos) {
-            if color == piece.color {
-                None // Blocking
-            } else {
-                Some(MoveCapture::Capture)
+    // Like apply_movement, but also swaps in the promoted piece
+    fn apply_move(&mut self, mv: Move) -> Vec<(Piece, BoardPosition)> {
+        let taken = self.apply_movement(mv.from, mv.to);
+        if let Some(kind) = mv.promotion {
+            // The pawn may have been blown up along with its capture (Atomic)
+            if let Some(piece) = self.get_pos(mv.to) {
+                self.set_pos(mv.to, Some(Piece { kind, ..piece }));
             }
-        } else {
-            Some(MoveCapture::Move)
         }
+        taken
     }
 
-    fn save_moves_captures(
-        &self,
-        piece: Piece,
-        pos: BoardPosition,
-        moves: &mut Vec<BoardPosition>,
-        captures: &mut Vec<BoardPosition>,
-    ) {
-        match self.is_move_or_capture(piece, pos) {
-            Some(MoveCapture::Move) => moves.push(pos),
-            Some(MoveCapture::Capture) => captures.push(pos),
-            _ => (),
+    /// Plays a move for the current player and hands the turn over. The move is assumed to be legal,
+    /// i.e. one of those returned by `legal_moves()`. Returns every piece removed from the board.
+    pub fn make_move(&mut self, mv: Move) -> Vec<(Piece, BoardPosition)> {
+        let taken = self.apply_move(mv);
+
+        // Only counted once per move, no matter how many pieces are giving check
+        if self.rules.variant == Variant::ThreeCheck && self.is_in_check(self.curr_player.next()) {
+            let player = self.curr_player;
+            self.checks.increment(player);
         }
+
+        self.advance_turn();
+        self.plies += 1;
+        taken
     }
 
-    fn check_line(
-        &self,
-        piece: Piece,
-        from_pos: BoardPosition,
-        direction: (i8, i8),
-    ) -> (Vec<BoardPosition>, Vec<BoardPosition>) {
-        let mut moves = Vec::new();
-        let mut captures = Vec::new();
+    /// The move number as written in notation, which goes up after each of Black's moves.
+    pub fn fullmove_number(&self) -> u32 {
+        self.plies / 2 + 1
+    }
 
-        let mut new_pos = from_pos;
-        loop {
-            new_pos += direction;
-            match self.is_move_or_capture(piece, new_pos) {
-                Some(MoveCapture::Move) => moves.push(new_pos),
-                Some(MoveCapture::Capture) => {
-                    captures.push(new_pos);
-                    break;
-                }
-                None => break,
+    /// Whether moving the piece at `from` to `to` would promote a pawn.
+    pub fn is_promotion(&self, from: BoardPosition, to: BoardPosition) -> bool {
+        self.rules.pawns.promotion
+            && matches!(
+                self.get_pos(from),
+                Some(Piece {
+                    kind: PieceKind::Pawn(_),
+                    ..
+                })
+            )
+            && (to.row == 0 || to.row == 7)
+    }
+
+    /// Every legal move, in the long algebraic notation UCI uses, e.g. "e2e4" or "e7e8q".
+    pub fn legal_moves_uci(&self) -> Vec<String> {
+        self.legal_moves()
+            .into_iter()
+            .map(notation::to_uci)
+            .collect()
+    }
+
+    /// Makes a move given in UCI's long algebraic notation, as long as it's legal.
+    pub fn make_move_uci(&mut self, uci: &str) -> Result<(), UciError> {
+        let mv = notation::parse_uci(uci).ok_or(UciError::Malformed)?;
+        if !self.is_legal_move(mv) {
+            return Err(UciError::Illegal);
+        }
+        self.make_move(mv);
+        Ok(())
+    }
+
+    /// Every legal move for the current player, and only theirs (see legal_moves_for() for the other side).
+    /// Promotions are listed once per piece that can be promoted to.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut legal = Vec::new();
+        for (piece, from) in self.pieces().filter(|(p, _)| p.color == self.curr_player) {
+            let (moves, captures) = self.moves_and_captures(piece, from);
+            for to in moves.into_iter().chain(captures) {
+                self.push_moves(&mut legal, from, to);
             }
         }
-        (moves, captures)
+        legal
     }
 
-    fn get_king_pos(&self, player: PieceColor) -> BoardPosition {
-        let king = Piece {
-            kind: PieceKind::King,
-            color: player,
+    /// Whether the current player can make this move, the same as looking for it in legal_moves() but only
+    /// working out the moving piece's moves. A promotion has to say what it promotes to, and nothing else
+    /// can.
+    pub fn is_legal_move(&self, mv: Move) -> bool {
+        let piece = match self.get_pos(mv.from) {
+            Some(piece) if piece.color == self.curr_player => piece,
+            _ => return false,
         };
-        self.iter_pieces()
-            .find_map(|(piece, pos)| if piece == king { Some(pos) } else { None })
-            .expect("Couldn't find king for {player:?} player")
-    }
-
-    fn is_in_check(&self, player: PieceColor) -> bool {
-        let king_pos = self.get_king_pos(player);
-        self.iter_pieces()
-            .filter(|(piece, _)| piece.color != player)
-            .any(|(piece, pos)| {
-                let (_, captures) = self.pseudo_moves_and_captures(piece, pos);
-                captures.contains(&king_pos)
-            })
-    }
+        let promotion_ok = if self.is_promotion(mv.from, mv.to) {
+            mv.promotion.is_some_and(|kind| PROMOTIONS.contains(&kind))
+        } else {
+            mv.promotion.is_none()
+        };
+        if !promotion_ok {
+            return false;
+        }
 
-    fn no_legal_moves(&self) -> bool {
-        self.iter_pieces()
-            .filter(|(piece, _)| piece.color == self.curr_player)
-            .all(|(piece, piece_pos)| {
-                let (m, c) = self.moves_and_captures(piece, piece_pos);
-                m.is_empty() && c.is_empty()
-            })
+        let (mut moves, captures) = self.pseudo_moves_and_captures(piece, mv.from);
+        moves.extend(self.castling_moves(piece, mv.from));
+        (moves.contains(&mv.to) || captures.contains(&mv.to))
+            && self.check_pseudo_move(piece, mv.from, mv.to).is_ok()
     }
 
-    fn advance_turn(&mut self) {
-        self.curr_player = match self.curr_player {
-            PieceColor::White => PieceColor::Black,
-            PieceColor::Black => PieceColor::White,
+    /// The moves the player could make if it were their turn now, e.g. for analysing the side that isn't
+    /// to move. For the side to move that's the same as legal_moves(). The other side gets no en passant
+    /// capture, since that's only allowed straight after the pawn's double move.
+    pub fn legal_moves_for(&self, player: PieceColor) -> Vec<Move> {
+        if player == self.curr_player {
+            return self.legal_moves();
         }
+        let mut as_player = self.clone();
+        as_player.curr_player = player;
+        as_player.en_passant = None;
+        as_player.legal_moves()
     }
-}
-
-struct PieceIter<'a> {
-    game_state: &'a GameState,
-    curr_pos: Option<BoardPosition>,
-}
 
-impl<'a> Iterator for PieceIter<'a> {
-    type Item = (Piece, BoardPosition);
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let curr_pos = self.curr_pos?;
-            self.curr_pos = curr_pos.next();
-            if let Some(piece) = self.game_state.get_pos(curr_pos) {
-                return Some((piece, curr_pos));
+    /// The legal moves for the current player that change the material on the board, i.e. captures and promotions.
+    pub fn legal_captures(&self) -> Vec<Move> {
+        let mut legal = Vec::new();
+        for (piece, from) in self.pieces().filter(|(p, _)| p.color == self.curr_player) {
+            let (moves, captures) = self.moves_and_captures(piece, from);
+            let promotions = moves.into_iter().filter(|to| self.is_promotion(from, *to));
+            for to in captures.into_iter().chain(promotions) {
+                self.push_moves(&mut legal, from, to);
             }
         }
+        legal
     }
-}
 
-#[rustfmt::skip]
-const STARTING_BOARD: [[Option<Piece>; 8]; 8] = [
-    [
-        Some(Piece { color: PieceColor::White, kind: PieceKind::Rook,   }),
-        Some(Piece { color: PieceColor::White, kind: PieceKind::Knight, }),
-        Some(Piece { color: PieceColor::White, kind: PieceKind::Bishop, }),
-        Some(Piece { color: PieceColor::White, kind: PieceKind::Queen,  }),
-        Some(Piece { color: PieceColor::White, kind: PieceKind::King,   }),
-        Some(Piece { color: PieceColor::White, kind: PieceKind::Bishop, }),
-        Some(Piece { color: PieceColor::White, kind: PieceKind::Knight, }),
-        Some(Piece { color: PieceColor::White, kind: PieceKind::Rook,   }),
-    ],
-    [Some(Piece { color: PieceColor::White, kind: PieceKind::Pawn(false) }); 8],
-    [None; 8],
-    [None; 8],
-    [None; 8],
-    [None; 8],
-    [Some(Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false) }); 8],
-    [
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::Rook,   }),
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::Knight, }),
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::Bishop, }),
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::Queen,  }),
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::King,   }),
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::Bishop, }),
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::Knight, }),
-        Some(Piece { color: PieceColor::Black, kind: PieceKind::Rook,   }),
-    ]
-];
-
-fn setup(mut game_state: ResMut<GameState>) {
-    game_state.board = STARTING_BOARD;
-}
+    // Adds the move, or one move per promotion option
+    fn push_moves(&self, moves: &mut Vec<Move>, from: BoardPosition, to: BoardPosition) {
+        if self.is_promotion(from, to) {
+            moves.extend(PROMOTIONS.into_iter().map(|kind| Move {
+                from,
+                to,
+                promotion: Some(kind),
+            }));
+        } else {
+            moves.push(Move {
+                from,
+                to,
+                promotion: None,
+            });
+        }
+    }
 
-#[derive(Clone, Copy, Default)]
-enum TurnState {
-    #[default]
-    CheckForGameOver,
-    SelectPiece,
-    ShowHighlights,
-    SelectTarget,
-    AnimateMove,
-    CheckCapture,
-    EndTurn,
-}
+    fn moves_and_captures(
+        &self,
+        piece: Piece,
+        piece_pos: BoardPosition,
+    ) -> (Vec<BoardPosition>, Vec<BoardPosition>) {
+        let (mut moves, mut captures) = self.pseudo_moves_and_captures(piece, piece_pos);
+        moves.extend(self.castling_moves(piece, piece_pos));
 
-#[derive(Clone, Component, Copy, Default)]
-pub struct TurnData {
-    state: TurnState,
-    pub move_piece: Option<Entity>,
-    pub move_target: Option<BoardPosition>,
-}
+        let is_legal = |pos: &BoardPosition| self.check_pseudo_move(piece, piece_pos, *pos).is_ok();
+        moves.retain(is_legal);
+        captures.retain(is_legal);
 
-impl TurnData {
-    fn reset(&mut self) {
-        self.state = TurnState::CheckForGameOver;
-        self.move_piece = None;
-        self.move_target = None;
+        (moves, captures)
     }
-}
 
-#[derive(Component)]
-pub struct ValidMove;
+    // Whether a move the piece could physically make would leave its own side in trouble. A plain move is
+    // checked on a moved copy of the bitboards, anything that changes more than its two squares is made
+    // on a copy of the game.
+    fn check_pseudo_move(
+        &self,
+        piece: Piece,
+        from: BoardPosition,
+        to: BoardPosition,
+    ) -> Result<(), IllegalMove> {
+        #[cfg(test)]
+        MOVE_CHECKS.with(|checks| checks.set(checks.get() + 1));
+        let en_passant = matches!(piece.kind, PieceKind::Pawn(_))
+            && self.en_passant.is_some_and(|ep| ep.capture_pos == to);
+        let plain = self.rules.variant != Variant::Atomic
+            && !en_passant
+            && self.castling(from, to).is_none();
+        if plain {
+            let mut after = self.bitboards;
+            after.move_piece(from, to);
+            let enemy = piece.color.next();
+            return match bitboard::squares(after.pieces(piece.color, PieceKind::King)).next() {
+                None => Err(IllegalMove::DestroysOwnKing),
+                Some(_) if after.pieces(enemy, PieceKind::King) == 0 => Ok(()),
+                Some(king_pos) if after.is_attacked(king_pos, enemy) => {
+                    Err(IllegalMove::LeavesKingInCheck)
+                }
+                Some(_) => Ok(()),
+            };
+        }
 
-#[derive(Component)]
-struct Captured;
+        let mut new_state = self.clone();
+        new_state.apply_movement(from, to);
+        new_state.advance_turn(); // TODO: is it needed? don't think we use the turn state anymore.
+        if !new_state.has_king(piece.color) {
+            Err(IllegalMove::DestroysOwnKing)
+        } else if !new_state.has_king(piece.color.next()) {
+            Ok(()) // Blew up the enemy king (Atomic), which wins even if we're left in check
+        } else if new_state.is_in_check(piece.color) {
+            Err(IllegalMove::LeavesKingInCheck)
+        } else {
+            Ok(())
+        }
+    }
 
-/*
-                          ┌──────────────────────────────────────────┐
-                          │                                          │
-                 ┌────────▼─────────┐                                │
-                 │                  │                                │
-                 │ Select piece     ◄──────────────────────┐         │
-                 │                  │                      │         │
-                 └────────┬─────────┘                      │         │
-                          │                                │         │
-                          │ Valid (own piece)              │         │
-                          │                                │         │
-                 ┌────────▼─────────┐                      │         │
-                 │ Highlight piece  │                      │         │
-┌────────────────► Generate moves   │                      │         │
-│                │ Highlight moves  │                      │         │
-│                └────────┬─────────┘                      │         │
-│                         │                                │         │
-│                         │                                │         │
-│                         │                                │         │
-│                ┌────────▼─────────┐                      │         │
-│        Invalid │                  │ Invalid              │         │
-└────────────────┤ Select target    ├──────────────────────┘         │
-     (own piece) │                  │ (enemy, empty, off board)      │
-                 └────────┬─────────┘                                │
-                          │                                          │
-                          │ Valid target selected                    │
-                          │                                          │
-                 ┌────────▼─────────┐                                │
-                 │ Clear highlights │                                │
-                 │ Enact move       │                                │
-                 │ Animate move     │                                │
-                 └────────┬─────────┘                                │
-                          │                                          │
-                          │ Anim done                                │
-                          │                                          │
-                 ┌────────▼─────────┐                                │
-                 │ Clear selections │                                │
-                 │ Change player    ├────────────────────────────────┘
-                 │ End turn         │
-                 └──────────────────┘
- */
-#[allow(clippy::too_many_arguments)]
-fn turn_manager(
-    mut commands: Commands,
-    mut game_state: ResMut<GameState>,
-    mut turn_data: ResMut<TurnData>,
-    mut click_square_events: EventReader<ClickSquareEvent>,
-    piece_query: Query<(Entity, &BoardPosition), With<Piece>>,
-    captured_query: Query<Entity, With<Captured>>,
-    square_query: Query<(Entity, &BoardPosition), With<Square>>,
-    valid_moves_query: Query<(Entity, &BoardPosition), With<ValidMove>>,
-    mut piece_move_events: EventWriter<PieceMoveEvent>,
-    mut anim_complete_events: EventReader<PieceAnimCompleteEvent>,
-) {
-    if game_state.game_over.is_some() {
-        return;
+    /// Why the piece at `from` can't move to `to`, or None if it can. Also None when there's no piece
+    /// at `from`.
+    pub fn illegal_move_reason(
+        &self,
+        from: BoardPosition,
+        to: BoardPosition,
+    ) -> Option<IllegalMove> {
+        let piece = self.get_pos(from)?;
+        let (mut moves, captures) = self.pseudo_moves_and_captures(piece, from);
+        moves.extend(self.castling_moves(piece, from));
+        if !moves.contains(&to) && !captures.contains(&to) {
+            return Some(IllegalMove::NotADestination);
+        }
+        match self.check_pseudo_move(piece, from, to) {
+            // If the king wasn't in check before, then moving something else can only have put it in
+            // check by leaving the line between it and an attacker. That's a pin unless something else
+            // left the line too, as when an en passant capture takes both pawns off the king's rank.
+            Err(IllegalMove::LeavesKingInCheck)
+                if piece.kind != PieceKind::King
+                    && !self.is_in_check(piece.color)
+                    && self.is_pinned(from) =>
+            {
+                Some(IllegalMove::Pinned)
+            }
+            result => result.err(),
+        }
     }
 
-    match turn_data.state {
-        TurnState::CheckForGameOver => {
-            if game_state.no_legal_moves() {
-                if game_state.is_in_check(game_state.curr_player) {
-                    game_state.game_over = Some(GameOver::Checkmate(game_state.curr_player.next()));
-                } else {
-                    game_state.game_over = Some(GameOver::Stalemate);
+    // Whether taking the piece off its square, and nothing else, would leave its king in check
+    fn is_pinned(&self, pos: BoardPosition) -> bool {
+        let mut lifted = self.clone();
+        lifted
+            .set_pos(pos, None)
+            .is_some_and(|piece| lifted.is_in_check(piece.color))
+    }
+
+    // Everything the piece could physically reach, as squares it moves to and squares it captures on,
+    // from a1 to h8. Castling is left to castling_moves().
+    fn pseudo_moves_and_captures(
+        &self,
+        piece: Piece,
+        piece_pos: BoardPosition,
+    ) -> (Vec<BoardPosition>, Vec<BoardPosition>) {
+        let empty = !self.bitboards.occupied();
+        let enemy = self.bitboards.color(piece.color.next());
+
+        let (moves, captures) = match piece.kind {
+            PieceKind::Pawn(has_moved) => {
+                let next_row = match piece.color {
+                    PieceColor::White => 1,
+                    PieceColor::Black => -1,
+                };
+
+                // 1-move, then the 2-move, which can't jump over whatever's blocking the 1-move
+                let mut moves = 0;
+                let one = piece_pos + (next_row, 0);
+                if one.is_in_bounds() && empty & bitboard::square_bit(one) != 0 {
+                    moves |= bitboard::square_bit(one);
+                    let two = piece_pos + (next_row * 2, 0);
+                    let double = !has_moved && self.rules.pawns.double_move;
+                    if double && two.is_in_bounds() && empty & bitboard::square_bit(two) != 0 {
+                        moves |= bitboard::square_bit(two);
+                    }
                 }
-            } else {
-                turn_data.state = TurnState::SelectPiece;
+
+                let attacks = bitboard::pawn_attacks(piece.color, piece_pos);
+                let en_passant = self
+                    .en_passant
+                    .map_or(0, |ep| bitboard::square_bit(ep.capture_pos) & empty);
+                (moves, attacks & (enemy | en_passant))
             }
+            kind => {
+                let attacks = self.bitboards.attacks(kind, piece_pos);
+                (attacks & empty, attacks & enemy)
+            }
+        };
+        (
+            bitboard::squares(moves).collect(),
+            bitboard::squares(captures).collect(),
+        )
+    }
+
+    /// Whether the player still has a king, which they might not in Atomic.
+    pub fn has_king(&self, player: PieceColor) -> bool {
+        self.bitboards.pieces(player, PieceKind::King) != 0
+    }
+
+    // None if the player has lost their king, e.g. to an explosion in Atomic
+    fn get_king_pos(&self, player: PieceColor) -> Option<BoardPosition> {
+        bitboard::squares(self.bitboards.pieces(player, PieceKind::King)).next()
+    }
+
+    /// Whether the player to move is in check, treating a missing king (Atomic) as not in check.
+    pub fn is_check(&self) -> bool {
+        self.has_king(self.curr_player) && self.is_in_check(self.curr_player)
+    }
+
+    /// Whether the player's king is attacked. A player without a king counts as in check, so that a
+    /// board where they've lost it (however that happened) ends the game as a loss for them.
+    pub fn is_in_check(&self, player: PieceColor) -> bool {
+        let king_pos = match self.get_king_pos(player) {
+            Some(pos) => pos,
+            None => return true,
+        };
+        self.bitboards.is_attacked(king_pos, player.next())
+    }
+
+    /// Where the pieces giving check to the player's king are.
+    pub fn checkers(&self, player: PieceColor) -> Vec<BoardPosition> {
+        let king_pos = match self.get_king_pos(player) {
+            Some(pos) => pos,
+            None => return Vec::new(),
+        };
+        let attackers = self.bitboards.attackers(king_pos, player.next());
+        bitboard::squares(attackers).collect()
+    }
+
+    /// The opposing pieces the piece on the square attacks, whether or not taking them would be legal.
+    pub fn threatened_by(&self, pos: BoardPosition) -> Vec<BoardPosition> {
+        let piece = match self.get_pos(pos) {
+            Some(piece) => piece,
+            None => return Vec::new(),
+        };
+        let (_, captures) = self.pseudo_moves_and_captures(piece, pos);
+        captures
+            .into_iter()
+            .filter(|pos| self.get_pos(*pos).is_some()) // Not the empty en passant square
+            .collect()
+    }
+
+    /// Once the game has ended in checkmate, the mated king and the pieces that mated it.
+    pub fn mate_squares(&self) -> Option<MateSquares> {
+        match self.game_over {
+            Some(GameOver::Checkmate(winner)) => Some(MateSquares {
+                king: self.get_king_pos(winner.next())?,
+                attackers: self.checkers(winner.next()),
+            }),
+            _ => None,
         }
-        TurnState::SelectPiece => {
-            for ev in click_square_events.iter() {
-                if ev.kind == MouseButton::Left {
-                    if let Some(pos) = ev.board_pos {
-                        for (entity, piece_pos) in &piece_query {
-                            let piece = game_state
-                                .get_pos(*piece_pos)
-                                .expect("Entity for piece exists but it's not on the board");
-                            if game_state.curr_player == piece.color && pos == *piece_pos {
-                                turn_data.move_piece = Some(entity); // This piece is highlighted in render_board()
-                                turn_data.state = TurnState::ShowHighlights;
-                                break;
-                            }
-                        }
+    }
+
+    /// The winner by a variant-specific rule, such as reaching the hill in King of the Hill.
+    pub fn variant_winner(&self) -> Option<PieceColor> {
+        match self.rules.variant {
+            Variant::Standard => None,
+            Variant::KingOfTheHill => {
+                const HILL: [BoardPosition; 4] = [
+                    BoardPosition { row: 3, col: 3 }, // D4
+                    BoardPosition { row: 4, col: 3 }, // D5
+                    BoardPosition { row: 3, col: 4 }, // E4
+                    BoardPosition { row: 4, col: 4 }, // E5
+                ];
+                self.pieces().find_map(|(piece, pos)| {
+                    if piece.kind == PieceKind::King && HILL.contains(&pos) {
+                        Some(piece.color)
                     } else {
-                        turn_data.move_piece = None;
+                        None
                     }
-                }
+                })
             }
+            Variant::ThreeCheck => [PieceColor::White, PieceColor::Black]
+                .into_iter()
+                .find(|player| self.checks.get(*player) >= 3),
+            Variant::Atomic => [PieceColor::White, PieceColor::Black]
+                .into_iter()
+                .find(|player| !self.has_king(player.next())),
         }
-        TurnState::ShowHighlights => {
-            let piece_pos = piece_query
-                .get_component::<BoardPosition>(turn_data.move_piece.unwrap())
-                .unwrap();
-            let piece = game_state
-                .get_pos(*piece_pos)
-                .expect("Entity for piece exists but it's not on the board");
-            let (moves, captures) = game_state.moves_and_captures(piece, *piece_pos);
-            for (entity, board_pos) in &square_query {
-                if moves.contains(board_pos) || captures.contains(board_pos) {
-                    commands.entity(entity).insert(ValidMove);
-                }
+    }
+
+    fn no_legal_moves(&self) -> bool {
+        // Nothing can be played without a king, since every move would leave it "in check"
+        let king_pos = match self.get_king_pos(self.curr_player) {
+            Some(pos) => pos,
+            None => return true,
+        };
+
+        // The king is tried first as it's the piece most likely to have a way out, and if it's stuck
+        // the rest stop at the first move that works
+        let king = Piece {
+            kind: PieceKind::King,
+            color: self.curr_player,
+        };
+        !self.has_legal_move(king, king_pos)
+            && !self
+                .pieces()
+                .filter(|(piece, _)| piece.color == self.curr_player && *piece != king)
+                .any(|(piece, piece_pos)| self.has_legal_move(piece, piece_pos))
+    }
+
+    // Like checking moves_and_captures() isn't empty, without working out the rest once one's found
+    fn has_legal_move(&self, piece: Piece, piece_pos: BoardPosition) -> bool {
+        let (mut moves, captures) = self.pseudo_moves_and_captures(piece, piece_pos);
+        moves.extend(self.castling_moves(piece, piece_pos));
+        moves
+            .into_iter()
+            .chain(captures)
+            .any(|pos| self.check_pseudo_move(piece, piece_pos, pos).is_ok())
+    }
+
+    // Checks that the board is something we can actually play from
+    pub fn validate(&self) -> Result<(), BoardError> {
+        for color in [PieceColor::White, PieceColor::Black] {
+            let count = self
+                .pieces()
+                .filter(|(piece, _)| piece.color == color)
+                .count();
+            if count > 16 {
+                return Err(BoardError::TooManyPieces(color, count));
+            }
+
+            let kings = self
+                .pieces()
+                .filter(|(piece, _)| piece.color == color && piece.kind == PieceKind::King)
+                .count();
+            if kings != 1 {
+                return Err(BoardError::KingCount(color, kings));
             }
-            turn_data.state = TurnState::SelectTarget;
         }
-        TurnState::SelectTarget => {
-            for ev in click_square_events.iter() {
-                if ev.kind == MouseButton::Left {
-                    if let Some(target_pos) = ev.board_pos {
-                        // Check if the target selection is a friendly piece
-                        let friendly_target = piece_query.iter().find_map(|(entity, piece_pos)| {
-                            let piece = game_state
-                                .get_pos(*piece_pos)
-                                .expect("Entity for piece exists but it's not on the board");
-                            if target_pos == *piece_pos && game_state.curr_player == piece.color {
-                                Some(entity)
-                            } else {
-                                None
-                            }
-                        });
 
-                        if let Some(entity) = friendly_target {
-                            // Invalid selection, but it's our own piece so just go back and use this as the piece to move
-                            turn_data.move_piece = Some(entity); // This piece is highlighted in render_board()
-                            turn_data.state = TurnState::ShowHighlights;
-                        } else if valid_moves_query.iter().any(|(_, pos)| *pos == target_pos) {
-                            // Valid selection, move this piece
-                            turn_data.move_target = Some(target_pos);
-                            turn_data.state = TurnState::AnimateMove;
-
-                            // Unwrap some values - these *should* all be guaranteed to be Some at this point
-                            let piece_ent = turn_data.move_piece.unwrap();
-                            let source = piece_query
-                                .get_component::<BoardPosition>(piece_ent)
-                                .unwrap();
-                            let target = turn_data.move_target.unwrap();
-
-                            // Move the piece in the game state, and mark the captured piece (if any)
-                            let captured = game_state.apply_movement(*source, target);
-                            if let Some(cap) = captured {
-                                for (entity, piece_pos) in &piece_query {
-                                    if *piece_pos == cap.1 {
-                                        commands.entity(entity).insert(Captured);
-                                    }
-                                }
-                            }
+        if let Some((_, pos)) = self.pieces().find(|(piece, pos)| {
+            matches!(piece.kind, PieceKind::Pawn(_)) && (pos.row == 0 || pos.row == 7)
+        }) {
+            return Err(BoardError::PawnOnBackRank(pos));
+        }
 
-                            // Signal to the ECS that the piece has moved, so it can be updated & animated there
-                            piece_move_events.send(PieceMoveEvent::new(
-                                turn_data.move_piece.unwrap(),
-                                *source,
-                                turn_data.move_target.unwrap(),
-                            ));
-                        } else {
-                            // Invalid selection (whether enemy piece or empty). Deselect and go back to the beginning.
-                            turn_data.move_piece = None;
-                            turn_data.state = TurnState::SelectPiece;
-                        }
-                    } else {
-                        // Invalid selection (off board). Deselect and go back to the beginning.
-                        turn_data.move_piece = None;
-                        turn_data.state = TurnState::SelectPiece;
-                    }
+        Ok(())
+    }
 
-                    // Clear highlighted valid moves
-                    for (entity, _) in &valid_moves_query {
-                        commands.entity(entity).remove::<ValidMove>();
-                    }
+    fn position_key(&self) -> PositionKey {
+        PositionKey {
+            bitboards: self.bitboards,
+            curr_player: self.curr_player,
+            en_passant: self.en_passant.map(|ep| ep.capture_pos),
+            castling: self.castling,
+        }
+    }
+
+    /// Zobrist hash of the position, made up of the same things as a repetition check looks at: the pieces,
+    /// the side to move, the castling rights and the en passant square. Only the position matters, not the
+    /// moves that led to it.
+    pub fn zobrist_hash(&self) -> u64 {
+        let mut hash = 0;
+        for (piece, pos) in self.pieces() {
+            let kind = match piece.kind {
+                PieceKind::King => 0,
+                PieceKind::Queen => 1,
+                PieceKind::Rook => 2,
+                PieceKind::Bishop => 3,
+                PieceKind::Knight => 4,
+                PieceKind::Pawn(_) => 5,
+            };
+            let color = match piece.color {
+                PieceColor::White => 0,
+                PieceColor::Black => 6,
+            };
+            hash ^= zobrist_key((color + kind) * 64 + bitboard::square_index(pos) as u64);
+        }
+        if self.curr_player == PieceColor::Black {
+            hash ^= zobrist_key(768);
+        }
+        for (color_index, color) in [PieceColor::White, PieceColor::Black]
+            .into_iter()
+            .enumerate()
+        {
+            for side in [CastlingSide::King, CastlingSide::Queen] {
+                if let Some(col) = self.castling.rook_col(color, side) {
+                    let index = color_index as u64 * 16 + side as u64 * 8 + col as u64;
+                    hash ^= zobrist_key(769 + index);
                 }
             }
         }
-        TurnState::AnimateMove => {
-            for event in anim_complete_events.iter() {
-                if event.entity == turn_data.move_piece.unwrap() {
-                    turn_data.state = TurnState::CheckCapture;
-                }
+        if let Some(ep) = self.en_passant {
+            hash ^= zobrist_key(801 + ep.capture_pos.col as u64);
+        }
+        hash
+    }
+
+    // How the game's ended if it can't carry on: a win by the variant's rule, only the kings being left,
+    // checkmate or stalemate
+    fn forced_game_over(&self) -> Option<GameOver> {
+        if let Some(winner) = self.variant_winner() {
+            Some(GameOver::Variant(winner))
+        } else if self.only_kings_left() {
+            Some(GameOver::DrawInsufficientMaterial)
+        } else if self.no_legal_moves() {
+            if self.is_in_check(self.curr_player) {
+                Some(GameOver::Checkmate(self.curr_player.next()))
+            } else {
+                Some(GameOver::Stalemate)
             }
+        } else {
+            None
         }
-        TurnState::CheckCapture => {
-            for entity in &captured_query {
-                commands.entity(entity).despawn_recursive();
+    }
+
+    // Two bare kings can never mate each other, except in King of the Hill where either could still walk
+    // to the middle
+    fn only_kings_left(&self) -> bool {
+        self.rules.variant != Variant::KingOfTheHill
+            && self
+                .pieces()
+                .all(|(piece, _)| piece.kind == PieceKind::King)
+    }
+
+    /// Plays the game to its end with the moves `pick` chooses, which must be legal, taking a draw as soon
+    /// as one can be claimed. The same rules as the turn manager, without the board, e.g. for running
+    /// bots against each other.
+    pub fn play_out(&mut self, mut pick: impl FnMut(&GameState) -> Move) -> GameOver {
+        let mut history = PositionHistory(vec![self.position_key()]);
+        loop {
+            let game_over = self
+                .game_over
+                .or_else(|| self.forced_game_over())
+                .or_else(|| self.automatic_draw(&history).map(GameOver::DrawAutomatic))
+                .or_else(|| self.claimable_draw(&history).map(GameOver::Draw));
+            if let Some(game_over) = game_over {
+                self.game_over = Some(game_over);
+                return game_over;
             }
-            turn_data.state = TurnState::EndTurn;
+
+            let mv = pick(self);
+            self.make_move(mv);
+            history.0.push(self.position_key());
         }
-        TurnState::EndTurn => {
-            turn_data.reset(); // Clear selections & end turn
-            game_state.advance_turn(); // Change player
+    }
+
+    // The draws that end the game whether or not anyone claims them. They only come after the game's been
+    // checked for mate, which wins even on the move that would have drawn.
+    fn automatic_draw(&self, history: &PositionHistory) -> Option<DrawReason> {
+        if self.halfmove_clock >= 150 {
+            Some(DrawReason::SeventyFiveMove)
+        } else if history.repetitions(&self.position_key()) >= 5 {
+            Some(DrawReason::FivefoldRepetition)
+        } else {
+            None
         }
     }
-}
 
-pub struct GamePlugin;
+    fn claimable_draw(&self, history: &PositionHistory) -> Option<DrawReason> {
+        if self.halfmove_clock >= 100 {
+            Some(DrawReason::FiftyMove)
+        } else if history.repetitions(&self.position_key()) >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else {
+            None
+        }
+    }
 
-impl Plugin for GamePlugin {
-    fn build(&self, app: &mut App) {
-        app.add_startup_system(setup)
-            .add_system(turn_manager)
+    fn advance_turn(&mut self) {
+        self.curr_player = match self.curr_player {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+}
+
+#[rustfmt::skip]
+const STARTING_BOARD: [[Option<Piece>; 8]; 8] = [
+    [
+        Some(Piece { color: PieceColor::White, kind: PieceKind::Rook,   }),
+        Some(Piece { color: PieceColor::White, kind: PieceKind::Knight, }),
+        Some(Piece { color: PieceColor::White, kind: PieceKind::Bishop, }),
+        Some(Piece { color: PieceColor::White, kind: PieceKind::Queen,  }),
+        Some(Piece { color: PieceColor::White, kind: PieceKind::King,   }),
+        Some(Piece { color: PieceColor::White, kind: PieceKind::Bishop, }),
+        Some(Piece { color: PieceColor::White, kind: PieceKind::Knight, }),
+        Some(Piece { color: PieceColor::White, kind: PieceKind::Rook,   }),
+    ],
+    [Some(Piece { color: PieceColor::White, kind: PieceKind::Pawn(false) }); 8],
+    [None; 8],
+    [None; 8],
+    [None; 8],
+    [None; 8],
+    [Some(Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false) }); 8],
+    [
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::Rook,   }),
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::Knight, }),
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::Bishop, }),
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::Queen,  }),
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::King,   }),
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::Bishop, }),
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::Knight, }),
+        Some(Piece { color: PieceColor::Black, kind: PieceKind::Rook,   }),
+    ]
+];
+
+/// Sets up a position piece by piece, e.g. for tests and puzzles. Squares are named like "e4", and
+/// anything that isn't a square name panics, since that's a mistake in the setup rather than something
+/// to recover from. Nobody can castle unless given the right with `castling_rights()`.
+#[derive(Clone, Debug, Default)]
+pub struct GameStateBuilder {
+    game_state: GameState,
+    castling: Vec<(PieceColor, CastlingSide)>,
+}
+
+impl GameStateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn piece(mut self, piece: Piece, square: &str) -> Self {
+        self.game_state.set_pos(parse_square(square), Some(piece));
+        self
+    }
+
+    pub fn to_move(mut self, player: PieceColor) -> Self {
+        self.game_state.curr_player = player;
+        self
+    }
+
+    /// Castling rights as in FEN, e.g. "KQkq", "Kq" or "-". Each is with the outermost rook on that
+    /// side of the king, and is dropped if there isn't one on the back rank.
+    pub fn castling_rights(mut self, rights: &str) -> Self {
+        for c in rights.chars() {
+            self.castling.push(match c {
+                'K' => (PieceColor::White, CastlingSide::King),
+                'Q' => (PieceColor::White, CastlingSide::Queen),
+                'k' => (PieceColor::Black, CastlingSide::King),
+                'q' => (PieceColor::Black, CastlingSide::Queen),
+                '-' => continue,
+                _ => panic!("Not a castling right: {c}"),
+            });
+        }
+        self
+    }
+
+    /// The square a pawn skipped over with its last move, e.g. "d6" after Black's d7-d5.
+    pub fn en_passant(mut self, square: &str) -> Self {
+        let capture_pos = parse_square(square);
+        let piece_pos = match capture_pos.row {
+            2 => capture_pos + (1, 0),
+            5 => capture_pos + (-1, 0),
+            _ => panic!("Not an en passant square: {square}"),
+        };
+        self.game_state.en_passant = Some(EnPassant {
+            capture_pos,
+            piece_pos,
+        });
+        self
+    }
+
+    pub fn rules(mut self, rules: GameRules) -> Self {
+        self.game_state.rules = rules;
+        self
+    }
+
+    pub fn build(mut self) -> Result<GameState, BoardError> {
+        let available = CastlingRights::from_board(&self.game_state.board());
+        for (color, side) in self.castling {
+            self.game_state.castling.sides_mut(color)[side as usize] =
+                available.rook_col(color, side);
+        }
+        self.game_state.validate()?;
+        Ok(self.game_state)
+    }
+}
+
+fn parse_square(square: &str) -> BoardPosition {
+    BoardPosition::from_algebraic(square).unwrap_or_else(|| panic!("Not a square: {square}"))
+}
+
+// The fixed pseudo-random number for one feature of a position (SplitMix64 of its index), so hashes are
+// the same from run to run
+fn zobrist_key(index: u64) -> u64 {
+    let mut z = index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Every position reached so far in the game, used to detect repetition
+#[derive(Default)]
+pub struct PositionHistory(Vec<PositionKey>);
+
+impl PositionHistory {
+    fn repetitions(&self, key: &PositionKey) -> usize {
+        self.0.iter().filter(|k| *k == key).count()
+    }
+}
+
+/// Every move made in the game so far, along with the position it started from.
+#[derive(Default)]
+pub struct MoveHistory {
+    start: GameState,
+    moves: Vec<Move>,
+}
+
+impl MoveHistory {
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// The game as it was after the given number of half-moves, found by replaying from the start.
+    pub fn position_at(&self, ply: usize) -> GameState {
+        let mut game_state = self.start.clone();
+        for mv in &self.moves[..ply.min(self.moves.len())] {
+            game_state.make_move(*mv);
+        }
+        game_state
+    }
+}
+
+/// Where the game's random choices come from, e.g. the AI's book moves and blunders, so that a game can be
+/// played out again exactly by starting from the same seed.
+pub struct GameRng {
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
+// A different game each time, unless a seed is given
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+/// How far the pitch of the sounds played for moves is varied either way, as a fraction of the clip's own
+/// pitch, so a run of quick moves doesn't sound the same every time.
+#[derive(Clone, Copy, Debug)]
+pub struct SoundPitch {
+    pub variation: f32,
+}
+
+impl Default for SoundPitch {
+    fn default() -> Self {
+        Self { variation: 0.06 }
+    }
+}
+
+impl SoundPitch {
+    /// A playback speed for the next sound, which is what sets its pitch: within `variation` of 1.
+    pub fn pick(&self, rng: &mut impl Rng) -> f32 {
+        let variation = self.variation.abs();
+        1.0 + rng.gen_range(-variation..=variation)
+    }
+}
+
+/// How long each move took, from the player being asked for it until it was made, whoever made it. Moves
+/// played before the app started (i.e. imported) count as taking no time.
+#[derive(Default)]
+pub struct MoveTimes {
+    times: Vec<Duration>,
+    turn_started: Option<f64>, // Seconds since startup
+}
+
+impl MoveTimes {
+    /// Indexed the same as MoveHistory::moves.
+    pub fn times(&self) -> &[Duration] {
+        &self.times
+    }
+
+    /// Starts timing the next move at `now` (in seconds), unless it's already being timed.
+    pub fn start_turn(&mut self, now: f64) {
+        self.turn_started.get_or_insert(now);
+    }
+
+    /// Records the move with the given index in the game as having been made at `now`.
+    pub fn end_turn(&mut self, ply: usize, now: f64) {
+        let took = self.turn_started.take().map_or(Duration::ZERO, |started| {
+            Duration::from_secs_f64((now - started).max(0.0))
+        });
+        self.times.resize(ply, Duration::ZERO);
+        self.times.push(took);
+    }
+
+    // Forgets the moves after the first `plies`, along with the one being timed
+    fn truncate(&mut self, plies: usize) {
+        self.times.truncate(plies);
+        self.turn_started = None;
+    }
+}
+
+/// The player whose king is in check right now, if any. Set as soon as the checking move is made, and
+/// cleared once the move out of check is.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InCheck(pub Option<PieceColor>);
+
+// A draw that the player to move may claim but which doesn't end the game on its own
+#[derive(Default)]
+pub struct ClaimableDraw(pub Option<DrawReason>);
+
+pub struct ClaimDrawEvent;
+
+/// Sent once for each move made, after the game state has been updated. Meant for logging and the like,
+/// whereas PieceMoveEvent is what drives the rendering.
+#[derive(Clone, Debug)]
+pub struct MoveMadeEvent {
+    pub piece: Piece, // As it was before moving, so a pawn even if it promoted
+    pub from: BoardPosition,
+    pub to: BoardPosition,
+    pub captured: Option<Piece>, // Just the piece taken directly, not the rest of an Atomic explosion
+    pub san: String,
+    pub fen: String, // Of the position after the move
+    pub gives_check: bool,
+}
+
+/// Sent for each sound the game plays, at the speed (and so the pitch) it was picked to play at.
+#[derive(Clone, Debug)]
+pub struct PlaySoundEvent {
+    pub path: &'static str,
+    pub speed: f32,
+}
+
+/// Who makes the moves for one side.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PlayerKind {
+    #[default]
+    Human, // Clicks on the board
+    Ai,     // The built-in engine
+    Random, // Any legal move, for stress testing
+    Remote, // Someone elsewhere, whose moves arrive as RequestMoveEvents
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlayerConfig {
+    pub white: PlayerKind,
+    pub black: PlayerKind,
+}
+
+impl PlayerConfig {
+    pub fn get(&self, player: PieceColor) -> PlayerKind {
+        match player {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+}
+
+// Set after an undo leaves the AI to move, so it doesn't just replay its move straight away. Until a
+// move is made the AI's pieces are moved by clicking, same as a human's.
+#[derive(Default)]
+pub struct AiPaused(pub bool);
+
+/// How far back a single undo goes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UndoPolicy {
+    #[default]
+    ToHumanTurn, // Back to the last time a human was to move, so against the AI both its reply and your move
+    SinglePly, // Always one half-move
+}
+
+impl UndoPolicy {
+    // How many of the `made` half-moves so far one undo takes back, where `to_move` is the player to move now
+    pub fn plies(self, players: &PlayerConfig, to_move: PieceColor, made: usize) -> usize {
+        let human_to_move = |plies: &usize| {
+            let player = if plies % 2 == 0 {
+                to_move
+            } else {
+                to_move.next()
+            };
+            players.get(player) == PlayerKind::Human
+        };
+        match self {
+            UndoPolicy::ToHumanTurn => (1..=made).find(human_to_move).unwrap_or(made.min(1)),
+            UndoPolicy::SinglePly => made.min(1),
+        }
+    }
+}
+
+/// Asks for moves to be taken back, as many as the UndoPolicy says.
+pub struct RequestUndoEvent;
+
+/// Takes the game back to how it was after the given number of half-moves, dropping every move since, so
+/// it can carry on from there.
+pub struct TakeBackToEvent(pub usize);
+
+/// Sent after moves have been taken back, with how many half-moves went.
+pub struct UndoEvent {
+    pub plies: usize,
+}
+
+/// Starts the game over from the beginning.
+pub struct NewGameEvent;
+
+/// Starts a new game from whatever is on the board right now (e.g. after editing it), with the given
+/// player to move first.
+pub struct StartFromBoardEvent(pub PieceColor);
+
+/// Starts a new game from the given position, e.g. one read from a FEN. It's played under the current
+/// rules, whatever the position's own are.
+pub struct LoadPositionEvent(pub GameState);
+
+/// The given player gives up the game.
+pub struct ResignEvent(pub PieceColor);
+
+/// The given player offers a draw, or accepts the one their opponent offered.
+pub struct OfferDrawEvent(pub PieceColor);
+
+// A draw offer waiting for an answer, from the player who made it. The opponent making a move declines it.
+#[derive(Default)]
+pub struct DrawOffer(pub Option<PieceColor>);
+
+// Sent when a player picks a target square their selected piece can't move to
+pub struct IllegalMoveEvent {
+    pub reason: IllegalMove,
+    pub from: BoardPosition,
+    pub to: BoardPosition,
+}
+
+/// Callbacks to run after every move made in the game, whoever made it, e.g. for logging or analysis by
+/// something embedding the game. Each is given the position after the move and the move itself. Add
+/// them through the resource, e.g. `app.world.resource_mut::<MoveHooks>().add(|game_state, mv| ...)`.
+#[derive(Default)]
+pub struct MoveHooks(Vec<MoveHook>);
+
+type MoveHook = Box<dyn Fn(&GameState, &Move) + Send + Sync>;
+
+impl MoveHooks {
+    pub fn add(&mut self, hook: impl Fn(&GameState, &Move) + Send + Sync + 'static) {
+        self.0.push(Box::new(hook));
+    }
+
+    fn run(&self, game_state: &GameState, mv: &Move) {
+        for hook in &self.0 {
+            hook(game_state, mv);
+        }
+    }
+}
+
+/// Sent instead of making a move the beginner guard (part of coach mode) thinks gives away material,
+/// so the player can confirm it first.
+pub struct ConfirmMoveEvent(pub Move);
+
+/// Asks for a move to be made for the current player, as if they had clicked it in. Ignored unless the
+/// game is waiting for that player to move and the move is legal.
+pub struct RequestMoveEvent(pub Move);
+
+/// Moves to be played one per turn, for whichever player is to move, instead of waiting for them to
+/// click, e.g. to replay a game or drive the whole game from a test. Only has an effect while it's
+/// inserted as a resource. A move that isn't legal when its turn comes is dropped like any other bad
+/// RequestMoveEvent, and the next one is tried straight after.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedMoves(pub VecDeque<Move>);
+
+impl ScriptedMoves {
+    pub fn new(moves: impl IntoIterator<Item = Move>) -> Self {
+        Self(moves.into_iter().collect())
+    }
+}
+
+// Set when the loaded board can't be played, in which case the game doesn't start
+#[derive(Default)]
+pub struct InvalidBoard(pub Option<BoardError>);
+
+// PGN movetext to play through before the game starts, so it picks up where that game left off
+pub struct ImportedGame(pub String);
+
+fn setup(
+    mut game_state: ResMut<GameState>,
+    mut position_history: ResMut<PositionHistory>,
+    mut move_history: ResMut<MoveHistory>,
+    mut invalid_board: ResMut<InvalidBoard>,
+    rules: Res<GameRules>,
+    imported_game: Option<Res<ImportedGame>>,
+) {
+    let board = rules.start.board();
+    game_state.set_board(&board);
+    game_state.castling = CastlingRights::from_board(&board);
+    game_state.rules = *rules;
+    invalid_board.0 = game_state.validate().err();
+    position_history.0.push(game_state.position_key());
+    move_history.start = game_state.clone();
+
+    if let Some(imported_game) = imported_game {
+        // Only commit to the import if all of it is playable
+        match notation::play_pgn(&mut game_state.clone(), &imported_game.0) {
+            Ok(moves) => {
+                for mv in moves {
+                    game_state.make_move(mv);
+                    position_history.0.push(game_state.position_key());
+                    move_history.moves.push(mv);
+                }
+            }
+            Err(e) => error!("Couldn't import PGN, starting a new game instead. {e}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+enum TurnState {
+    #[default]
+    CheckForGameOver,
+    SelectPiece,
+    ShowHighlights,
+    SelectTarget,
+    Promote(Move), // Waiting for the player to pick what the pawn becomes
+    AnimateMove,
+    CheckCapture,
+    EndTurn,
+}
+
+/// Where a turn has got to, as seen from outside the turn manager. See the diagram above `turn_manager()`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum TurnPhase {
+    #[default]
+    CheckForGameOver,
+    SelectPiece,
+    ShowHighlights,
+    SelectTarget,
+    Promote,
+    AnimateMove,
+    CheckCapture,
+    EndTurn,
+}
+
+/// Sent when the turn moves on to another phase. Phases passed through within a single frame (e.g. an
+/// undo's reset straight back to checking for game over) aren't seen, only where it ends up.
+#[derive(Clone, Copy, Debug)]
+pub struct TurnPhaseChanged {
+    pub from: TurnPhase,
+    pub to: TurnPhase,
+}
+
+#[derive(Clone, Component, Copy, Default)]
+pub struct TurnData {
+    state: TurnState,
+    pub move_piece: Option<Entity>,
+    pub move_target: Option<BoardPosition>,
+}
+
+impl TurnData {
+    /// Whether the current player is yet to pick a piece to move.
+    pub fn is_awaiting_move(&self) -> bool {
+        matches!(self.state, TurnState::SelectPiece)
+    }
+
+    pub fn phase(&self) -> TurnPhase {
+        match self.state {
+            TurnState::CheckForGameOver => TurnPhase::CheckForGameOver,
+            TurnState::SelectPiece => TurnPhase::SelectPiece,
+            TurnState::ShowHighlights => TurnPhase::ShowHighlights,
+            TurnState::SelectTarget => TurnPhase::SelectTarget,
+            TurnState::Promote(_) => TurnPhase::Promote,
+            TurnState::AnimateMove => TurnPhase::AnimateMove,
+            TurnState::CheckCapture => TurnPhase::CheckCapture,
+            TurnState::EndTurn => TurnPhase::EndTurn,
+        }
+    }
+
+    /// The piece the player has picked and is still deciding what to do with. None once the move's been
+    /// made, even while the piece is still on its way.
+    pub fn selected_piece(&self) -> Option<Entity> {
+        self.move_piece.filter(|_| self.move_target.is_none())
+    }
+
+    /// Whether the player's moving a pawn to the last rank and has yet to pick what it becomes.
+    pub fn is_choosing_promotion(&self) -> bool {
+        matches!(self.state, TurnState::Promote(_))
+    }
+
+    pub fn reset(&mut self) {
+        self.state = TurnState::CheckForGameOver;
+        self.move_piece = None;
+        self.move_target = None;
+    }
+}
+
+/// What a pawn the player moves to the last rank becomes without asking, or None to always ask. Holding Alt
+/// while picking the square asks anyway.
+#[derive(Clone, Copy, Debug)]
+pub struct AutoPromote(pub Option<PieceKind>);
+
+impl Default for AutoPromote {
+    fn default() -> Self {
+        Self(Some(PieceKind::Queen))
+    }
+}
+
+/// What to promote to straight away, or None if the player has to be asked.
+pub fn promotion_choice(auto_promote: AutoPromote, ask: bool) -> Option<PieceKind> {
+    auto_promote.0.filter(|_| !ask)
+}
+
+// The keys for picking a promotion, in the order they're offered
+pub const PROMOTION_KEYS: [(KeyCode, PieceKind); 4] = [
+    (KeyCode::Key1, PieceKind::Queen),
+    (KeyCode::Key2, PieceKind::Rook),
+    (KeyCode::Key3, PieceKind::Bishop),
+    (KeyCode::Key4, PieceKind::Knight),
+];
+
+/// Whether a piece picked to move can no longer be moved, going by where its entity is now (None if it's
+/// been despawned): the square is empty or holds something of the other side's.
+pub fn is_stale_selection(game_state: &GameState, selected_pos: Option<BoardPosition>) -> bool {
+    match selected_pos.and_then(|pos| game_state.get_pos(pos)) {
+        Some(piece) => piece.color != game_state.curr_player,
+        None => true,
+    }
+}
+
+#[derive(Component)]
+pub struct ValidMove;
+
+/// Marks a piece that's been taken, which stays on the board until its capture has been shown.
+#[derive(Component)]
+pub struct Captured;
+
+// The records kept of the game so far, grouped to keep turn_manager under the system parameter limit
+#[derive(SystemParam)]
+struct History<'w, 's> {
+    positions: ResMut<'w, PositionHistory>,
+    moves: ResMut<'w, MoveHistory>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+// How the game's been set up to play, grouped for the same reason
+#[derive(SystemParam)]
+struct TurnSettings<'w, 's> {
+    players: Res<'w, PlayerConfig>,
+    show_move_hints: Res<'w, ShowMoveHints>,
+    ai_paused: Res<'w, AiPaused>,
+    bindings: Res<'w, InputBindings>,
+    coach_mode: Res<'w, CoachMode>,
+    values: Res<'w, PieceValues>,
+    auto_promote: Res<'w, AutoPromote>,
+    capture_style: Res<'w, CaptureStyle>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+// Everything sent out when a move is made
+#[derive(SystemParam)]
+struct MoveEvents<'w, 's> {
+    piece_move: EventWriter<'w, 's, PieceMoveEvent>,
+    move_made: EventWriter<'w, 's, MoveMadeEvent>,
+    illegal_move: EventWriter<'w, 's, IllegalMoveEvent>,
+    confirm_move: EventWriter<'w, 's, ConfirmMoveEvent>,
+    hooks: Res<'w, MoveHooks>,
+}
+
+/*
+                          ┌──────────────────────────────────────────┐
+                          │                                          │
+                 ┌────────▼─────────┐                                │
+                 │                  │                                │
+                 │ Select piece     ◄──────────────────────┐         │
+                 │                  │                      │         │
+                 └────────┬─────────┘                      │         │
+                          │                                │         │
+                          │ Valid (own piece)              │         │
+                          │                                │         │
+                 ┌────────▼─────────┐                      │         │
+                 │ Highlight piece  │                      │         │
+┌────────────────► Generate moves   │                      │         │
+│                │ Highlight moves  │                      │         │
+│                └────────┬─────────┘                      │         │
+│                         │                                │         │
+│                         │                                │         │
+│                         │                                │         │
+│                ┌────────▼─────────┐                      │         │
+│        Invalid │                  │ Invalid              │         │
+└────────────────┤ Select target    ├──────────────────────┘         │
+     (own piece) │                  │ (enemy, empty, off board)      │
+                 │                  │ or cancelled (Esc, right-click)│
+                 └────────┬─────────┘                                │
+                          │                                          │
+                          │ Valid target selected                    │
+                          │                                          │
+                 ┌────────▼─────────┐                                │
+                 │ Clear highlights │                                │
+                 │ Enact move       │                                │
+                 │ Change player    │                                │
+                 │ Animate move     │                                │
+                 └────────┬─────────┘                                │
+                          │                                          │
+                          │ Anim done                                │
+                          │                                          │
+                 ┌────────▼─────────┐                                │
+                 │ Clear selections ├────────────────────────────────┘
+                 │ End turn         │
+                 └──────────────────┘
+ */
+#[allow(clippy::too_many_arguments)]
+fn turn_manager(
+    mut commands: Commands,
+    mut game_state: ResMut<GameState>,
+    mut turn_data: ResMut<TurnData>,
+    mut click_square_events: EventReader<ClickSquareEvent>,
+    piece_query: Query<(Entity, &BoardPosition), With<Piece>>,
+    captured_query: Query<Entity, With<Captured>>,
+    square_query: Query<(Entity, &BoardPosition), With<Square>>,
+    valid_moves_query: Query<(Entity, &BoardPosition), With<ValidMove>>,
+    mut move_events: MoveEvents,
+    mut anim_complete_events: EventReader<PieceAnimCompleteEvent>,
+    mut history: History,
+    mut claimable_draw: ResMut<ClaimableDraw>,
+    keyboard_input: Res<Input<KeyCode>>,
+    invalid_board: Res<InvalidBoard>,
+    mut request_move_events: EventReader<RequestMoveEvent>,
+    settings: TurnSettings,
+) {
+    if game_state.game_over.is_some() || invalid_board.0.is_some() {
+        // Clicks do nothing now, and a piece picked before the game ended (e.g. on time) is let go.
+        // Reviewing the game still works, that doesn't go through here.
+        click_square_events.iter().for_each(drop);
+        match turn_data.state {
+            TurnState::ShowHighlights | TurnState::SelectTarget | TurnState::Promote(_) => {
+                clear_valid_moves(&mut commands, &valid_moves_query);
+                turn_data.reset();
+                return;
+            }
+            // A move still being shown when the game ended (e.g. on resigning) is seen through to the
+            // end of its turn, so that its capture is cleaned up and review and undo aren't held up
+            TurnState::AnimateMove | TurnState::CheckCapture | TurnState::EndTurn => (),
+            _ => return,
+        }
+    }
+
+    // Clicks only count while a human is picking their move, so nobody can move the pieces of a side
+    // the AI or a remote player controls (unless the AI's been paused by an undo). Drop the rest now
+    // rather than let them turn into a stale selection once the game is ready for input again.
+    let clicks_move = match settings.players.get(game_state.curr_player) {
+        PlayerKind::Human => true,
+        PlayerKind::Ai => settings.ai_paused.0,
+        PlayerKind::Random | PlayerKind::Remote => false,
+    };
+    let awaiting_click = matches!(
+        turn_data.state,
+        TurnState::SelectPiece | TurnState::SelectTarget
+    ) && clicks_move;
+    if !awaiting_click {
+        click_square_events.iter().for_each(drop);
+    }
+
+    // Moves can also come from somewhere other than the mouse, e.g. the AI
+    if let Some(ev) = request_move_events.iter().last() {
+        if matches!(
+            turn_data.state,
+            TurnState::SelectPiece | TurnState::SelectTarget
+        ) {
+            if game_state.is_legal_move(ev.0) {
+                clear_valid_moves(&mut commands, &valid_moves_query);
+                commit_move(
+                    &mut commands,
+                    &mut game_state,
+                    &mut history.moves,
+                    &mut turn_data,
+                    &piece_query,
+                    &mut move_events,
+                    ev.0,
+                );
+                return;
+            }
+            warn!("Ignoring request for illegal move {:?}", ev.0);
+        }
+    }
+
+    // Something other than this system (the editor, an undo, a remote player) may have changed the board
+    // or whose turn it is since the piece was picked, or despawned it. Start the turn over rather than
+    // act on a selection that no longer makes sense.
+    if let Some(entity) = turn_data.move_piece {
+        let selected_pos = piece_query
+            .get_component::<BoardPosition>(entity)
+            .ok()
+            .copied();
+        match turn_data.state {
+            TurnState::ShowHighlights | TurnState::SelectTarget | TurnState::Promote(_)
+                if is_stale_selection(&game_state, selected_pos) =>
+            {
+                warn!("Dropping the selection of a piece that can't move any more");
+                clear_valid_moves(&mut commands, &valid_moves_query);
+                turn_data.reset();
+                return;
+            }
+            // Its animation is never going to finish
+            TurnState::AnimateMove if selected_pos.is_none() => {
+                turn_data.state = TurnState::CheckCapture;
+            }
+            _ => (),
+        }
+    }
+
+    match turn_data.state {
+        TurnState::CheckForGameOver => {
+            let game_over = game_state.forced_game_over().or_else(|| {
+                game_state
+                    .automatic_draw(&history.positions)
+                    .map(GameOver::DrawAutomatic)
+            });
+            if let Some(game_over) = game_over {
+                game_state.game_over = Some(game_over);
+            } else {
+                claimable_draw.0 = game_state.claimable_draw(&history.positions);
+                turn_data.state = TurnState::SelectPiece;
+            }
+        }
+        TurnState::SelectPiece => {
+            for ev in click_square_events.iter().filter(|ev| !ev.shift) {
+                // Any click ends a peek at a piece's moves
+                clear_valid_moves(&mut commands, &valid_moves_query);
+
+                let action = settings.bindings.action(ev.kind, false);
+                if action == Some(ClickAction::Inspect) {
+                    // Show where one of our pieces could go, without selecting it
+                    let own_piece = ev.board_pos.filter(|pos| {
+                        game_state
+                            .get_pos(*pos)
+                            .is_some_and(|piece| piece.color == game_state.curr_player)
+                    });
+                    if let Some(pos) = own_piece {
+                        highlight_valid_moves(&mut commands, &game_state, &square_query, pos);
+                    }
+                } else if action == Some(ClickAction::Select) {
+                    if let Some(pos) = ev.board_pos {
+                        for (entity, piece_pos) in &piece_query {
+                            let piece = game_state
+                                .get_pos(*piece_pos)
+                                .expect("Entity for piece exists but it's not on the board");
+                            if game_state.curr_player == piece.color && pos == *piece_pos {
+                                turn_data.move_piece = Some(entity); // This piece is highlighted in render_board()
+                                turn_data.state = TurnState::ShowHighlights;
+                                break;
+                            }
+                        }
+                    } else {
+                        turn_data.move_piece = None;
+                    }
+                }
+            }
+        }
+        TurnState::ShowHighlights => {
+            let piece_pos = piece_query
+                .get_component::<BoardPosition>(turn_data.move_piece.unwrap())
+                .unwrap();
+            if settings.show_move_hints.0 {
+                highlight_valid_moves(&mut commands, &game_state, &square_query, *piece_pos);
+            }
+            turn_data.state = TurnState::SelectTarget;
+        }
+        TurnState::SelectTarget => {
+            if keyboard_input.just_pressed(KeyCode::Escape) {
+                // Cancel the selection without making a move
+                turn_data.move_piece = None;
+                turn_data.state = TurnState::SelectPiece;
+                clear_valid_moves(&mut commands, &valid_moves_query);
+                return;
+            }
+
+            for ev in click_square_events.iter().filter(|ev| !ev.shift) {
+                let selected_pos = turn_data.move_piece.and_then(|entity| {
+                    piece_query
+                        .get_component::<BoardPosition>(entity)
+                        .ok()
+                        .copied()
+                });
+                let on_selected = ev.board_pos.is_some() && ev.board_pos == selected_pos;
+                let action = settings.bindings.action(ev.kind, on_selected);
+                if action == Some(ClickAction::Cancel) {
+                    // Clicking the selected piece can also cancel the selection
+                    turn_data.move_piece = None;
+                    turn_data.state = TurnState::SelectPiece;
+                    clear_valid_moves(&mut commands, &valid_moves_query);
+                    break;
+                } else if action == Some(ClickAction::Select) {
+                    if let Some(target_pos) = ev.board_pos {
+                        // The target is checked against the rules rather than the highlighted squares,
+                        // which aren't there when move hints are turned off
+                        let source = *piece_query
+                            .get_component::<BoardPosition>(turn_data.move_piece.unwrap())
+                            .unwrap();
+                        let illegal_reason = game_state.illegal_move_reason(source, target_pos);
+
+                        // Check if the target selection is a friendly piece
+                        let friendly_target = piece_query.iter().find_map(|(entity, piece_pos)| {
+                            let piece = game_state
+                                .get_pos(*piece_pos)
+                                .expect("Entity for piece exists but it's not on the board");
+                            if target_pos == *piece_pos && game_state.curr_player == piece.color {
+                                Some(entity)
+                            } else {
+                                None
+                            }
+                        });
+
+                        if illegal_reason.is_none() {
+                            // Valid selection, move this piece. Checked before friendly pieces since
+                            // castling in Chess960 can mean moving the king onto its own rook.
+                            let mv = Move {
+                                from: source,
+                                to: target_pos,
+                                promotion: None,
+                            };
+                            // None if it's not a promotion, Some(None) if the player needs asking
+                            let ask = keyboard_input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+                            let promotion = game_state
+                                .is_promotion(source, target_pos)
+                                .then(|| promotion_choice(*settings.auto_promote, ask));
+                            match promotion {
+                                Some(None) => turn_data.state = TurnState::Promote(mv),
+                                promotion => play_picked_move(
+                                    &mut commands,
+                                    &mut game_state,
+                                    &mut history.moves,
+                                    &mut turn_data,
+                                    &piece_query,
+                                    &mut move_events,
+                                    &settings,
+                                    Move {
+                                        promotion: promotion.flatten(),
+                                        ..mv
+                                    },
+                                ),
+                            }
+                        } else if let Some(entity) = friendly_target {
+                            // Invalid selection, but it's our own piece so just go back and use this as the piece to move
+                            turn_data.move_piece = Some(entity); // This piece is highlighted in render_board()
+                            turn_data.state = TurnState::ShowHighlights;
+                        } else {
+                            // Invalid selection (whether enemy piece or empty). Say why, then deselect and go back to the beginning.
+                            if let Some(reason) = illegal_reason {
+                                move_events.illegal_move.send(IllegalMoveEvent {
+                                    reason,
+                                    from: source,
+                                    to: target_pos,
+                                });
+                            }
+                            turn_data.move_piece = None;
+                            turn_data.state = TurnState::SelectPiece;
+                        }
+                    } else {
+                        // Invalid selection (off board). Deselect and go back to the beginning.
+                        turn_data.move_piece = None;
+                        turn_data.state = TurnState::SelectPiece;
+                    }
+
+                    clear_valid_moves(&mut commands, &valid_moves_query);
+                }
+            }
+        }
+        TurnState::Promote(mv) => {
+            let choice = PROMOTION_KEYS
+                .into_iter()
+                .find(|(key, _)| keyboard_input.just_pressed(*key));
+            if let Some((_, kind)) = choice {
+                play_picked_move(
+                    &mut commands,
+                    &mut game_state,
+                    &mut history.moves,
+                    &mut turn_data,
+                    &piece_query,
+                    &mut move_events,
+                    &settings,
+                    Move {
+                        promotion: Some(kind),
+                        ..mv
+                    },
+                );
+            } else if keyboard_input.just_pressed(KeyCode::Escape) {
+                // Changed their mind, so the pawn stays where it is
+                turn_data.move_piece = None;
+                turn_data.state = TurnState::SelectPiece;
+            }
+        }
+        TurnState::AnimateMove => {
+            for event in anim_complete_events.iter() {
+                if event.entity == turn_data.move_piece.unwrap() {
+                    turn_data.state = TurnState::CheckCapture;
+                }
+            }
+        }
+        TurnState::CheckCapture => {
+            // Any other style is animated in pieces.rs, which despawns the pieces once they've gone
+            if *settings.capture_style == CaptureStyle::Instant {
+                for entity in &captured_query {
+                    commands.entity(entity).despawn_recursive();
+                }
+                turn_data.state = TurnState::EndTurn;
+            } else if captured_query.is_empty() {
+                turn_data.state = TurnState::EndTurn;
+            }
+        }
+        TurnState::EndTurn => {
+            // The player already changed when the move was made
+            turn_data.reset(); // Clear selections & end turn
+            history.positions.0.push(game_state.position_key());
+            claimable_draw.0 = None;
+        }
+    }
+}
+
+// Plays a move the player's picked, unless coach mode holds it back for them to confirm first
+#[allow(clippy::too_many_arguments)]
+fn play_picked_move(
+    commands: &mut Commands,
+    game_state: &mut GameState,
+    move_history: &mut MoveHistory,
+    turn_data: &mut TurnData,
+    piece_query: &Query<(Entity, &BoardPosition), With<Piece>>,
+    move_events: &mut MoveEvents,
+    settings: &TurnSettings,
+    mv: Move,
+) {
+    if settings.coach_mode.0 && coach::loses_material(game_state, mv, &settings.values) {
+        move_events.confirm_move.send(ConfirmMoveEvent(mv));
+        turn_data.move_piece = None;
+        turn_data.state = TurnState::SelectPiece;
+    } else {
+        commit_move(
+            commands,
+            game_state,
+            move_history,
+            turn_data,
+            piece_query,
+            move_events,
+            mv,
+        );
+    }
+}
+
+// Makes the move in the game state, marks the captured pieces (if any) and starts animating it
+fn commit_move(
+    commands: &mut Commands,
+    game_state: &mut GameState,
+    move_history: &mut MoveHistory,
+    turn_data: &mut TurnData,
+    piece_query: &Query<(Entity, &BoardPosition), With<Piece>>,
+    move_events: &mut MoveEvents,
+    mv: Move,
+) {
+    let piece_ent = piece_query
+        .iter()
+        .find_map(|(entity, pos)| (*pos == mv.from).then_some(entity))
+        .expect("Moving a piece that has no entity");
+    turn_data.move_piece = Some(piece_ent);
+    turn_data.move_target = Some(mv.to);
+    turn_data.state = TurnState::AnimateMove;
+
+    let piece = game_state
+        .get_pos(mv.from)
+        .expect("Moving a non-existent piece");
+    let san = notation::to_san(game_state, mv);
+    let castling = game_state.castling(mv.from, mv.to);
+    let captured = game_state.make_move(mv);
+    move_history.moves.push(mv);
+    move_events.move_made.send(MoveMadeEvent {
+        piece,
+        from: mv.from,
+        to: mv.to,
+        captured: captured.first().map(|(piece, _)| *piece),
+        san,
+        fen: notation::to_fen(game_state),
+        gives_check: game_state.is_check(),
+    });
+    move_events.hooks.run(game_state, &mv);
+
+    for (_, cap_pos) in captured {
+        for (entity, piece_pos) in piece_query {
+            if *piece_pos == cap_pos {
+                commands.entity(entity).insert(Captured);
+            }
+        }
+    }
+    let landed = castling.map_or(mv.to, |castling| castling.king_to);
+    match game_state.get_pos(landed) {
+        // The moving piece itself was destroyed (Atomic)
+        None => {
+            commands.entity(piece_ent).insert(Captured);
+        }
+        // Swap the pawn's model for its promoted piece
+        Some(piece) if mv.promotion.is_some() => {
+            commands.entity(piece_ent).insert(piece);
+        }
+        Some(_) => (),
+    }
+
+    // Signal to the ECS that the piece has moved, so it can be updated & animated there. A castling rook
+    // goes first so the king's move is the one left marking the board.
+    if let Some(castling) = castling {
+        let rook_ent = piece_query
+            .iter()
+            .find_map(|(entity, pos)| (*pos == castling.rook_from).then_some(entity))
+            .expect("Castling with a rook that has no entity");
+        move_events.piece_move.send(PieceMoveEvent::new(
+            rook_ent,
+            castling.rook_from,
+            castling.rook_to,
+        ));
+    }
+    move_events
+        .piece_move
+        .send(PieceMoveEvent::new(piece_ent, mv.from, landed));
+}
+
+// Marks every square the piece at `piece_pos` can move to
+fn highlight_valid_moves(
+    commands: &mut Commands,
+    game_state: &GameState,
+    square_query: &Query<(Entity, &BoardPosition), With<Square>>,
+    piece_pos: BoardPosition,
+) {
+    let piece = game_state
+        .get_pos(piece_pos)
+        .expect("Entity for piece exists but it's not on the board");
+    let (moves, captures) = game_state.moves_and_captures(piece, piece_pos);
+    for (entity, board_pos) in square_query {
+        if moves.contains(board_pos) || captures.contains(board_pos) {
+            commands.entity(entity).insert(ValidMove);
+        }
+    }
+}
+
+fn clear_valid_moves(
+    commands: &mut Commands,
+    valid_moves_query: &Query<(Entity, &BoardPosition), With<ValidMove>>,
+) {
+    for (entity, _) in valid_moves_query {
+        commands.entity(entity).remove::<ValidMove>();
+    }
+}
+
+// Everything that goes back along with the moves, grouped so that undo and new game can share it
+#[derive(SystemParam)]
+struct Rewind<'w, 's> {
+    commands: Commands<'w, 's>,
+    game_state: ResMut<'w, GameState>,
+    turn_data: ResMut<'w, TurnData>,
+    history: History<'w, 's>,
+    claimable_draw: ResMut<'w, ClaimableDraw>,
+    draw_offer: ResMut<'w, DrawOffer>,
+    shadow_squares: ResMut<'w, ShadowSquares>,
+    valid_moves_query: Query<'w, 's, (Entity, &'static BoardPosition), With<ValidMove>>,
+    rebuild_events: EventWriter<'w, 's, RebuildPiecesEvent>,
+}
+
+impl Rewind<'_, '_> {
+    // Goes back to how the game was after `ply` half-moves, forgetting every move since. Replaying from
+    // the start also undoes the game being over.
+    fn back_to(&mut self, ply: usize) {
+        *self.game_state = self.history.moves.position_at(ply);
+        self.history.moves.moves.truncate(ply);
+        self.history.positions.0.truncate(ply + 1);
+        self.claimable_draw.0 = None;
+        self.draw_offer.0 = None;
+        self.turn_data.reset();
+        clear_valid_moves(&mut self.commands, &self.valid_moves_query);
+
+        self.rebuild_events
+            .send(RebuildPiecesEvent(self.game_state.board()));
+        let last_move = self.history.moves.moves.last();
+        self.shadow_squares.source = last_move.map(|mv| mv.from);
+        self.shadow_squares.target = last_move.map(|mv| mv.to);
+    }
+}
+
+fn undo_moves(
+    mut request_undo_events: EventReader<RequestUndoEvent>,
+    policy: Res<UndoPolicy>,
+    players: Res<PlayerConfig>,
+    mut rewind: Rewind,
+    mut ai_paused: ResMut<AiPaused>,
+    mut undo_events: EventWriter<UndoEvent>,
+) {
+    if request_undo_events.iter().count() == 0 || rewind.turn_data.move_target.is_some() {
+        return; // Also lets the move in progress finish first
+    }
+
+    let made = rewind.history.moves.moves.len();
+    let plies = policy.plies(&players, rewind.game_state.curr_player, made);
+    if plies == 0 {
+        return;
+    }
+    rewind.back_to(made - plies);
+
+    // Hold the AI back until someone moves, otherwise it'd make the move that was just taken back
+    ai_paused.0 = players.get(rewind.game_state.curr_player) == PlayerKind::Ai;
+    undo_events.send(UndoEvent { plies });
+}
+
+fn take_back_to(
+    mut take_back_events: EventReader<TakeBackToEvent>,
+    players: Res<PlayerConfig>,
+    mut rewind: Rewind,
+    mut review_state: ResMut<ReviewState>,
+    mut ai_paused: ResMut<AiPaused>,
+    mut undo_events: EventWriter<UndoEvent>,
+) {
+    let made = rewind.history.moves.moves.len();
+    let ply = match take_back_events.iter().last() {
+        Some(ev) if ev.0 < made => ev.0,
+        _ => return,
+    };
+    rewind.back_to(ply);
+    review_state.ply = None;
+
+    // Same as an undo, the AI waits for someone to move rather than replaying what it did before
+    ai_paused.0 = players.get(rewind.game_state.curr_player) == PlayerKind::Ai;
+    undo_events.send(UndoEvent { plies: made - ply });
+}
+
+fn new_game(
+    mut new_game_events: EventReader<NewGameEvent>,
+    mut rewind: Rewind,
+    mut review_state: ResMut<ReviewState>,
+    mut ai_paused: ResMut<AiPaused>,
+) {
+    if new_game_events.iter().count() == 0 {
+        return;
+    }
+
+    // The starting position is kept in the history, even when the game was imported from a PGN
+    rewind.back_to(0);
+    if review_state.ply.is_some() {
+        review_state.ply = None;
+    }
+    ai_paused.0 = false;
+}
+
+// Makes the board the game's new starting position, then starts the game over from there like any new game
+fn start_from_board(
+    mut start_events: EventReader<StartFromBoardEvent>,
+    game_state: Res<GameState>,
+    mut history: History,
+    mut invalid_board: ResMut<InvalidBoard>,
+    mut new_game_events: EventWriter<NewGameEvent>,
+) {
+    let to_move = match start_events.iter().last() {
+        Some(ev) => ev.0,
+        None => return,
+    };
+
+    let start = GameState {
+        bitboards: game_state.bitboards,
+        curr_player: to_move,
+        castling: CastlingRights::from_board(&game_state.board()),
+        rules: game_state.rules,
+        ..default()
+    };
+    restart_from(start, &mut history, &mut invalid_board);
+    new_game_events.send(NewGameEvent);
+}
+
+fn load_position(
+    mut load_events: EventReader<LoadPositionEvent>,
+    game_state: Res<GameState>,
+    mut history: History,
+    mut invalid_board: ResMut<InvalidBoard>,
+    mut new_game_events: EventWriter<NewGameEvent>,
+) {
+    let mut start = match load_events.iter().last() {
+        Some(ev) => ev.0.clone(),
+        None => return,
+    };
+    start.rules = game_state.rules;
+    restart_from(start, &mut history, &mut invalid_board);
+    new_game_events.send(NewGameEvent);
+}
+
+// Makes `start` the position the game's history begins from, for a NewGameEvent to go back to
+fn restart_from(start: GameState, history: &mut History, invalid_board: &mut InvalidBoard) {
+    invalid_board.0 = start.validate().err();
+    history.positions.0 = vec![start.position_key()];
+    history.moves.start = start;
+}
+
+fn resign(mut game_state: ResMut<GameState>, mut resign_events: EventReader<ResignEvent>) {
+    for ResignEvent(player) in resign_events.iter() {
+        if game_state.game_over.is_none() {
+            game_state.game_over = Some(GameOver::Resignation(player.next()));
+        }
+    }
+}
+
+// A draw is agreed once both players have offered one
+fn offer_draw(
+    mut game_state: ResMut<GameState>,
+    mut draw_offer: ResMut<DrawOffer>,
+    mut offer_draw_events: EventReader<OfferDrawEvent>,
+    mut move_made_events: EventReader<MoveMadeEvent>,
+) {
+    for ev in move_made_events.iter() {
+        if draw_offer
+            .0
+            .is_some_and(|offered_by| offered_by != ev.piece.color)
+        {
+            draw_offer.0 = None;
+        }
+    }
+
+    for OfferDrawEvent(player) in offer_draw_events.iter() {
+        if game_state.game_over.is_some() {
+            continue;
+        }
+        match draw_offer.0 {
+            Some(offered_by) if offered_by != *player => {
+                game_state.game_over = Some(GameOver::Draw(DrawReason::Agreement));
+                draw_offer.0 = None;
+            }
+            _ => draw_offer.0 = Some(*player),
+        }
+    }
+}
+
+fn time_moves(
+    time: Res<Time>,
+    turn_data: Res<TurnData>,
+    move_history: Res<MoveHistory>,
+    mut move_times: ResMut<MoveTimes>,
+    mut move_made_events: EventReader<MoveMadeEvent>,
+    mut undo_events: EventReader<UndoEvent>,
+    mut new_game_events: EventReader<NewGameEvent>,
+) {
+    let now = time.seconds_since_startup();
+    if move_made_events.iter().count() > 0 {
+        move_times.end_turn(move_history.moves.len() - 1, now);
+    }
+    if undo_events.iter().count() + new_game_events.iter().count() > 0 {
+        move_times.truncate(move_history.moves.len());
+    }
+    if turn_data.is_awaiting_move() {
+        move_times.start_turn(now);
+    }
+}
+
+fn update_in_check(game_state: Res<GameState>, mut in_check: ResMut<InCheck>) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    // Only touched when it actually changes, for whatever's announcing the check
+    let checked = game_state.is_check().then_some(game_state.curr_player);
+    if in_check.0 != checked {
+        in_check.0 = checked;
+    }
+}
+
+// A check has its own sound, so moves giving check leave it to play_check_sound
+fn play_move_sounds(
+    mut move_made_events: EventReader<MoveMadeEvent>,
+    pitch: Res<SoundPitch>,
+    mut rng: ResMut<GameRng>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+    for ev in move_made_events.iter().filter(|ev| !ev.gives_check) {
+        let path = if ev.captured.is_some() {
+            "sounds/capture.wav"
+        } else {
+            "sounds/move.wav"
+        };
+        let speed = pitch.pick(rng.rng());
+        sound_events.send(PlaySoundEvent { path, speed });
+    }
+}
+
+fn play_check_sound(
+    in_check: Res<InCheck>,
+    pitch: Res<SoundPitch>,
+    mut rng: ResMut<GameRng>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+    if in_check.is_changed() && in_check.0.is_some() {
+        let speed = pitch.pick(rng.rng());
+        sound_events.send(PlaySoundEvent {
+            path: "sounds/check.wav",
+            speed,
+        });
+    }
+}
+
+fn play_sounds(
+    mut sound_events: EventReader<PlaySoundEvent>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    for ev in sound_events.iter() {
+        let settings = PlaybackSettings::ONCE.with_speed(ev.speed);
+        audio.play_with_settings(asset_server.load(ev.path), settings);
+    }
+}
+
+// The AI takes over again once a move's been made after an undo
+fn resume_ai(mut move_made_events: EventReader<MoveMadeEvent>, mut ai_paused: ResMut<AiPaused>) {
+    if move_made_events.iter().count() > 0 {
+        ai_paused.0 = false;
+    }
+}
+
+fn claim_draw(
+    mut game_state: ResMut<GameState>,
+    claimable_draw: Res<ClaimableDraw>,
+    mut claim_draw_events: EventReader<ClaimDrawEvent>,
+) {
+    for _ in claim_draw_events.iter() {
+        if game_state.game_over.is_none() {
+            if let Some(reason) = claimable_draw.0 {
+                game_state.game_over = Some(GameOver::Draw(reason));
+            }
+        }
+    }
+}
+
+// Moves can only be made on the live game, not while reviewing an earlier position
+fn is_live(review_state: Res<ReviewState>, editor: Res<Editor>) -> ShouldRun {
+    if review_state.ply.is_none() && !editor.active {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+// Watches for the turn changing phase however it happens, whether in the turn manager or from outside
+// it (review, the editor, undo)
+fn announce_turn_phase(
+    turn_data: Res<TurnData>,
+    mut last_phase: Local<TurnPhase>,
+    mut phase_events: EventWriter<TurnPhaseChanged>,
+) {
+    let phase = turn_data.phase();
+    if phase != *last_phase {
+        phase_events.send(TurnPhaseChanged {
+            from: *last_phase,
+            to: phase,
+        });
+        *last_phase = phase;
+    }
+}
+
+// Hands the next scripted move over once the turn manager is ready for it, which is only after the last
+// move's animation has finished and the game over check is done
+fn play_scripted_moves(
+    game_state: Res<GameState>,
+    turn_data: Res<TurnData>,
+    scripted_moves: Option<ResMut<ScriptedMoves>>,
+    mut request_move_events: EventWriter<RequestMoveEvent>,
+) {
+    let mut scripted_moves = match scripted_moves {
+        Some(scripted_moves) => scripted_moves,
+        None => return,
+    };
+    if game_state.game_over.is_some() || !turn_data.is_awaiting_move() {
+        return;
+    }
+
+    if let Some(mv) = scripted_moves.0.pop_front() {
+        request_move_events.send(RequestMoveEvent(mv));
+    }
+}
+
+/// Label for the system that runs each turn, so that systems feeding it moves can be ordered around it.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, SystemLabel)]
+pub struct TurnManager;
+
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system_to_stage(StartupStage::PreStartup, setup)
+            .add_system(turn_manager.with_run_criteria(is_live).label(TurnManager))
+            .add_system(undo_moves.with_run_criteria(is_live).before(TurnManager))
+            .add_system(resume_ai)
+            // Sent after the turn manager has moved on from the last move, so each one is only sent once
+            .add_system(
+                play_scripted_moves
+                    .with_run_criteria(is_live)
+                    .after(TurnManager),
+            )
+            .add_system(update_in_check.after(TurnManager))
+            .add_system(time_moves.after(TurnManager))
+            .add_system_to_stage(CoreStage::PostUpdate, announce_turn_phase) // After anything's changed the turn
+            .add_system(play_move_sounds.after(TurnManager))
+            .add_system(play_check_sound.after(update_in_check))
+            .add_system(play_sounds.after(play_move_sounds).after(play_check_sound))
+            .add_system(new_game.before(TurnManager))
+            .add_system(take_back_to.before(TurnManager))
+            .add_system(start_from_board.before(new_game))
+            .add_system(load_position.before(new_game))
+            .add_system(resign)
+            .add_system(offer_draw)
+            .add_system(claim_draw)
             .init_resource::<GameState>()
-            .init_resource::<TurnData>();
+            .init_resource::<TurnData>()
+            .init_resource::<GameRules>()
+            .init_resource::<PositionHistory>()
+            .init_resource::<MoveHistory>()
+            .init_resource::<MoveTimes>()
+            .init_resource::<GameRng>()
+            .init_resource::<SoundPitch>()
+            .init_resource::<ReviewState>()
+            .init_resource::<Editor>()
+            .init_resource::<CoachMode>()
+            .init_resource::<PieceValues>()
+            .init_resource::<AutoPromote>()
+            .init_resource::<CaptureStyle>()
+            .init_resource::<MoveHooks>()
+            .init_resource::<ClaimableDraw>()
+            .init_resource::<InvalidBoard>()
+            .init_resource::<PlayerConfig>()
+            .init_resource::<UndoPolicy>()
+            .init_resource::<AiPaused>()
+            .init_resource::<DrawOffer>()
+            .init_resource::<InCheck>()
+            .add_event::<RequestUndoEvent>()
+            .add_event::<UndoEvent>()
+            .add_event::<TurnPhaseChanged>()
+            .add_event::<TakeBackToEvent>()
+            .add_event::<NewGameEvent>()
+            .add_event::<StartFromBoardEvent>()
+            .add_event::<LoadPositionEvent>()
+            .add_event::<ResignEvent>()
+            .add_event::<OfferDrawEvent>()
+            .add_event::<ClaimDrawEvent>()
+            .add_event::<RequestMoveEvent>()
+            .add_event::<ConfirmMoveEvent>()
+            .add_event::<MoveMadeEvent>()
+            .add_event::<PlaySoundEvent>()
+            .add_event::<IllegalMoveEvent>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::input::{keyboard::KeyboardInput, ButtonState};
+
+    use super::*;
+    use crate::{
+        ai::RandomBot,
+        bitboard::{square_index, squares},
+        test_app::{self, board_app, game_app, square},
+    };
+
+    fn position(fen: &str, variant: Variant) -> GameState {
+        let mut game_state = notation::from_fen(fen).unwrap();
+        game_state.rules.variant = variant;
+        game_state
+    }
+
+    fn play(game_state: &mut GameState, moves: &[&str]) {
+        for uci in moves {
+            game_state.make_move_uci(uci).unwrap();
+        }
+    }
+
+    // Claims a draw once the move has been played from the position, returning how the game ended
+    fn claim_after(fen: &str, uci: &str) -> Option<GameOver> {
+        let mut app = game_app();
+        test_app::load(&mut app, fen);
+        test_app::play(&mut app, &[uci]);
+        app.world.send_event(ClaimDrawEvent);
+        test_app::update(&mut app);
+        app.world.resource::<GameState>().game_over
+    }
+
+    #[test]
+    fn fifty_move_draw_can_be_claimed_after_fifty_moves() {
+        let game_over = claim_after("4k3/8/8/8/8/8/8/4K1N1 w - - 99 60", "g1f3");
+        assert!(matches!(
+            game_over,
+            Some(GameOver::Draw(DrawReason::FiftyMove))
+        ));
+    }
+
+    #[test]
+    fn fifty_move_draw_cant_be_claimed_after_forty_nine_moves() {
+        let game_over = claim_after("4k3/8/8/8/8/8/8/4K1N1 w - - 97 60", "g1f3");
+        assert!(game_over.is_none());
+    }
+
+    #[test]
+    fn king_of_the_hill_is_won_by_reaching_the_center() {
+        let mut game_state = position("4k3/8/8/8/8/8/8/4K3 w - - 0 1", Variant::KingOfTheHill);
+        play(&mut game_state, &["e1e2", "e8e7", "e2e3", "e7e6"]);
+        assert!(game_state.forced_game_over().is_none());
+
+        play(&mut game_state, &["e3e4"]);
+        assert!(matches!(
+            game_state.forced_game_over(),
+            Some(GameOver::Variant(PieceColor::White))
+        ));
+    }
+
+    #[test]
+    fn three_checks_win_for_the_checking_side() {
+        let mut game_state = position("k7/8/8/8/8/8/8/4K2Q w - - 0 1", Variant::ThreeCheck);
+        play(&mut game_state, &["h1h8", "a8a7", "h8h7", "a7a6"]);
+        assert_eq!(game_state.checks.get(PieceColor::White), 2);
+        assert!(game_state.forced_game_over().is_none());
+
+        play(&mut game_state, &["h7h6"]);
+        assert!(matches!(
+            game_state.forced_game_over(),
+            Some(GameOver::Variant(PieceColor::White))
+        ));
+    }
+
+    #[test]
+    fn double_check_counts_once() {
+        let mut game_state = position("4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1", Variant::ThreeCheck);
+        play(&mut game_state, &["e4f6"]);
+        assert_eq!(game_state.checkers(PieceColor::Black).len(), 2);
+        assert_eq!(game_state.checks.get(PieceColor::White), 1);
+    }
+
+    #[test]
+    fn atomic_capture_blows_up_a_defending_knight_but_not_pawns() {
+        let mut game_state = position("4k3/8/2n1p3/3p4/8/8/8/3RK3 w - - 0 1", Variant::Atomic);
+        let taken = game_state.make_move(notation::parse_uci("d1d5").unwrap());
+
+        let taken: Vec<_> = taken.into_iter().map(|(piece, _)| piece.kind).collect();
+        assert_eq!(
+            taken,
+            [PieceKind::Pawn(true), PieceKind::Rook, PieceKind::Knight]
+        );
+        let at = |name| game_state.get(square(name));
+        assert_eq!(at("c6"), None);
+        assert_eq!(at("d5"), None);
+        assert!(matches!(
+            at("e6"),
+            Some(Piece {
+                kind: PieceKind::Pawn(_),
+                ..
+            })
+        ));
+        assert!(game_state.forced_game_over().is_none());
+    }
+
+    #[test]
+    fn atomic_capture_next_to_the_king_wins() {
+        let mut game_state = position("4k3/5p2/8/7Q/8/8/8/4K3 w - - 0 1", Variant::Atomic);
+        play(&mut game_state, &["h5f7"]);
+        assert!(!game_state.has_king(PieceColor::Black));
+        assert!(matches!(
+            game_state.forced_game_over(),
+            Some(GameOver::Variant(PieceColor::White))
+        ));
+    }
+
+    #[test]
+    fn chess960_back_ranks_follow_the_rules() {
+        for seed in [0, 1, 2, 42, 959, 12345, u64::MAX] {
+            let start = StartPosition::Chess960(seed);
+            assert!(start.chess960_number().unwrap() < 960);
+
+            let board = start.board();
+            let cols = |kind| (0..8).filter(move |col| board[0][*col].unwrap().kind == kind);
+            let bishops: Vec<usize> = cols(PieceKind::Bishop).collect();
+            let rooks: Vec<usize> = cols(PieceKind::Rook).collect();
+            let king = cols(PieceKind::King).next().unwrap();
+            assert_eq!(bishops.len(), 2);
+            assert_ne!(bishops[0] % 2, bishops[1] % 2, "seed {seed}");
+            assert!(rooks[0] < king && king < rooks[1], "seed {seed}");
+            assert_eq!(
+                board[0].map(|p| p.unwrap().kind),
+                board[7].map(|p| p.unwrap().kind)
+            );
+        }
+    }
+
+    #[test]
+    fn chess960_position_518_is_the_standard_one() {
+        assert_eq!(
+            chess960_back_rank(518),
+            STARTING_BOARD[0].map(|p| p.unwrap().kind)
+        );
+    }
+
+    fn valid_move_count(app: &mut App) -> usize {
+        app.world
+            .query_filtered::<(), With<ValidMove>>()
+            .iter(&app.world)
+            .count()
+    }
+
+    #[test]
+    fn cancelling_the_selection_clears_the_highlights() {
+        let mut app = board_app();
+        test_app::click(&mut app, MouseButton::Left, "g1");
+        test_app::update(&mut app);
+        assert_eq!(valid_move_count(&mut app), 2);
+        assert_eq!(
+            app.world.resource::<TurnData>().phase(),
+            TurnPhase::SelectTarget
+        );
+
+        test_app::press(&mut app, KeyCode::Escape);
+        assert_eq!(valid_move_count(&mut app), 0);
+        let turn_data = app.world.resource::<TurnData>();
+        assert_eq!(turn_data.phase(), TurnPhase::SelectPiece);
+        assert_eq!(turn_data.selected_piece(), None);
+    }
+
+    fn piece(color: PieceColor, kind: PieceKind) -> Piece {
+        Piece { color, kind }
+    }
+
+    #[test]
+    fn validate_catches_each_kind_of_broken_board() {
+        let white_king = piece(PieceColor::White, PieceKind::King);
+        let black_king = piece(PieceColor::Black, PieceKind::King);
+        let kings = GameStateBuilder::new()
+            .piece(white_king, "e1")
+            .piece(black_king, "e8");
+        assert!(kings.clone().build().is_ok());
+
+        let no_king = GameStateBuilder::new().piece(black_king, "e8").build();
+        assert_eq!(
+            no_king.unwrap_err(),
+            BoardError::KingCount(PieceColor::White, 0)
+        );
+
+        let two_kings = kings.clone().piece(black_king, "a8").build();
+        assert_eq!(
+            two_kings.unwrap_err(),
+            BoardError::KingCount(PieceColor::Black, 2)
+        );
+
+        let pawn = piece(PieceColor::Black, PieceKind::Pawn(true));
+        let back_rank_pawn = kings.clone().piece(pawn, "c1").build();
+        assert_eq!(
+            back_rank_pawn.unwrap_err(),
+            BoardError::PawnOnBackRank(BoardPosition { row: 0, col: 2 })
+        );
+
+        let mut crowded = kings;
+        for file in "abcdefgh".chars() {
+            for rank in ["2", "3"] {
+                crowded = crowded.piece(
+                    piece(PieceColor::White, PieceKind::Pawn(true)),
+                    &format!("{file}{rank}"),
+                );
+            }
+        }
+        assert_eq!(
+            crowded.build().unwrap_err(),
+            BoardError::TooManyPieces(PieceColor::White, 17)
+        );
+    }
+
+    #[test]
+    fn loading_a_broken_board_is_reported_rather_than_played() {
+        let mut app = game_app();
+        let mut game_state = GameState::default();
+        game_state.set_pos(
+            BoardPosition::new(),
+            Some(piece(PieceColor::White, PieceKind::King)),
+        );
+        app.world.send_event(LoadPositionEvent(game_state));
+        for _ in 0..3 {
+            test_app::update(&mut app);
+        }
+        assert_eq!(
+            app.world.resource::<InvalidBoard>().0,
+            Some(BoardError::KingCount(PieceColor::Black, 0))
+        );
+        assert!(!app.world.resource::<TurnData>().is_awaiting_move());
+    }
+
+    #[test]
+    fn clicks_during_the_animation_are_dropped() {
+        let mut app = game_app();
+        app.insert_resource(ScriptedMoves::new(notation::parse_uci("e2e4")));
+        for _ in 0..3 {
+            app.update(); // Without finishing the animation
+        }
+        assert_eq!(
+            app.world.resource::<TurnData>().phase(),
+            TurnPhase::AnimateMove
+        );
+
+        app.world.send_event(ClickSquareEvent {
+            kind: MouseButton::Left,
+            board_pos: BoardPosition::from_algebraic("e7"),
+            shift: false,
+        });
+        app.update();
+        let pawn = app
+            .world
+            .query_filtered::<(Entity, &BoardPosition), With<Piece>>()
+            .iter(&app.world)
+            .find(|(_, pos)| **pos == BoardPosition { row: 3, col: 4 })
+            .map(|(entity, _)| entity)
+            .unwrap();
+        app.world
+            .send_event(PieceAnimCompleteEvent { entity: pawn });
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+
+        let turn_data = app.world.resource::<TurnData>();
+        assert_eq!(turn_data.phase(), TurnPhase::SelectPiece);
+        assert_eq!(turn_data.selected_piece(), None);
+    }
+
+    #[test]
+    fn move_made_event_for_e4() {
+        let mut app = game_app();
+        app.insert_resource(ScriptedMoves::new(notation::parse_uci("e2e4")));
+        let mut made = Vec::new();
+        let mut reader = app.world.resource::<Events<MoveMadeEvent>>().get_reader();
+        for _ in 0..5 {
+            test_app::update(&mut app);
+            let events = app.world.resource::<Events<MoveMadeEvent>>();
+            made.extend(reader.iter(events).cloned());
+        }
+
+        assert_eq!(made.len(), 1);
+        let ev = &made[0];
+        assert_eq!(
+            ev.piece,
+            Piece {
+                color: PieceColor::White,
+                kind: PieceKind::Pawn(false)
+            }
+        );
+        assert_eq!(
+            (ev.from, ev.to),
+            (
+                BoardPosition { row: 1, col: 4 },
+                BoardPosition { row: 3, col: 4 }
+            )
+        );
+        assert_eq!(ev.captured, None);
+        assert_eq!(ev.san, "e4");
+        assert_eq!(
+            ev.fen,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        assert!(!ev.gives_check);
+    }
+
+    #[test]
+    fn right_click_shows_moves_without_selecting() {
+        let mut app = board_app();
+        test_app::click(&mut app, MouseButton::Right, "g1");
+        test_app::update(&mut app);
+        assert_eq!(valid_move_count(&mut app), 2);
+        let turn_data = app.world.resource::<TurnData>();
+        assert_eq!(turn_data.phase(), TurnPhase::SelectPiece);
+        assert_eq!(turn_data.selected_piece(), None);
+
+        // Any other click ends the peek
+        test_app::click(&mut app, MouseButton::Right, "e4");
+        test_app::update(&mut app);
+        assert_eq!(valid_move_count(&mut app), 0);
+    }
+
+    #[test]
+    fn clicks_on_a_computer_or_remote_side_are_ignored() {
+        for kind in [PlayerKind::Ai, PlayerKind::Remote] {
+            let mut app = game_app();
+            app.insert_resource(PlayerConfig {
+                white: kind,
+                black: PlayerKind::Human,
+            });
+            test_app::click(&mut app, MouseButton::Left, "e2");
+            test_app::update(&mut app);
+            assert_eq!(app.world.resource::<TurnData>().selected_piece(), None);
+            test_app::click(&mut app, MouseButton::Left, "e4");
+            for _ in 0..5 {
+                test_app::update(&mut app);
+            }
+
+            assert_eq!(app.world.resource::<GameState>().plies, 0);
+            let turn_data = app.world.resource::<TurnData>();
+            assert_eq!(turn_data.phase(), TurnPhase::SelectPiece);
+            assert_eq!(turn_data.selected_piece(), None);
+        }
+    }
+
+    #[test]
+    fn illegal_move_reasons() {
+        let game_state = position("4r1k1/8/8/8/8/8/1N6/4K3 w - - 0 1", Variant::Standard);
+        let reason = |from, to| game_state.illegal_move_reason(square(from), square(to));
+
+        // The king is in check, so moves that don't deal with it are ruled out
+        assert_eq!(reason("b2", "d3"), Some(IllegalMove::LeavesKingInCheck));
+        assert_eq!(reason("e1", "e2"), Some(IllegalMove::LeavesKingInCheck));
+        assert_eq!(reason("b2", "b4"), Some(IllegalMove::NotADestination));
+        assert_eq!(reason("e1", "e3"), Some(IllegalMove::NotADestination));
+        assert_eq!(reason("e1", "d1"), None);
+        assert_eq!(reason("d4", "d5"), None); // No piece there
+
+        let game_state = position("4k3/8/8/8/8/8/R2n4/4K3 w - - 0 1", Variant::Atomic);
+        assert_eq!(
+            game_state.illegal_move_reason(square("a2"), square("d2")),
+            Some(IllegalMove::DestroysOwnKing)
+        );
+    }
+
+    #[test]
+    fn moves_are_enforced_with_the_hints_hidden() {
+        let mut app = board_app();
+        app.insert_resource(ShowMoveHints(false));
+        test_app::click(&mut app, MouseButton::Left, "g1");
+        test_app::update(&mut app);
+        assert_eq!(valid_move_count(&mut app), 0);
+        assert!(app.world.resource::<TurnData>().selected_piece().is_some());
+
+        // Not a knight's move, so nothing happens
+        test_app::click(&mut app, MouseButton::Left, "g3");
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+        assert_eq!(app.world.resource::<GameState>().plies, 0);
+
+        test_app::click(&mut app, MouseButton::Left, "g1");
+        test_app::update(&mut app);
+        test_app::click(&mut app, MouseButton::Left, "f3");
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.plies, 1);
+        assert_eq!(
+            game_state.get(square("f3")),
+            Some(piece(PieceColor::White, PieceKind::Knight))
+        );
+    }
+
+    #[test]
+    fn moving_or_losing_a_rook_only_forfeits_its_side() {
+        use CastlingSide::{King, Queen};
+        let fen = "r3k2r/8/8/8/8/8/1B6/R3K2R w KQkq - 0 1";
+
+        let mut game_state = position(fen, Variant::Standard);
+        play(&mut game_state, &["h1h3"]);
+        let rights = game_state.castling;
+        assert_eq!(rights.rook_col(PieceColor::White, King), None);
+        assert_eq!(rights.rook_col(PieceColor::White, Queen), Some(0));
+        assert_eq!(rights.rook_col(PieceColor::Black, King), Some(7));
+        assert_eq!(rights.rook_col(PieceColor::Black, Queen), Some(0));
+        play(&mut game_state, &["a8b8", "e1c1"]);
+        assert_eq!(
+            game_state.get(square("d1")),
+            Some(piece(PieceColor::White, PieceKind::Rook))
+        );
+
+        // Taking a rook on its home square takes away its side's right too
+        let mut game_state = position(fen, Variant::Standard);
+        play(&mut game_state, &["b2h8"]);
+        let rights = game_state.castling;
+        assert_eq!(rights.rook_col(PieceColor::Black, King), None);
+        assert_eq!(rights.rook_col(PieceColor::Black, Queen), Some(0));
+        assert_eq!(rights.rook_col(PieceColor::White, King), Some(7));
+        assert_eq!(rights.rook_col(PieceColor::White, Queen), Some(0));
+    }
+
+    #[test]
+    fn a_kingless_board_is_a_loss_rather_than_a_panic() {
+        let mut game_state = position(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            Variant::Standard,
+        );
+        game_state.set_pos(square("e1"), None);
+
+        assert_eq!(game_state.get_king_pos(PieceColor::White), None);
+        assert!(game_state.is_in_check(PieceColor::White));
+        assert!(!game_state.is_in_check(PieceColor::Black));
+        assert!(game_state.no_legal_moves());
+        assert!(game_state.legal_moves().is_empty());
+        assert!(matches!(
+            game_state.forced_game_over(),
+            Some(GameOver::Checkmate(PieceColor::Black))
+        ));
+
+        // Moving from an empty square does nothing
+        let before = game_state.board();
+        assert!(game_state
+            .apply_movement(square("e4"), square("e5"))
+            .is_empty());
+        assert_eq!(game_state.board(), before);
+    }
+
+    #[test]
+    fn move_numbers_after_e4_e5_nf3() {
+        let mut app = game_app();
+        test_app::play(&mut app, &["e2e4", "e7e5", "g1f3"]);
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.fullmove_number(), 2);
+        assert_eq!(game_state.plies, 3);
+
+        // A new game starts counting again
+        app.world.send_event(NewGameEvent);
+        for _ in 0..3 {
+            test_app::update(&mut app);
+        }
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.fullmove_number(), 1);
+        assert_eq!(game_state.plies, 0);
+    }
+
+    #[test]
+    fn moves_off_a_pin_are_told_apart() {
+        let game_state = position("4r1k1/8/8/8/8/4B3/4N3/4K3 w - - 0 1", Variant::Standard);
+        let reason = |from, to| game_state.illegal_move_reason(square(from), square(to));
+
+        // With two pieces in the way, neither is pinned
+        assert_eq!(reason("e3", "d4"), None);
+        assert_eq!(reason("e2", "c3"), None);
+
+        let game_state = position("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1", Variant::Standard);
+        let reason = |from, to| game_state.illegal_move_reason(square(from), square(to));
+        assert_eq!(reason("e2", "c3"), Some(IllegalMove::Pinned));
+        assert_eq!(reason("e2", "e4"), Some(IllegalMove::NotADestination));
+
+        // A king walking into check isn't pinned, and neither is a piece that fails to answer a check
+        let game_state = position("4r1k1/8/8/8/8/8/1N6/3K4 w - - 0 1", Variant::Standard);
+        let reason = |from, to| game_state.illegal_move_reason(square(from), square(to));
+        assert_eq!(reason("d1", "e1"), Some(IllegalMove::LeavesKingInCheck));
+        let game_state = position("4r1k1/8/8/8/8/8/1N6/4K3 w - - 0 1", Variant::Standard);
+        let reason = |from, to| game_state.illegal_move_reason(square(from), square(to));
+        assert_eq!(reason("b2", "d3"), Some(IllegalMove::LeavesKingInCheck));
+    }
+
+    #[test]
+    fn undo_policy_plies() {
+        let vs_ai = PlayerConfig {
+            white: PlayerKind::Human,
+            black: PlayerKind::Ai,
+        };
+        let humans = PlayerConfig::default();
+        let (white, black) = (PieceColor::White, PieceColor::Black);
+
+        // Against the AI, back past its reply to the human's own move
+        assert_eq!(UndoPolicy::ToHumanTurn.plies(&vs_ai, white, 2), 2);
+        assert_eq!(UndoPolicy::ToHumanTurn.plies(&vs_ai, black, 1), 1);
+        assert_eq!(UndoPolicy::ToHumanTurn.plies(&humans, white, 2), 1);
+        assert_eq!(UndoPolicy::SinglePly.plies(&vs_ai, white, 2), 1);
+        assert_eq!(UndoPolicy::SinglePly.plies(&vs_ai, white, 0), 0);
+        assert_eq!(UndoPolicy::ToHumanTurn.plies(&vs_ai, white, 0), 0);
+    }
+
+    // Plays 1.e4 e5, then undoes once with the given policy against an AI playing Black
+    fn undo_after_e4_e5(policy: UndoPolicy) -> App {
+        let mut app = game_app();
+        test_app::play(&mut app, &["e2e4", "e7e5"]);
+        app.insert_resource(policy).insert_resource(PlayerConfig {
+            white: PlayerKind::Human,
+            black: PlayerKind::Ai,
+        });
+        app.world.send_event(RequestUndoEvent);
+        for _ in 0..3 {
+            test_app::update(&mut app);
+        }
+        app
+    }
+
+    #[test]
+    fn full_ply_undo_goes_back_to_the_human() {
+        let mut app = undo_after_e4_e5(UndoPolicy::ToHumanTurn);
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.plies, 0);
+        assert_eq!(game_state.curr_player, PieceColor::White);
+        assert_eq!(app.world.resource::<MoveHistory>().moves.len(), 0);
+        assert!(!app.world.resource::<AiPaused>().0);
+        let board = game_state.board();
+        assert_eq!(test_app::entity_board(&mut app), board);
+    }
+
+    #[test]
+    fn half_ply_undo_pauses_the_ai() {
+        let mut app = undo_after_e4_e5(UndoPolicy::SinglePly);
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.plies, 1);
+        assert_eq!(game_state.curr_player, PieceColor::Black);
+        assert_eq!(app.world.resource::<MoveHistory>().moves.len(), 1);
+        assert!(app.world.resource::<AiPaused>().0);
+        let board = game_state.board();
+        assert_eq!(test_app::entity_board(&mut app), board);
+    }
+
+    #[test]
+    fn in_check_is_set_by_a_check_and_cleared_by_the_escape() {
+        let mut app = game_app();
+        test_app::load(&mut app, "4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(app.world.resource::<InCheck>().0, None);
+
+        test_app::play(&mut app, &["a1a8"]);
+        assert_eq!(app.world.resource::<InCheck>().0, Some(PieceColor::Black));
+        test_app::play(&mut app, &["e8e7"]);
+        assert_eq!(app.world.resource::<InCheck>().0, None);
+    }
+
+    #[test]
+    fn select_can_be_rebound_to_the_right_button() {
+        let mut app = game_app();
+        app.insert_resource(InputBindings {
+            select: MouseButton::Right,
+            inspect: MouseButton::Left,
+            cancel: MouseButton::Left,
+            ..default()
+        });
+        test_app::click(&mut app, MouseButton::Right, "e2");
+        test_app::update(&mut app);
+        assert!(app.world.resource::<TurnData>().selected_piece().is_some());
+
+        // The left button now cancels
+        test_app::click(&mut app, MouseButton::Left, "e2");
+        test_app::update(&mut app);
+        assert_eq!(app.world.resource::<TurnData>().selected_piece(), None);
+
+        test_app::click(&mut app, MouseButton::Right, "e2");
+        test_app::update(&mut app);
+        test_app::click(&mut app, MouseButton::Right, "e4");
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+        assert_eq!(
+            app.world.resource::<GameState>().get(square("e4")),
+            Some(piece(PieceColor::White, PieceKind::Pawn(true)))
+        );
+    }
+
+    #[test]
+    fn each_ending_has_its_own_message() {
+        use PieceColor::{Black, White};
+        let message = |game_over| game_over_message(&game_over, Variant::Standard);
+        let expected = [
+            (GameOver::Checkmate(White), "CHECKMATE!\nWhite wins!"),
+            (GameOver::Checkmate(Black), "CHECKMATE!\nBlack wins!"),
+            (
+                GameOver::Stalemate,
+                "STALEMATE\nNo legal moves, it's a draw",
+            ),
+            (
+                GameOver::Draw(DrawReason::FiftyMove),
+                "DRAW\nfifty-move rule",
+            ),
+            (
+                GameOver::Draw(DrawReason::ThreefoldRepetition),
+                "DRAW\nthreefold repetition",
+            ),
+            (GameOver::Draw(DrawReason::Agreement), "DRAW\nby agreement"),
+            (
+                GameOver::DrawAutomatic(DrawReason::SeventyFiveMove),
+                "DRAW\nseventy-five-move rule",
+            ),
+            (
+                GameOver::DrawAutomatic(DrawReason::FivefoldRepetition),
+                "DRAW\nfivefold repetition",
+            ),
+            (
+                GameOver::DrawInsufficientMaterial,
+                "DRAW\nNot enough material left to checkmate",
+            ),
+            (GameOver::Timeout(Black), "TIME'S UP!\nBlack wins!"),
+            (GameOver::Resignation(White), "Black RESIGNS\nWhite wins!"),
+            (GameOver::Variant(White), "VICTORY!\nWhite wins!"),
+        ];
+        for (game_over, text) in expected {
+            assert_eq!(message(game_over), text);
+        }
+        let mut messages: Vec<_> = expected.iter().map(|(_, text)| *text).collect();
+        messages.sort();
+        messages.dedup();
+        assert_eq!(messages.len(), expected.len());
+
+        // Variant wins are told in the variant's own terms
+        let variant_win = |variant| game_over_message(&GameOver::Variant(Black), variant);
+        assert_eq!(
+            variant_win(Variant::KingOfTheHill),
+            "KING OF THE HILL!\nBlack wins!"
+        );
+        assert_eq!(
+            variant_win(Variant::ThreeCheck),
+            "THREE CHECKS!\nBlack wins!"
+        );
+        assert_eq!(variant_win(Variant::Atomic), "KABOOM!\nBlack wins!");
+    }
+
+    #[test]
+    fn pawn_cant_jump_a_blocker() {
+        let pawn = piece(PieceColor::White, PieceKind::Pawn(false));
+
+        // A knight right in front blocks both moves, even with e4 empty
+        let game_state = position("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1", Variant::Standard);
+        let (moves, _) = game_state.moves_and_captures(pawn, square("e2"));
+        assert!(moves.is_empty());
+        assert_eq!(
+            game_state.illegal_move_reason(square("e2"), square("e4")),
+            Some(IllegalMove::NotADestination)
+        );
+
+        // Something on e4 only stops the two-square move
+        let game_state = position("4k3/8/8/8/4n3/8/4P3/4K3 w - - 0 1", Variant::Standard);
+        let (moves, _) = game_state.moves_and_captures(pawn, square("e2"));
+        assert_eq!(moves, [square("e3")]);
+
+        // Same for Black
+        let pawn = piece(PieceColor::Black, PieceKind::Pawn(false));
+        let game_state = position("4k3/4p3/4N3/8/8/8/8/4K3 b - - 0 1", Variant::Standard);
+        let (moves, _) = game_state.moves_and_captures(pawn, square("e7"));
+        assert!(moves.is_empty());
+    }
+
+    // Moves and captures worked out from the board array one square at a time, the way they were before
+    // the pieces were kept as bitboards, in the order that was generated in
+    fn array_moves_and_captures(
+        game_state: &GameState,
+        piece: Piece,
+        from: BoardPosition,
+    ) -> (Vec<BoardPosition>, Vec<BoardPosition>) {
+        let board = game_state.board();
+        let at = |pos: BoardPosition| {
+            pos.is_in_bounds()
+                .then(|| board[pos.row as usize][pos.col as usize])
+        };
+        let (mut moves, mut captures) = (Vec::new(), Vec::new());
+        let save = |pos: BoardPosition, moves: &mut Vec<_>, captures: &mut Vec<_>| match at(pos) {
+            Some(None) => {
+                moves.push(pos);
+                true
+            }
+            Some(Some(other)) if other.color != piece.color => {
+                captures.push(pos);
+                false
+            }
+            _ => false,
+        };
+
+        #[rustfmt::skip]
+        let lines: &[(i8, i8)] = match piece.kind {
+            PieceKind::Queen => &[(-1, 0), (1, 0), (0, 1), (0, -1), (-1, -1), (-1, 1), (1, -1), (1, 1)],
+            PieceKind::Rook => &[(-1, 0), (1, 0), (0, 1), (0, -1)],
+            PieceKind::Bishop => &[(-1, -1), (-1, 1), (1, -1), (1, 1)],
+            _ => &[],
+        };
+        for &dir in lines {
+            let mut pos = from + dir;
+            while save(pos, &mut moves, &mut captures) {
+                pos += dir;
+            }
+        }
+        let steps: &[(i8, i8)] = match piece.kind {
+            PieceKind::King => &KING_OFFSETS,
+            PieceKind::Knight => &bitboard::KNIGHT_OFFSETS,
+            _ => &[],
+        };
+        for &offset in steps {
+            save(from + offset, &mut moves, &mut captures);
+        }
+
+        if let PieceKind::Pawn(has_moved) = piece.kind {
+            let next_row = match piece.color {
+                PieceColor::White => 1,
+                PieceColor::Black => -1,
+            };
+            if at(from + (next_row, 0)) == Some(None) {
+                moves.push(from + (next_row, 0));
+                let double = !has_moved && game_state.rules.pawns.double_move;
+                if double && at(from + (next_row * 2, 0)) == Some(None) {
+                    moves.push(from + (next_row * 2, 0));
+                }
+            }
+            for col in [-1, 1] {
+                let pos = from + (next_row, col);
+                match at(pos) {
+                    Some(Some(other)) if other.color != piece.color => captures.push(pos),
+                    Some(None)
+                        if game_state
+                            .en_passant
+                            .is_some_and(|ep| ep.capture_pos == pos) =>
+                    {
+                        captures.push(pos)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        (moves, captures)
+    }
+
+    // The pieces of one side that attack the square, going by the moves generated from the board array.
+    // A piece of the other side is put on the square so that those moves are captures.
+    fn array_attackers(
+        game_state: &GameState,
+        pos: BoardPosition,
+        by: PieceColor,
+    ) -> Vec<BoardPosition> {
+        let mut target = game_state.clone();
+        target.set_pos(pos, Some(piece(by.next(), PieceKind::Knight)));
+        target
+            .pieces()
+            .filter(|(piece, from)| {
+                piece.color == by
+                    && array_moves_and_captures(&target, *piece, *from)
+                        .1
+                        .contains(&pos)
+            })
+            .map(|(_, from)| from)
+            .collect()
+    }
+
+    // Legal moves found by making each one on a copy of the game and checking the king with the array
+    fn array_legal_moves(game_state: &GameState) -> Vec<(BoardPosition, BoardPosition)> {
+        let player = game_state.curr_player;
+        let mut legal = Vec::new();
+        for (piece, from) in game_state.pieces().filter(|(p, _)| p.color == player) {
+            let (mut moves, captures) = array_moves_and_captures(game_state, piece, from);
+            moves.extend(game_state.castling_moves(piece, from));
+            for to in moves.into_iter().chain(captures) {
+                let mut after = game_state.clone();
+                after.apply_movement(from, to);
+                let safe = match after.get_king_pos(player) {
+                    None => false,
+                    Some(_) if !after.has_king(player.next()) => true,
+                    Some(king) => array_attackers(&after, king, player.next()).is_empty(),
+                };
+                if safe {
+                    legal.push((from, to));
+                }
+            }
+        }
+        legal.sort_by_key(|(from, to)| (square_index(*from), square_index(*to)));
+        legal
+    }
+
+    #[test]
+    fn bitboard_move_generation_agrees_with_the_array_over_random_games() {
+        let mut rng = StdRng::seed_from_u64(866);
+        let mut positions = 0;
+        for game in 0..40 {
+            let variant = if game % 4 == 3 {
+                Variant::Atomic
+            } else {
+                Variant::Standard
+            };
+            let mut game_state = position(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                variant,
+            );
+            for _ in 0..120 {
+                positions += 1;
+                assert_eq!(
+                    Bitboards::from_board(&game_state.board()),
+                    game_state.bitboards
+                );
+                for pos in BoardPosition::all() {
+                    for by in [PieceColor::White, PieceColor::Black] {
+                        let attackers: Vec<_> =
+                            squares(game_state.bitboards.attackers(pos, by)).collect();
+                        assert_eq!(
+                            attackers,
+                            array_attackers(&game_state, pos, by),
+                            "{game_state}"
+                        );
+                    }
+                }
+
+                // Every piece of both sides, legal or not, then the legal moves of the side to move
+                for (piece, from) in game_state.pieces() {
+                    let (mut moves, mut captures) =
+                        array_moves_and_captures(&game_state, piece, from);
+                    moves.sort_by_key(|pos| square_index(*pos));
+                    captures.sort_by_key(|pos| square_index(*pos));
+                    assert_eq!(
+                        game_state.pseudo_moves_and_captures(piece, from),
+                        (moves, captures),
+                        "{piece:?} on {from:?} in\n{game_state}"
+                    );
+                }
+                let mut legal: Vec<_> = game_state
+                    .legal_moves()
+                    .into_iter()
+                    .map(|mv| (mv.from, mv.to))
+                    .collect();
+                legal.dedup(); // Promotions are listed once per piece
+                legal.sort_by_key(|(from, to)| (square_index(*from), square_index(*to)));
+                assert_eq!(legal, array_legal_moves(&game_state), "{game_state}");
+
+                let moves = game_state.legal_moves();
+                if moves.is_empty() || game_state.forced_game_over().is_some() {
+                    break;
+                }
+                game_state.make_move(moves[rng.gen_range(0..moves.len())]);
+            }
+        }
+        assert!(positions > 1000);
+    }
+
+    #[test]
+    fn step_tables_match_working_the_targets_out() {
+        for from in BoardPosition::all() {
+            // Knights two steps out along each diagonal's row, then along its column
+            let mut knight = 0;
+            for diag in [(-1, -1), (-1, 1), (1, -1), (1, 1)] {
+                let diag_pos = from + diag;
+                for pos in [diag_pos + (diag.0, 0), diag_pos + (0, diag.1)] {
+                    if pos.is_in_bounds() {
+                        knight |= bitboard::square_bit(pos);
+                    }
+                }
+            }
+            assert_eq!(bitboard::KNIGHT_ATTACKS[square_index(from)], knight);
+
+            let king = (-1..=1)
+                .flat_map(|row| (-1..=1).map(move |col| (row, col)))
+                .filter(|offset| *offset != (0, 0))
+                .map(|offset| from + offset)
+                .filter(|pos| pos.is_in_bounds())
+                .fold(0, |bits, pos| bits | bitboard::square_bit(pos));
+            assert_eq!(bitboard::KING_ATTACKS[square_index(from)], king);
+        }
+    }
+
+    #[test]
+    fn three_second_gap_is_recorded_for_the_move() {
+        let mut move_times = MoveTimes::default();
+        move_times.start_turn(10.0);
+        move_times.start_turn(11.0); // Still timing from the first frame it was awaited
+        move_times.end_turn(0, 13.0);
+        assert_eq!(move_times.times(), [Duration::from_secs(3)]);
+
+        // A move that was never awaited took no time, and nor did imported ones before it
+        move_times.end_turn(3, 14.0);
+        assert_eq!(
+            move_times.times(),
+            [
+                Duration::from_secs(3),
+                Duration::ZERO,
+                Duration::ZERO,
+                Duration::ZERO
+            ]
+        );
+
+        // Undoing drops the undone moves and the turn being timed
+        move_times.start_turn(15.0);
+        move_times.truncate(1);
+        move_times.end_turn(1, 20.0);
+        assert_eq!(move_times.times(), [Duration::from_secs(3), Duration::ZERO]);
+    }
+
+    #[test]
+    fn every_move_played_is_timed() {
+        let mut app = game_app();
+        test_app::play(&mut app, &["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(app.world.resource::<MoveTimes>().times().len(), 3);
+    }
+
+    #[test]
+    fn pawn_rules_can_be_turned_off() {
+        let pawn = piece(PieceColor::White, PieceKind::Pawn(false));
+        let without = |fen, pawns| {
+            let mut game_state = position(fen, Variant::Standard);
+            game_state.rules.pawns = pawns;
+            game_state
+        };
+
+        // A starting pawn only has the one-square move without the double move
+        let no_double = PawnRules {
+            double_move: false,
+            ..default()
+        };
+        let game_state = without(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            no_double,
+        );
+        let (moves, _) = game_state.moves_and_captures(pawn, square("e2"));
+        assert_eq!(moves, [square("e3")]);
+        assert_eq!(game_state.legal_moves().len(), 12);
+        assert!(!game_state.is_legal_move(notation::parse_uci("e2e4").unwrap()));
+
+        // Without en passant, a double move can't be taken in passing
+        let no_en_passant = PawnRules {
+            en_passant: false,
+            ..default()
+        };
+        let mut game_state = without("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", no_en_passant);
+        play(&mut game_state, &["e2e4"]);
+        assert!(!game_state.is_legal_move(notation::parse_uci("d4e3").unwrap()));
+
+        // Without promotion, a pawn reaching the end stays a pawn
+        let no_promotion = PawnRules {
+            promotion: false,
+            ..default()
+        };
+        let mut game_state = without("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", no_promotion);
+        assert!(!game_state.is_promotion(square("a7"), square("a8")));
+        play(&mut game_state, &["a7a8"]);
+        assert!(matches!(
+            game_state.get_pos(square("a8")).map(|piece| piece.kind),
+            Some(PieceKind::Pawn(_))
+        ));
+    }
+
+    #[test]
+    fn stale_selections_are_dropped_without_panicking() {
+        let mut game_state = position(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            Variant::Standard,
+        );
+        assert!(!is_stale_selection(&game_state, Some(square("e2"))));
+        assert!(is_stale_selection(&game_state, None));
+        assert!(is_stale_selection(&game_state, Some(square("e4"))));
+        assert!(is_stale_selection(&game_state, Some(square("e7"))));
+        game_state.curr_player = PieceColor::Black;
+        assert!(is_stale_selection(&game_state, Some(square("e2"))));
+
+        // The selected piece is despawned out from under the turn manager
+        let mut app = game_app();
+        test_app::click(&mut app, MouseButton::Left, "e2");
+        test_app::update(&mut app);
+        let pawn = app.world.resource::<TurnData>().selected_piece().unwrap();
+        app.world.despawn(pawn);
+        app.world
+            .resource_mut::<GameState>()
+            .set(BoardPosition { row: 1, col: 4 }, None);
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+
+        let turn_data = app.world.resource::<TurnData>();
+        assert_eq!(turn_data.phase(), TurnPhase::SelectPiece);
+        assert_eq!(turn_data.selected_piece(), None);
+        let mut valid_moves = app.world.query_filtered::<(), With<ValidMove>>();
+        assert_eq!(valid_moves.iter(&app.world).count(), 0);
+
+        // And the game carries on
+        test_app::play(&mut app, &["d2d4"]);
+        assert_eq!(app.world.resource::<GameState>().plies, 1);
+    }
+
+    #[test]
+    fn random_games_always_end_properly() {
+        let mut rng = StdRng::seed_from_u64(884);
+        for variant in [
+            Variant::Standard,
+            Variant::KingOfTheHill,
+            Variant::ThreeCheck,
+            Variant::Atomic,
+        ] {
+            for start in [StartPosition::Standard, StartPosition::Chess960(884)] {
+                for _ in 0..10 {
+                    let mut game_state = GameState {
+                        rules: GameRules {
+                            variant,
+                            start,
+                            ..default()
+                        },
+                        ..default()
+                    };
+                    game_state.set_board(&start.board());
+                    game_state.castling = CastlingRights::from_board(&start.board());
+
+                    let game_over = game_state
+                        .play_out(|game_state| RandomBot::pick(game_state, &mut rng).unwrap());
+                    assert_eq!(game_state.game_over, Some(game_over));
+                    // A game that had to end did so for that reason, and one that could have gone on was drawn
+                    match game_over {
+                        GameOver::Checkmate(_)
+                        | GameOver::Stalemate
+                        | GameOver::DrawInsufficientMaterial
+                        | GameOver::Variant(_) => {
+                            assert_eq!(
+                                game_state.forced_game_over(),
+                                Some(game_over),
+                                "{game_state}"
+                            )
+                        }
+                        GameOver::Draw(_) | GameOver::DrawAutomatic(_) => {
+                            assert_eq!(game_state.forced_game_over(), None, "{game_state}")
+                        }
+                        GameOver::Timeout(_) | GameOver::Resignation(_) => {
+                            panic!("{game_over:?} without a clock or a player")
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn en_passant_cant_expose_the_king_along_the_rank() {
+        let en_passant = |uci| notation::parse_uci(uci).unwrap();
+
+        // Taking c6 takes both pawns off the fifth rank, leaving the rook looking at the king. Neither pawn is
+        // pinned on its own.
+        let game_state = position("8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1", Variant::Standard);
+        assert!(!game_state.is_legal_move(en_passant("b5c6")));
+        assert!(!game_state.legal_moves().contains(&en_passant("b5c6")));
+        assert_eq!(
+            game_state.illegal_move_reason(square("b5"), square("c6")),
+            Some(IllegalMove::LeavesKingInCheck)
+        );
+        assert!(game_state.is_legal_move(en_passant("b5b6")));
+
+        // The same for Black, and still fine with nothing on the far side of the rank
+        let game_state = position("4K3/8/8/8/R4Ppk/8/8/8 b - f3 0 1", Variant::Standard);
+        assert_eq!(
+            game_state.illegal_move_reason(square("g4"), square("f3")),
+            Some(IllegalMove::LeavesKingInCheck)
+        );
+        let game_state = position("4K3/8/8/8/5Ppk/8/8/8 b - f3 0 1", Variant::Standard);
+        assert!(game_state.is_legal_move(en_passant("g4f3")));
+    }
+
+    #[test]
+    fn built_positions_round_trip_through_fen() {
+        let kings = GameStateBuilder::new()
+            .piece(piece(PieceColor::White, PieceKind::King), "e1")
+            .piece(piece(PieceColor::Black, PieceKind::King), "e8");
+
+        let bare = kings.clone().build().unwrap();
+        let after_d5 = kings
+            .clone()
+            .piece(piece(PieceColor::White, PieceKind::Pawn(true)), "e5")
+            .piece(piece(PieceColor::Black, PieceKind::Pawn(true)), "d5")
+            .en_passant("d6")
+            .build()
+            .unwrap();
+        let castling = kings
+            .clone()
+            .piece(piece(PieceColor::White, PieceKind::Rook), "a1")
+            .piece(piece(PieceColor::White, PieceKind::Rook), "h1")
+            .piece(piece(PieceColor::Black, PieceKind::Rook), "a8")
+            .castling_rights("Kq")
+            .to_move(PieceColor::Black)
+            .build()
+            .unwrap();
+        // No rook for it, so no right
+        let missing_rook = kings.castling_rights("KQkq").build().unwrap();
+
+        for (game_state, fen) in [
+            (bare, "4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            (after_d5, "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"),
+            (castling, "r3k3/8/8/8/8/8/8/R3K2R b Kq - 0 1"),
+            (missing_rook, "4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+        ] {
+            assert_eq!(notation::to_fen(&game_state), fen);
+            let parsed = notation::from_fen(fen).unwrap();
+            assert_eq!(notation::to_fen(&parsed), fen);
+            assert_eq!(parsed.board(), game_state.board());
+        }
+    }
+
+    // Clicks the pawn over to a8, checking whether the turn stopped to ask what it becomes
+    fn promote_a7(app: &mut App) -> bool {
+        test_app::load(app, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        test_app::click(app, MouseButton::Left, "a7");
+        test_app::update(app);
+        test_app::click(app, MouseButton::Left, "a8");
+        let mut asked = false;
+        for _ in 0..5 {
+            asked |= app.world.resource::<TurnData>().phase() == TurnPhase::Promote;
+            test_app::update(app);
+        }
+        asked
+    }
+
+    fn on_a8(app: &App) -> Option<PieceKind> {
+        app.world.resource::<GameState>().board()[7][0].map(|piece| piece.kind)
+    }
+
+    #[test]
+    fn auto_promotion_skips_the_prompt() {
+        assert_eq!(
+            promotion_choice(AutoPromote(Some(PieceKind::Rook)), false),
+            Some(PieceKind::Rook)
+        );
+        assert_eq!(
+            promotion_choice(AutoPromote(Some(PieceKind::Rook)), true),
+            None
+        );
+        assert_eq!(promotion_choice(AutoPromote(None), false), None);
+
+        let mut app = game_app();
+        app.insert_resource(AutoPromote(Some(PieceKind::Rook)));
+        assert!(!promote_a7(&mut app));
+        assert_eq!(on_a8(&app), Some(PieceKind::Rook));
+        assert_eq!(app.world.resource::<GameState>().plies, 1);
+    }
+
+    #[test]
+    fn without_auto_promotion_the_player_is_asked() {
+        let mut app = game_app();
+        app.insert_resource(AutoPromote(None));
+        assert!(promote_a7(&mut app));
+        assert_eq!(on_a8(&app), None);
+
+        test_app::press(&mut app, KeyCode::Key3);
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+        assert_eq!(on_a8(&app), Some(PieceKind::Bishop));
+
+        // Holding Alt asks even with a piece picked
+        let mut app = game_app();
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(KeyCode::LAlt),
+            state: ButtonState::Pressed,
+        });
+        assert!(promote_a7(&mut app));
+        test_app::press(&mut app, KeyCode::Escape);
+        assert_eq!(
+            app.world.resource::<TurnData>().phase(),
+            TurnPhase::SelectPiece
+        );
+        assert!(matches!(
+            app.world.resource::<GameState>().board()[6][0].map(|piece| piece.kind),
+            Some(PieceKind::Pawn(_))
+        ));
+    }
+
+    #[derive(Default)]
+    struct PhaseLog(Vec<(TurnPhase, TurnPhase)>);
+
+    fn log_phases(mut events: EventReader<TurnPhaseChanged>, mut log: ResMut<PhaseLog>) {
+        log.0.extend(events.iter().map(|ev| (ev.from, ev.to)));
+    }
+
+    #[test]
+    fn each_phase_of_a_move_is_announced() {
+        let mut app = game_app();
+        app.init_resource::<PhaseLog>()
+            .add_system_to_stage(CoreStage::Last, log_phases);
+        test_app::update(&mut app);
+        assert_eq!(
+            app.world.resource::<TurnData>().phase(),
+            TurnPhase::SelectPiece
+        );
+        app.world.resource_mut::<PhaseLog>().0.clear();
+
+        test_app::click(&mut app, MouseButton::Left, "e2");
+        test_app::update(&mut app);
+        test_app::click(&mut app, MouseButton::Left, "e4");
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+        assert_eq!(
+            app.world.resource::<PhaseLog>().0,
+            [
+                (TurnPhase::SelectPiece, TurnPhase::ShowHighlights),
+                (TurnPhase::ShowHighlights, TurnPhase::SelectTarget),
+                (TurnPhase::SelectTarget, TurnPhase::AnimateMove),
+                (TurnPhase::AnimateMove, TurnPhase::CheckCapture),
+                (TurnPhase::CheckCapture, TurnPhase::EndTurn),
+                (TurnPhase::EndTurn, TurnPhase::CheckForGameOver),
+                (TurnPhase::CheckForGameOver, TurnPhase::SelectPiece),
+            ]
+        );
+
+        // Nothing more while waiting for Black
+        app.world.resource_mut::<PhaseLog>().0.clear();
+        for _ in 0..5 {
+            test_app::update(&mut app);
+        }
+        assert!(app.world.resource::<PhaseLog>().0.is_empty());
+    }
+
+    #[test]
+    fn scripted_fools_mate_ends_in_checkmate() {
+        let mut app = game_app();
+        let moves = ["f2f3", "e7e5", "g2g4", "d8h4"];
+        app.insert_resource(ScriptedMoves::new(
+            moves.iter().map(|uci| notation::parse_uci(uci).unwrap()),
+        ));
+        // One move per turn, each waiting for the last one's animation to finish
+        for _ in 0..100 {
+            test_app::update(&mut app);
+        }
+
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.plies, 4);
+        assert_eq!(
+            game_state.game_over,
+            Some(GameOver::Checkmate(PieceColor::Black))
+        );
+        assert!(app.world.resource::<ScriptedMoves>().0.is_empty());
+        let played: Vec<String> = app
+            .world
+            .resource::<MoveHistory>()
+            .moves()
+            .iter()
+            .map(|&mv| notation::to_uci(mv))
+            .collect();
+        assert_eq!(played, moves);
+    }
+
+    #[test]
+    fn no_legal_moves_stops_early_with_the_same_answer() {
+        // Counts the moves tested while running `f`
+        let checks = |f: &dyn Fn() -> bool| {
+            MOVE_CHECKS.with(|checks| checks.set(0));
+            let result = f();
+            (result, MOVE_CHECKS.with(|checks| checks.get()))
+        };
+
+        let fens = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                false,
+            ),
+            (
+                "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 2 3",
+                false,
+            ),
+            // In check
+            ("4k3/8/8/8/8/8/8/R3K2r w - - 0 1", false),
+            // Fool's mate
+            (
+                "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+                true,
+            ),
+            // Stalemate
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", true),
+        ];
+        for (fen, stuck) in fens {
+            let game_state = position(fen, Variant::Standard);
+            let (none, early) = checks(&|| game_state.no_legal_moves());
+            let (empty, full) = checks(&|| game_state.legal_moves().is_empty());
+            assert_eq!(none, stuck, "{fen}");
+            assert_eq!(empty, stuck, "{fen}");
+            if stuck {
+                // Every move has to be tried to know there aren't any
+                assert_eq!(early, full, "{fen}");
+            } else {
+                assert!(early < full, "{fen}: {early} checks against {full}");
+            }
+        }
+    }
+
+    #[test]
+    fn single_moves_are_checked_like_the_full_list() {
+        // The e4 knight is pinned to the king by the e8 rook
+        let game_state = position("4r1k1/8/8/8/4N3/8/8/4K3 w - - 0 1", Variant::Standard);
+        let mv = |uci| notation::parse_uci(uci).unwrap();
+        let legal = game_state.legal_moves();
+
+        assert!(game_state.is_legal_move(mv("e1d2")));
+        assert!(!game_state.is_legal_move(mv("e4f6"))); // Pinned
+        assert!(!game_state.is_legal_move(mv("e4e5"))); // Not a knight move
+        let off_board = [
+            (square("e4"), BoardPosition { row: 5, col: 8 }),
+            (square("e1"), BoardPosition { row: -1, col: 4 }),
+            (BoardPosition { row: 8, col: 4 }, square("e4")),
+        ];
+        for (from, to) in off_board {
+            let mv = Move {
+                from,
+                to,
+                promotion: None,
+            };
+            assert!(!game_state.is_legal_move(mv), "{mv:?}");
+            assert!(!legal.contains(&mv));
+        }
+
+        for mv in ["e1d2", "e4f6", "e4e5"].map(mv) {
+            assert_eq!(game_state.is_legal_move(mv), legal.contains(&mv), "{mv:?}");
+        }
+    }
+
+    #[test]
+    fn seventy_five_moves_draw_without_a_claim() {
+        let mut app = game_app();
+        test_app::load(&mut app, "4k3/8/8/8/8/8/8/1N2K1N1 w - - 146 80");
+        test_app::play(&mut app, &["b1c3", "e8d8"]);
+        // The fifty-move draw could have been claimed all along, but nobody did
+        assert_eq!(
+            app.world.resource::<ClaimableDraw>().0,
+            Some(DrawReason::FiftyMove)
+        );
+        assert_eq!(app.world.resource::<GameState>().game_over, None);
+
+        test_app::play(&mut app, &["c3b1", "d8e8"]);
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.halfmove_clock, 150);
+        assert_eq!(
+            game_state.game_over,
+            Some(GameOver::DrawAutomatic(DrawReason::SeventyFiveMove))
+        );
+    }
+
+    #[test]
+    fn fifth_repetition_draws_without_a_claim() {
+        let mut app = game_app();
+        test_app::load(&mut app, "4k3/8/8/8/8/8/8/1N2K3 w - - 0 1");
+        let shuffle = ["b1c3", "e8d8", "c3b1", "d8e8"];
+        for _ in 0..3 {
+            test_app::play(&mut app, &shuffle);
+        }
+        assert_eq!(
+            app.world.resource::<ClaimableDraw>().0,
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        assert_eq!(app.world.resource::<GameState>().game_over, None);
+
+        test_app::play(&mut app, &shuffle);
+        assert_eq!(
+            app.world.resource::<GameState>().game_over,
+            Some(GameOver::DrawAutomatic(DrawReason::FivefoldRepetition))
+        );
+    }
+
+    #[test]
+    fn mate_on_the_seventy_fifth_move_still_wins() {
+        let mut app = game_app();
+        test_app::load(&mut app, "6k1/5ppp/8/8/8/8/8/R3K3 w - - 149 90");
+        test_app::play(&mut app, &["a1a8"]);
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(game_state.halfmove_clock, 150);
+        assert_eq!(
+            game_state.game_over,
+            Some(GameOver::Checkmate(PieceColor::White))
+        );
+    }
+
+    #[test]
+    fn boards_print_as_ascii() {
+        let mut game_state = position(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            Variant::Standard,
+        );
+        assert_eq!(
+            game_state.to_string(),
+            "\
+8 r n b q k b n r
+7 p p p p p p p p
+6 . . . . . . . .
+5 . . . . . . . .
+4 . . . . . . . .
+3 . . . . . . . .
+2 P P P P P P P P
+1 R N B Q K B N R
+  a b c d e f g h
+White to move"
+        );
+
+        play(&mut game_state, &["e2e4"]);
+        let text = game_state.to_string();
+        assert_eq!(text.lines().nth(4), Some("4 . . . . P . . ."));
+        assert_eq!(text.lines().nth(6), Some("2 P P P P . P P P"));
+        assert!(text.ends_with("\nBlack to move"));
+    }
+
+    #[test]
+    fn move_hooks_see_the_position_after_the_move() {
+        let mut app = game_app();
+        test_app::load(
+            &mut app,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        app.world
+            .resource_mut::<MoveHooks>()
+            .add(move |game_state, mv| {
+                let entry = (notation::to_uci(*mv), notation::to_fen(game_state));
+                log.lock().unwrap().push(entry);
+            });
+        test_app::play(&mut app, &["e2e4", "c7c5"]);
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(
+            *seen,
+            [
+                (
+                    "e2e4".to_string(),
+                    "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string()
+                ),
+                (
+                    "c7c5".to_string(),
+                    "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn legal_moves_are_only_for_the_side_to_move() {
+        let mut game_state = position(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            Variant::Standard,
+        );
+        let color = |game_state: &GameState, mv: &Move| game_state.get_pos(mv.from).unwrap().color;
+        let white = game_state.legal_moves();
+        assert_eq!(white.len(), 20);
+        assert!(white
+            .iter()
+            .all(|mv| color(&game_state, mv) == PieceColor::White));
+        assert_eq!(game_state.legal_moves_for(PieceColor::White), white);
+
+        let black = game_state.legal_moves_for(PieceColor::Black);
+        assert_eq!(black.len(), 20);
+        assert!(black
+            .iter()
+            .all(|mv| color(&game_state, mv) == PieceColor::Black));
+
+        // En passant only belongs to the side to move
+        play(&mut game_state, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+        let en_passant = notation::parse_uci("e5d6").unwrap();
+        assert!(game_state.legal_moves().contains(&en_passant));
+        play(&mut game_state, &["a2a3"]);
+        assert!(!game_state
+            .legal_moves_for(PieceColor::White)
+            .contains(&en_passant));
+    }
+
+    #[test]
+    fn sound_pitch_stays_within_its_variation() {
+        let mut rng = GameRng::new(7);
+        let pitch = SoundPitch { variation: 0.06 };
+        let picks: Vec<f32> = (0..1000).map(|_| pitch.pick(rng.rng())).collect();
+        let within = |p: f32, variation: f32| (p - 1.0).abs() <= variation + f32::EPSILON;
+        assert!(picks.iter().all(|&p| within(p, 0.06)));
+        // Spread over the range, not stuck at one end
+        assert!(picks.iter().any(|&p| p < 0.97));
+        assert!(picks.iter().any(|&p| p > 1.03));
+
+        let none = SoundPitch { variation: 0.0 };
+        assert_eq!(none.pick(rng.rng()), 1.0);
+        // Given the wrong way round it's the same range
+        let negative = SoundPitch { variation: -0.1 };
+        assert!(within(negative.pick(rng.rng()), 0.1));
+
+        // The same seed picks the same pitches
+        let mut again = GameRng::new(7);
+        let repeat: Vec<f32> = (0..1000).map(|_| pitch.pick(again.rng())).collect();
+        assert_eq!(picks, repeat);
+    }
+
+    // The sounds played while making the moves
+    fn sounds_for(app: &mut App, moves: &[&str]) -> Vec<PlaySoundEvent> {
+        let mut reader = app.world.resource::<Events<PlaySoundEvent>>().get_reader();
+        app.insert_resource(ScriptedMoves::new(
+            moves.iter().map(|uci| notation::parse_uci(uci).unwrap()),
+        ));
+        let mut sounds = Vec::new();
+        for _ in 0..20 * moves.len() {
+            test_app::update(app);
+            let events = app.world.resource::<Events<PlaySoundEvent>>();
+            sounds.extend(reader.iter(events).cloned());
+        }
+        sounds
+    }
+
+    #[test]
+    fn moves_and_captures_play_their_sounds_at_a_varied_pitch() {
+        let mut app = game_app();
+        let variation = app.world.resource::<SoundPitch>().variation;
+        let sounds = sounds_for(&mut app, &["e2e4", "d7d5", "e4d5"]);
+        let paths: Vec<&str> = sounds.iter().map(|sound| sound.path).collect();
+        assert_eq!(
+            paths,
+            ["sounds/move.wav", "sounds/move.wav", "sounds/capture.wav"]
+        );
+        assert!(sounds
+            .iter()
+            .all(|sound| (sound.speed - 1.0).abs() <= variation + f32::EPSILON));
+
+        // A check plays just the check sound, whether or not it also takes something
+        test_app::load(&mut app, "4k2r/8/8/8/8/8/8/4K2R w - - 0 1");
+        let sounds = sounds_for(&mut app, &["h1h8"]);
+        assert_eq!(sounds.len(), 1);
+        assert_eq!(sounds[0].path, "sounds/check.wav");
+    }
+
+    #[test]
+    fn taking_the_last_piece_but_the_kings_ends_the_game_that_turn() {
+        let mut app = game_app();
+        test_app::load(&mut app, "4k3/4Q3/8/8/8/8/8/7K b - - 0 1");
+        test_app::play(&mut app, &["e8e7"]);
+        let game_state = app.world.resource::<GameState>();
+        assert_eq!(app.world.resource::<MoveHistory>().moves().len(), 1);
+        assert_eq!(
+            game_state.game_over,
+            Some(GameOver::DrawInsufficientMaterial)
+        );
+        // White's never asked for a move
+        assert!(!app.world.resource::<TurnData>().is_awaiting_move());
+
+        // Anything else left on the board plays on
+        let mut taken = position("4k3/4Q3/8/8/8/8/P7/7K b - - 0 1", Variant::Standard);
+        play(&mut taken, &["e8e7"]);
+        assert_eq!(taken.forced_game_over(), None);
+        // As do bare kings in King of the Hill, where either can still win
+        let mut hill = position("4k3/4Q3/8/8/8/8/8/7K b - - 0 1", Variant::KingOfTheHill);
+        play(&mut hill, &["e8e7"]);
+        assert_eq!(hill.forced_game_over(), None);
     }
 }
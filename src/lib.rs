@@ -0,0 +1,26 @@
+pub mod ai;
+pub mod assets;
+pub mod attract;
+pub mod bitboard;
+pub mod board;
+pub mod book;
+pub mod clock;
+pub mod coach;
+pub mod confetti;
+pub mod diagnostics;
+pub mod display;
+pub mod editor;
+pub mod fen_input;
+pub mod game;
+pub mod lighting;
+pub mod notation;
+pub mod pieces;
+pub mod review;
+pub mod selection;
+pub mod settings;
+pub mod stats;
+pub mod trail;
+pub mod ui;
+
+#[cfg(test)]
+mod test_app;
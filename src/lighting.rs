@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Lighting {
+    pub position: Vec3,
+    pub intensity: f32,
+    pub shadows: bool,
+    pub fill_light: bool, // Dimmer light from the opposite side so pieces aren't half in the dark
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(2.0, 10.0, 2.0),
+            intensity: 800.0,
+            shadows: true,
+            fill_light: false,
+        }
+    }
+}
+
+impl Lighting {
+    fn key_light(&self) -> PointLight {
+        PointLight {
+            intensity: self.intensity,
+            shadows_enabled: self.shadows,
+            ..default()
+        }
+    }
+
+    fn fill_light(&self) -> PointLight {
+        PointLight {
+            intensity: self.intensity * 0.4,
+            shadows_enabled: false, // Two sets of shadows just looks messy
+            ..default()
+        }
+    }
+
+    fn fill_light_position(&self) -> Vec3 {
+        Vec3::new(-self.position.x, self.position.y, -self.position.z)
+    }
+}
+
+#[derive(Component)]
+struct KeyLight;
+
+#[derive(Component)]
+struct FillLight;
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(PointLightBundle::default())
+        .insert(KeyLight);
+    commands
+        .spawn_bundle(PointLightBundle::default())
+        .insert(FillLight);
+}
+
+fn toggle_shadows(keyboard_input: Res<Input<KeyCode>>, mut lighting: ResMut<Lighting>) {
+    if keyboard_input.just_pressed(KeyCode::L) {
+        lighting.shadows = !lighting.shadows;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_lighting(
+    lighting: Res<Lighting>,
+    mut key_query: Query<(&mut PointLight, &mut Transform), (With<KeyLight>, Without<FillLight>)>,
+    mut fill_query: Query<
+        (&mut PointLight, &mut Transform, &mut Visibility),
+        (With<FillLight>, Without<KeyLight>),
+    >,
+) {
+    if !lighting.is_changed() {
+        return;
+    }
+
+    for (mut light, mut transform) in &mut key_query {
+        *light = lighting.key_light();
+        transform.translation = lighting.position;
+    }
+    for (mut light, mut transform, mut visibility) in &mut fill_query {
+        *light = lighting.fill_light();
+        transform.translation = lighting.fill_light_position();
+        visibility.is_visible = lighting.fill_light;
+    }
+}
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup)
+            .add_system(toggle_shadows)
+            .add_system(apply_lighting)
+            .init_resource::<Lighting>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lights_follow_the_lighting_settings() {
+        let lighting = Lighting {
+            position: Vec3::new(1.0, 8.0, 3.0),
+            intensity: 500.0,
+            shadows: false,
+            fill_light: true,
+        };
+        let key = lighting.key_light();
+        assert_eq!(key.intensity, 500.0);
+        assert!(!key.shadows_enabled);
+        assert!(Lighting::default().key_light().shadows_enabled);
+
+        let fill = lighting.fill_light();
+        assert_eq!(fill.intensity, 200.0);
+        assert!(!fill.shadows_enabled);
+        assert_eq!(lighting.fill_light_position(), Vec3::new(-1.0, 8.0, -3.0));
+    }
+}
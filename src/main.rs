@@ -1,21 +1,50 @@
-mod board;
-mod game;
-mod pieces;
-mod ui;
+use std::time::Duration;
 
-use crate::{board::BoardPlugin, game::GamePlugin, pieces::PiecesPlugin, ui::UiPlugin};
-use bevy::prelude::*;
+use bevy::{prelude::*, window::WindowMode};
 use bevy_mod_picking::{InteractablePickingPlugin, PickingCameraBundle, PickingPlugin};
+use schach::{
+    ai::{AiPlugin, Difficulty},
+    assets::AssetCheckPlugin,
+    attract::{AttractMode, AttractPlugin},
+    board::{BoardPlugin, BoardStyle},
+    book::OpeningBook,
+    clock::{ChessClock, ClockPlugin},
+    coach::CoachPlugin,
+    confetti::ConfettiPlugin,
+    diagnostics::DiagnosticsOverlayPlugin,
+    display::{DisplayPlugin, DisplaySettings, PresentationSpin},
+    editor::EditorPlugin,
+    fen_input::FenInputPlugin,
+    game::{
+        AutoPromote, GamePlugin, GameRng, GameRules, ImportedGame, PlayerConfig, PlayerKind,
+        StartPosition,
+    },
+    lighting::LightingPlugin,
+    pieces::{CaptureStyle, PieceKind, PieceSet, PiecesPlugin},
+    review::ReviewPlugin,
+    selection::SelectionPlugin,
+    settings::{Settings, SettingsPlugin, SETTINGS_FILE},
+    trail::{MoveTrail, MoveTrailPlugin},
+    ui::UiPlugin,
+};
 
 fn main() {
-    App::new()
+    let settings = Settings::load(SETTINGS_FILE);
+    let args = parse_args(&settings);
+
+    let mut app = App::new();
+    settings.insert_resources(&mut app);
+    app
         //.insert_resource(Msaa { samples: 4 })
-        .insert_resource(WindowDescriptor {
-            title: "Schach!".to_string(),
-            width: 1200.0,
-            height: 800.0,
-            ..default()
-        })
+        .insert_resource(args.display.window_descriptor())
+        .insert_resource(args.display)
+        .insert_resource(args.rules)
+        .insert_resource(args.piece_set)
+        .insert_resource(args.auto_promote)
+        .insert_resource(args.capture_style)
+        .insert_resource(args.move_trail)
+        .insert_resource(args.spin)
+        .insert_resource(args.board_style)
         .add_plugins(DefaultPlugins)
         .add_plugin(PickingPlugin)
         .add_plugin(InteractablePickingPlugin)
@@ -23,23 +52,220 @@ fn main() {
         .add_plugin(PiecesPlugin)
         .add_plugin(GamePlugin)
         .add_plugin(UiPlugin)
-        .add_startup_system(setup)
-        .run();
+        .add_plugin(LightingPlugin)
+        .add_plugin(ClockPlugin)
+        .add_plugin(ReviewPlugin)
+        .add_plugin(AiPlugin)
+        .add_plugin(DisplayPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(AttractPlugin)
+        .add_plugin(CoachPlugin)
+        .add_plugin(EditorPlugin)
+        .add_plugin(DiagnosticsOverlayPlugin)
+        .add_plugin(AssetCheckPlugin)
+        .add_plugin(SelectionPlugin)
+        .add_plugin(ConfettiPlugin)
+        .add_plugin(MoveTrailPlugin)
+        .add_plugin(FenInputPlugin)
+        .add_startup_system(setup);
+    if let Some(clock) = args.clock {
+        app.insert_resource(clock);
+    }
+    if let Some(pgn) = args.pgn {
+        app.insert_resource(ImportedGame(pgn));
+    }
+    app.insert_resource(args.players);
+    if let Some(book) = args.book {
+        app.insert_resource(book);
+    }
+    app.insert_resource(args.difficulty);
+    if let Some(attract_mode) = args.attract_mode {
+        app.insert_resource(attract_mode);
+    }
+    if let Some(seed) = args.seed {
+        app.insert_resource(GameRng::new(seed));
+    }
+    app.run();
+}
+
+#[derive(Default)]
+struct Args {
+    rules: GameRules,
+    clock: Option<ChessClock>,
+    pgn: Option<String>,
+    players: PlayerConfig,
+    difficulty: Difficulty,
+    book: Option<OpeningBook>,
+    display: DisplaySettings,
+    piece_set: PieceSet,
+    attract_mode: Option<AttractMode>,
+    seed: Option<u64>,
+    auto_promote: AutoPromote,
+    capture_style: CaptureStyle,
+    move_trail: MoveTrail,
+    spin: PresentationSpin,
+    board_style: BoardStyle,
+}
+
+// Usage: schach [--variant <standard|king-of-the-hill|three-check|atomic>] [--chess960 <seed>] [--clock <minutes>] [--pgn <file>]
+//              [--no-double-move] [--no-en-passant] [--no-promotion] [--promote <queen|rook|bishop|knight|ask>]
+//              [--ai <white|black|both>] [--random <white|black|both>] [--difficulty <easy|medium|hard>] [--book <file>]
+//              [--window <windowed|fullscreen|borderless>] [--size <width>x<height>]
+//              [--pieces <file>] [--gap <fraction>] [--capture <instant|fade|slide>] [--trail <moves>]
+//              [--attract <seconds>] [--spin <degrees per second>] [--seed <seed>]
+fn parse_args(settings: &Settings) -> Args {
+    // Saved settings are the starting point, for the arguments to override
+    let mut parsed = Args::default();
+    parsed.display.mode = settings.window_mode;
+    let rules = &mut parsed.rules;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--variant" => match args.next().map(|v| v.parse()) {
+                Some(Ok(variant)) => rules.variant = variant,
+                Some(Err(e)) => eprintln!("{e}"),
+                None => eprintln!("Missing value for --variant"),
+            },
+            "--chess960" => match args.next().map(|v| v.parse()) {
+                Some(Ok(seed)) => rules.start = StartPosition::Chess960(seed),
+                Some(Err(e)) => eprintln!("Invalid seed for --chess960: {e}"),
+                None => eprintln!("Missing value for --chess960"),
+            },
+            "--no-double-move" => rules.pawns.double_move = false,
+            "--no-en-passant" => rules.pawns.en_passant = false,
+            "--no-promotion" => rules.pawns.promotion = false,
+            "--promote" => match args.next().as_deref() {
+                Some("queen") => parsed.auto_promote = AutoPromote(Some(PieceKind::Queen)),
+                Some("rook") => parsed.auto_promote = AutoPromote(Some(PieceKind::Rook)),
+                Some("bishop") => parsed.auto_promote = AutoPromote(Some(PieceKind::Bishop)),
+                Some("knight") => parsed.auto_promote = AutoPromote(Some(PieceKind::Knight)),
+                Some("ask") => parsed.auto_promote = AutoPromote(None),
+                Some(kind) => eprintln!("Unknown piece for --promote: {kind}"),
+                None => eprintln!("Missing value for --promote"),
+            },
+            "--clock" => match args.next().map(|v| v.parse::<f32>()) {
+                Some(Ok(minutes)) if minutes >= 0.0 && (minutes * 60.0).is_finite() => {
+                    let time = Duration::from_secs_f32(minutes * 60.0);
+                    parsed.clock = Some(ChessClock::new(time));
+                }
+                Some(Ok(_)) => eprintln!("Invalid minutes for --clock, out of range"),
+                Some(Err(e)) => eprintln!("Invalid minutes for --clock: {e}"),
+                None => eprintln!("Missing value for --clock"),
+            },
+            "--pgn" => match args.next().map(std::fs::read_to_string) {
+                Some(Ok(pgn)) => parsed.pgn = Some(pgn),
+                Some(Err(e)) => eprintln!("Couldn't read --pgn file: {e}"),
+                None => eprintln!("Missing value for --pgn"),
+            },
+            "--ai" => match args.next().as_deref() {
+                Some("white") => parsed.players.white = PlayerKind::Ai,
+                Some("black") => parsed.players.black = PlayerKind::Ai,
+                Some("both") => {
+                    parsed.players.white = PlayerKind::Ai;
+                    parsed.players.black = PlayerKind::Ai;
+                }
+                Some(color) => eprintln!("Unknown color for --ai: {color}"),
+                None => eprintln!("Missing value for --ai"),
+            },
+            "--random" => match args.next().as_deref() {
+                Some("white") => parsed.players.white = PlayerKind::Random,
+                Some("black") => parsed.players.black = PlayerKind::Random,
+                Some("both") => {
+                    parsed.players.white = PlayerKind::Random;
+                    parsed.players.black = PlayerKind::Random;
+                }
+                Some(color) => eprintln!("Unknown color for --random: {color}"),
+                None => eprintln!("Missing value for --random"),
+            },
+            "--difficulty" => match args.next().map(|v| v.parse()) {
+                Some(Ok(difficulty)) => parsed.difficulty = difficulty,
+                Some(Err(e)) => eprintln!("{e}"),
+                None => eprintln!("Missing value for --difficulty"),
+            },
+            "--book" => match args.next().map(std::fs::read_to_string) {
+                Some(Ok(book)) => match book.parse() {
+                    Ok(book) => parsed.book = Some(book),
+                    Err(e) => eprintln!("Invalid --book file, {e}"),
+                },
+                Some(Err(e)) => eprintln!("Couldn't read --book file: {e}"),
+                None => eprintln!("Missing value for --book"),
+            },
+            "--window" => match args.next().as_deref() {
+                Some("windowed") => parsed.display.mode = WindowMode::Windowed,
+                Some("fullscreen") => parsed.display.mode = WindowMode::Fullscreen,
+                Some("borderless") => parsed.display.mode = WindowMode::BorderlessFullscreen,
+                Some(mode) => eprintln!("Unknown mode for --window: {mode}"),
+                None => eprintln!("Missing value for --window"),
+            },
+            "--size" => match args.next().as_deref().map(parse_size) {
+                Some(Some((width, height))) => {
+                    parsed.display.width = width;
+                    parsed.display.height = height;
+                }
+                Some(None) => eprintln!("Invalid size for --size, expected e.g. 1200x800"),
+                None => eprintln!("Missing value for --size"),
+            },
+            "--pieces" => match args.next().map(std::fs::read_to_string) {
+                Some(Ok(set)) => match set.parse() {
+                    Ok(set) => parsed.piece_set = set,
+                    Err(e) => eprintln!("Invalid --pieces file, {e}"),
+                },
+                Some(Err(e)) => eprintln!("Couldn't read --pieces file: {e}"),
+                None => eprintln!("Missing value for --pieces"),
+            },
+            "--gap" => match args.next().map(|v| v.parse::<f32>()) {
+                Some(Ok(gap)) if (0.0..=0.5).contains(&gap) => parsed.board_style.gap = gap,
+                Some(Ok(_)) => eprintln!("Invalid fraction for --gap, must be from 0 to 0.5"),
+                Some(Err(e)) => eprintln!("Invalid fraction for --gap: {e}"),
+                None => eprintln!("Missing value for --gap"),
+            },
+            "--capture" => match args.next().map(|v| v.parse()) {
+                Some(Ok(style)) => parsed.capture_style = style,
+                Some(Err(e)) => eprintln!("{e}"),
+                None => eprintln!("Missing value for --capture"),
+            },
+            "--trail" => match args.next().map(|v| v.parse()) {
+                Some(Ok(length)) => {
+                    parsed.move_trail = MoveTrail { show: true, length };
+                }
+                Some(Err(e)) => eprintln!("Invalid number of moves for --trail: {e}"),
+                None => eprintln!("Missing value for --trail"),
+            },
+            "--attract" => match args.next().map(|v| v.parse::<f32>()) {
+                Some(Ok(secs)) if secs >= 0.0 => {
+                    let timeout = Duration::from_secs_f32(secs);
+                    parsed.attract_mode = Some(AttractMode::new(timeout));
+                }
+                Some(Ok(_)) => eprintln!("Invalid seconds for --attract, can't be negative"),
+                Some(Err(e)) => eprintln!("Invalid seconds for --attract: {e}"),
+                None => eprintln!("Missing value for --attract"),
+            },
+            "--spin" => match args.next().map(|v| v.parse::<f32>()) {
+                Some(Ok(degrees)) => parsed.spin = PresentationSpin::new(degrees.to_radians()),
+                Some(Err(e)) => eprintln!("Invalid degrees per second for --spin: {e}"),
+                None => eprintln!("Missing value for --spin"),
+            },
+            "--seed" => match args.next().map(|v| v.parse()) {
+                Some(Ok(seed)) => parsed.seed = Some(seed),
+                Some(Err(e)) => eprintln!("Invalid seed for --seed: {e}"),
+                None => eprintln!("Missing value for --seed"),
+            },
+            _ => eprintln!("Unknown argument: {arg}"),
+        }
+    }
+    parsed
+}
+
+// "<width>x<height>", e.g. "1200x800"
+fn parse_size(s: &str) -> Option<(f32, f32)> {
+    let (width, height) = s.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    (width > 0.0 && height > 0.0).then_some((width, height))
 }
 
 fn setup(mut commands: Commands) {
-    // Camera
+    // Camera, framed to fit the window in frame_camera()
     commands
-        .spawn_bundle(Camera3dBundle {
-            transform: Transform::from_xyz(0.0, 12.0, 8.0)
-                .looking_at(Vec3::new(0.0, 0.0, 0.0), Vec3::Y),
-            ..default()
-        })
+        .spawn_bundle(Camera3dBundle::default())
         .insert_bundle(PickingCameraBundle::default());
-
-    // Light
-    commands.spawn_bundle(PointLightBundle {
-        transform: Transform::from_translation(Vec3::new(2.0, 10.0, 2.0)),
-        ..default()
-    });
 }
@@ -0,0 +1,785 @@
+use std::fmt;
+
+use crate::{
+    board::BoardPosition,
+    game::{BoardError, CastlingRights, CastlingSide, EnPassant, GameState, Move},
+    pieces::{Piece, PieceColor, PieceKind},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SanError {
+    Malformed,
+    Illegal,   // Well-formed, but no legal move matches
+    Ambiguous, // More than one legal move matches
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed move"),
+            Self::Illegal => write!(f, "illegal move"),
+            Self::Ambiguous => write!(f, "ambiguous move"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UciError {
+    Malformed,
+    Illegal,
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed move"),
+            Self::Illegal => write!(f, "illegal move"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PgnError {
+    pub move_number: u32,
+    pub player: PieceColor,
+    pub san: String,
+    pub error: SanError,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dots = match self.player {
+            PieceColor::White => ".",
+            PieceColor::Black => "...",
+        };
+        write!(f, "{}{dots} {}: {}", self.move_number, self.san, self.error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FenError {
+    FieldCount(usize), // Neither the full six fields nor just the first four
+    Placement,         // Not eight ranks of eight squares, or a letter that isn't a piece
+    SideToMove,
+    Castling, // Includes a right that the board has no king and rook for
+    EnPassant,
+    Counter,
+    Board(BoardError), // Reads fine, but isn't a position that can be played from
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::FieldCount(count) => write!(f, "expected 6 fields, found {count}"),
+            Self::Placement => write!(f, "bad piece placement"),
+            Self::SideToMove => write!(f, "side to move isn't w or b"),
+            Self::Castling => write!(f, "bad castling rights"),
+            Self::EnPassant => write!(f, "bad en passant square"),
+            Self::Counter => write!(f, "bad move counter"),
+            Self::Board(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// The piece's FEN letter: upper case for White, lower case for Black.
+pub fn piece_char(piece: Piece) -> char {
+    let c = match piece.kind {
+        PieceKind::King => 'k',
+        PieceKind::Queen => 'q',
+        PieceKind::Rook => 'r',
+        PieceKind::Bishop => 'b',
+        PieceKind::Knight => 'n',
+        PieceKind::Pawn(_) => 'p',
+    };
+    match piece.color {
+        PieceColor::White => c.to_ascii_uppercase(),
+        PieceColor::Black => c,
+    }
+}
+
+// The piece placement field of a FEN, e.g. "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR"
+fn fen_placement(board: &[[Option<Piece>; 8]; 8]) -> String {
+    let mut fen = String::new();
+    for row in board.iter().rev() {
+        let mut empty = 0;
+        for square in row {
+            match square {
+                Some(piece) => {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push(piece_char(*piece));
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        fen.push('/');
+    }
+    fen.pop(); // No separator after the last rank
+    fen
+}
+
+/// The piece placement and side to move fields of the position's FEN, e.g.
+/// "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b".
+pub fn fen_board(game_state: &GameState) -> String {
+    let mut fen = fen_placement(&game_state.board());
+    fen.push_str(match game_state.curr_player {
+        PieceColor::White => " w",
+        PieceColor::Black => " b",
+    });
+    fen
+}
+
+/// The piece placement field of a FEN with only the pieces on the given squares, everything else left
+/// empty, e.g. "4k3/8/8/8/8/8/8/4K3" for the two kings.
+pub fn fen_fragment(board: &[[Option<Piece>; 8]; 8], squares: &[BoardPosition]) -> String {
+    let mut fragment = [[None; 8]; 8];
+    for pos in squares {
+        fragment[pos.row as usize][pos.col as usize] = board[pos.row as usize][pos.col as usize];
+    }
+    fen_placement(&fragment)
+}
+
+/// The pieces on the given squares in the order given, FEN letter then square, e.g. "Ke1, Pe2, ke8".
+/// Empty squares are left out.
+pub fn piece_list(board: &[[Option<Piece>; 8]; 8], squares: &[BoardPosition]) -> String {
+    squares
+        .iter()
+        .filter_map(|pos| {
+            let piece = board[pos.row as usize][pos.col as usize]?;
+            Some(format!("{}{}", piece_char(piece), square_name(*pos)))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The position's full FEN, e.g. "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b - e3 0 1".
+pub fn to_fen(game_state: &GameState) -> String {
+    format!(
+        "{} {} {} {} {}",
+        fen_board(game_state),
+        castling_field(game_state),
+        en_passant_field(game_state),
+        game_state.halfmove_clock,
+        game_state.fullmove_number()
+    )
+}
+
+/// Reads a position from its FEN, e.g. "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1". The
+/// move counters may be left off, and castling rights can name the rook's file instead (as `to_fen()`
+/// writes them for Chess960). The rules are left as the default for the caller to set.
+pub fn from_fen(fen: &str) -> Result<GameState, FenError> {
+    let fields: Vec<_> = fen.split_whitespace().collect();
+    if fields.len() != 6 && fields.len() != 4 {
+        return Err(FenError::FieldCount(fields.len()));
+    }
+
+    let board = parse_placement(fields[0]).ok_or(FenError::Placement)?;
+    let mut game_state = GameState::default();
+    game_state.set_board(&board);
+    game_state.curr_player = match fields[1] {
+        "w" => PieceColor::White,
+        "b" => PieceColor::Black,
+        _ => return Err(FenError::SideToMove),
+    };
+    game_state.castling = parse_castling(&board, fields[2]).ok_or(FenError::Castling)?;
+    game_state.en_passant = parse_en_passant(&game_state, fields[3]).ok_or(FenError::EnPassant)?;
+
+    if fields.len() == 6 {
+        let counter = |field: &str| field.parse::<u32>().map_err(|_| FenError::Counter);
+        game_state.halfmove_clock = counter(fields[4])?;
+        let fullmove_number = counter(fields[5])?;
+        if fullmove_number == 0 {
+            return Err(FenError::Counter);
+        }
+        game_state.plies = (fullmove_number - 1) * 2
+            + match game_state.curr_player {
+                PieceColor::White => 0,
+                PieceColor::Black => 1,
+            };
+    }
+
+    game_state.validate().map_err(FenError::Board)?;
+    Ok(game_state)
+}
+
+// The first rank written is the 8th. A pawn counts as having moved unless it's on its starting rank.
+fn parse_placement(field: &str) -> Option<[[Option<Piece>; 8]; 8]> {
+    let mut board = [[None; 8]; 8];
+    let ranks: Vec<_> = field.split('/').collect();
+    if ranks.len() != 8 {
+        return None;
+    }
+    for (rank, row) in ranks.into_iter().zip((0..8).rev()) {
+        let mut col = 0;
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                col += empty as usize;
+                continue;
+            }
+            let color = if c.is_ascii_uppercase() {
+                PieceColor::White
+            } else {
+                PieceColor::Black
+            };
+            let kind = match c.to_ascii_lowercase() {
+                'k' => PieceKind::King,
+                'q' => PieceKind::Queen,
+                'r' => PieceKind::Rook,
+                'b' => PieceKind::Bishop,
+                'n' => PieceKind::Knight,
+                'p' => PieceKind::Pawn(match color {
+                    PieceColor::White => row != 1,
+                    PieceColor::Black => row != 6,
+                }),
+                _ => return None,
+            };
+            *board[row].get_mut(col)? = Some(Piece { color, kind });
+            col += 1;
+        }
+        if col != 8 {
+            return None;
+        }
+    }
+    Some(board)
+}
+
+// Each right has to be one the board still allows, i.e. with the king and that rook on the back rank
+fn parse_castling(board: &[[Option<Piece>; 8]; 8], field: &str) -> Option<CastlingRights> {
+    let mut castling = CastlingRights::from_board(board);
+    let mut listed = Vec::new();
+    if field != "-" {
+        for c in field.chars() {
+            let color = if c.is_ascii_uppercase() {
+                PieceColor::White
+            } else {
+                PieceColor::Black
+            };
+            let side = match c.to_ascii_lowercase() {
+                'k' => CastlingSide::King,
+                'q' => CastlingSide::Queen,
+                file @ 'a'..='h' => {
+                    let col = Some(file as i8 - b'a' as i8);
+                    [CastlingSide::King, CastlingSide::Queen]
+                        .into_iter()
+                        .find(|side| castling.rook_col(color, *side) == col)?
+                }
+                _ => return None,
+            };
+            castling.rook_col(color, side)?;
+            listed.push((color, side));
+        }
+    }
+    castling.retain(|color, side| listed.contains(&(color, side)));
+    Some(castling)
+}
+
+// Only a square the player to move could capture onto, right behind a pawn that could have just got there
+fn parse_en_passant(game_state: &GameState, field: &str) -> Option<Option<EnPassant>> {
+    if field == "-" {
+        return Some(None);
+    }
+    let capture_pos = BoardPosition::from_algebraic(field)?;
+    let (row, forward) = match game_state.curr_player {
+        PieceColor::White => (5, -1),
+        PieceColor::Black => (2, 1),
+    };
+    // Checked before looking for the pawn, which would be off the board behind a square on the edge
+    if capture_pos.row != row {
+        return None;
+    }
+    let piece_pos = capture_pos + (forward, 0);
+    let pawn = game_state.get(piece_pos)?;
+    if pawn.color == game_state.curr_player || !matches!(pawn.kind, PieceKind::Pawn(_)) {
+        return None;
+    }
+    Some(Some(EnPassant {
+        capture_pos,
+        piece_pos,
+    }))
+}
+
+/// The rule state that isn't visible on the board, one field per line, written the way the FEN has it,
+/// plus the position's hash. After 1. e4 that's
+/// "Castling: KQkq\nEn passant: e3\nHalfmove clock: 0\nHash: 823625f92fdcfa9d".
+pub fn rule_state(game_state: &GameState) -> String {
+    format!(
+        "Castling: {}\nEn passant: {}\nHalfmove clock: {}\nHash: {:016x}",
+        castling_field(game_state),
+        en_passant_field(game_state),
+        game_state.halfmove_clock,
+        game_state.zobrist_hash()
+    )
+}
+
+fn en_passant_field(game_state: &GameState) -> String {
+    match game_state.en_passant {
+        Some(ep) => square_name(ep.capture_pos),
+        None => String::from("-"),
+    }
+}
+
+// "KQkq" style, except that a Chess960 rook that doesn't start in the corner is named by its file instead
+// (as in Shredder-FEN), e.g. "Gb"
+fn castling_field(game_state: &GameState) -> String {
+    let mut field = String::new();
+    for color in [PieceColor::White, PieceColor::Black] {
+        for (side, corner, letter) in [(CastlingSide::King, 7, 'K'), (CastlingSide::Queen, 0, 'Q')]
+        {
+            if let Some(col) = game_state.castling.rook_col(color, side) {
+                let c = if col == corner {
+                    letter
+                } else {
+                    (b'A' + col as u8) as char
+                };
+                field.push(match color {
+                    PieceColor::White => c,
+                    PieceColor::Black => c.to_ascii_lowercase(),
+                });
+            }
+        }
+    }
+    if field.is_empty() {
+        field.push('-');
+    }
+    field
+}
+
+fn kind_letter(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::King => "K",
+        PieceKind::Queen => "Q",
+        PieceKind::Rook => "R",
+        PieceKind::Bishop => "B",
+        PieceKind::Knight => "N",
+        PieceKind::Pawn(_) => "",
+    }
+}
+
+fn square_name(pos: BoardPosition) -> String {
+    pos.to_algebraic().expect("Naming a square off the board")
+}
+
+/// Writes a legal move in Standard Algebraic Notation, e.g. "Nbd2", "exd5" or "e8=Q+".
+pub fn to_san(game_state: &GameState, mv: Move) -> String {
+    let piece = game_state
+        .get(mv.from)
+        .expect("Writing a move for a non-existent piece");
+    if let Some(castling) = game_state.castling(mv.from, mv.to) {
+        let san = match castling.side {
+            CastlingSide::King => String::from("O-O"),
+            CastlingSide::Queen => String::from("O-O-O"),
+        };
+        return san + check_suffix(game_state, mv);
+    }
+
+    let is_pawn = matches!(piece.kind, PieceKind::Pawn(_));
+    let is_capture = game_state.get(mv.to).is_some() || (is_pawn && mv.from.col != mv.to.col);
+
+    let mut san = String::from(kind_letter(piece.kind));
+    if is_pawn {
+        if is_capture {
+            san.push(square_name(mv.from).remove(0));
+        }
+    } else {
+        // Only say where the piece came from if another one of the same kind could also get there
+        let others: Vec<_> = game_state
+            .legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && game_state.get(other.from).map(|p| p.kind) == Some(piece.kind)
+            })
+            .collect();
+        let from = square_name(mv.from);
+        if others.is_empty() {
+            // Unambiguous
+        } else if others.iter().all(|other| other.from.col != mv.from.col) {
+            san.push_str(&from[..1]); // The file is enough
+        } else if others.iter().all(|other| other.from.row != mv.from.row) {
+            san.push_str(&from[1..]); // The rank is enough
+        } else {
+            san.push_str(&from);
+        }
+    }
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&square_name(mv.to));
+    if let Some(kind) = mv.promotion {
+        san.push('=');
+        san.push_str(kind_letter(kind));
+    }
+
+    san + check_suffix(game_state, mv)
+}
+
+fn check_suffix(game_state: &GameState, mv: Move) -> &'static str {
+    let mut next = game_state.clone();
+    next.make_move(mv);
+    if !next.is_check() {
+        ""
+    } else if next.legal_moves().is_empty() {
+        "#"
+    } else {
+        "+"
+    }
+}
+
+fn parse_kind(c: char) -> Option<PieceKind> {
+    match c {
+        'K' => Some(PieceKind::King),
+        'Q' => Some(PieceKind::Queen),
+        'R' => Some(PieceKind::Rook),
+        'B' => Some(PieceKind::Bishop),
+        'N' => Some(PieceKind::Knight),
+        _ => None,
+    }
+}
+
+fn parse_file(c: char) -> Option<i8> {
+    ('a'..='h').contains(&c).then(|| c as i8 - b'a' as i8)
+}
+
+fn parse_rank(c: char) -> Option<i8> {
+    ('1'..='8').contains(&c).then(|| c as i8 - b'1' as i8)
+}
+
+/// Finds the legal move for the current player that a move in Standard Algebraic Notation
+/// (e.g. "Nf3", "exd5", "R1e2", "e8=Q+") refers to.
+pub fn parse_san(game_state: &GameState, san: &str) -> Result<Move, SanError> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal = game_state.legal_moves();
+    let kind_at = |pos| game_state.get(pos).map(|piece: Piece| piece.kind);
+
+    let candidates: Vec<_> = match san {
+        "O-O" | "0-0" | "O-O-O" | "0-0-0" => {
+            let side = if san.len() == 3 {
+                CastlingSide::King
+            } else {
+                CastlingSide::Queen
+            };
+            legal
+                .into_iter()
+                .filter(|mv| {
+                    game_state
+                        .castling(mv.from, mv.to)
+                        .is_some_and(|castling| castling.side == side)
+                })
+                .collect()
+        }
+        _ => {
+            let mut rest = san;
+
+            // Piece letter, or nothing for a pawn
+            let kind = rest.chars().next().and_then(parse_kind);
+            if kind.is_some() {
+                rest = &rest[1..];
+            }
+
+            // Promotion, written as either "e8=Q" or "e8Q"
+            let mut promotion = None;
+            if let Some(c) = rest.chars().last().filter(|c| c.is_ascii_uppercase()) {
+                promotion = Some(parse_kind(c).ok_or(SanError::Malformed)?);
+                rest = rest[..rest.len() - 1].trim_end_matches('=');
+            }
+            if promotion.is_some() && kind.is_some() {
+                return Err(SanError::Malformed);
+            }
+
+            // The destination is always the last two characters, anything before that narrows down the source
+            if rest.len() < 2 || !rest.is_char_boundary(rest.len() - 2) {
+                return Err(SanError::Malformed);
+            }
+            let (prefix, dest) = rest.split_at(rest.len() - 2);
+            let to = BoardPosition::from_algebraic(dest).ok_or(SanError::Malformed)?;
+            let mut prefix = prefix
+                .strip_suffix('x')
+                .unwrap_or(prefix)
+                .chars()
+                .peekable();
+            let from_col = prefix
+                .next_if(|c| parse_file(*c).is_some())
+                .and_then(parse_file);
+            let from_row = prefix
+                .next_if(|c| parse_rank(*c).is_some())
+                .and_then(parse_rank);
+            if prefix.next().is_some() {
+                return Err(SanError::Malformed);
+            }
+
+            legal
+                .into_iter()
+                .filter(|mv| {
+                    let kind_matches = match (kind, kind_at(mv.from)) {
+                        (None, Some(PieceKind::Pawn(_))) => true,
+                        (Some(kind), Some(actual)) => kind == actual,
+                        _ => false,
+                    };
+                    kind_matches
+                        && mv.to == to
+                        && mv.promotion == promotion
+                        && game_state.castling(mv.from, mv.to).is_none() // Only ever written as O-O
+                        && from_col.map_or(true, |col| mv.from.col == col)
+                        && from_row.map_or(true, |row| mv.from.row == row)
+                })
+                .collect()
+        }
+    };
+
+    match candidates[..] {
+        [mv] => Ok(mv),
+        [] => Err(SanError::Illegal),
+        _ => Err(SanError::Ambiguous),
+    }
+}
+
+/// Writes a move in the long algebraic notation UCI uses, e.g. "e2e4" or "e7e8q". Castling is written
+/// the way Move encodes it, which in standard chess is the king moving two squares.
+pub fn to_uci(mv: Move) -> String {
+    let mut uci = square_name(mv.from) + &square_name(mv.to);
+    if let Some(kind) = mv.promotion {
+        uci += &kind_letter(kind).to_ascii_lowercase();
+    }
+    uci
+}
+
+/// Reads a move in UCI's long algebraic notation. Only its form is checked, not whether it's legal.
+pub fn parse_uci(uci: &str) -> Option<Move> {
+    let from = BoardPosition::from_algebraic(uci.get(0..2)?)?;
+    let to = BoardPosition::from_algebraic(uci.get(2..4)?)?;
+    let mut rest = uci[4..].chars();
+    let promotion = match rest.next() {
+        None => None,
+        Some(c) => {
+            Some(parse_kind(c.to_ascii_uppercase()).filter(|kind| *kind != PieceKind::King)?)
+        }
+    };
+    if rest.next().is_some() {
+        return None;
+    }
+    Some(Move {
+        from,
+        to,
+        promotion,
+    })
+}
+
+// Splits PGN movetext into SAN tokens, skipping tag pairs, comments, variations, annotations,
+// move numbers and the result.
+fn movetext_tokens(pgn: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut skip_until = None; // Closing character of a tag pair, comment or variation
+    let mut variation_depth = 0;
+
+    for (i, c) in pgn.char_indices() {
+        if let Some(end) = skip_until {
+            match c {
+                '(' if end == ')' => variation_depth += 1,
+                ')' if end == ')' && variation_depth > 0 => variation_depth -= 1,
+                _ if c == end => skip_until = None,
+                _ => (),
+            }
+            continue;
+        }
+
+        let ends_token = c.is_whitespace() || matches!(c, '[' | '{' | '(' | ';');
+        if ends_token {
+            if let Some(s) = start.take() {
+                tokens.push(&pgn[s..i]);
+            }
+            skip_until = match c {
+                '[' => Some(']'),
+                '{' => Some('}'),
+                '(' => Some(')'),
+                ';' => Some('\n'),
+                _ => None,
+            };
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&pgn[s..]);
+    }
+
+    tokens
+        .into_iter()
+        .filter(|token| {
+            !token.starts_with('$') && !matches!(*token, "1-0" | "0-1" | "1/2-1/2" | "*")
+        })
+        .map(strip_move_number)
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+// "12." and "12..." may be written right up against the move that follows
+fn strip_move_number(token: &str) -> &str {
+    let rest = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.starts_with('.') {
+        rest.trim_start_matches('.')
+    } else {
+        token // Not a move number, e.g. "0-0"
+    }
+}
+
+/// Plays every move of some PGN movetext from the given position. On error the game state is left
+/// at the position before the offending move. Returns the moves played.
+pub fn play_pgn(game_state: &mut GameState, pgn: &str) -> Result<Vec<Move>, PgnError> {
+    let mut move_number = 1;
+    let mut moves = Vec::new();
+    for san in movetext_tokens(pgn) {
+        let player = game_state.curr_player;
+        let mv = parse_san(game_state, san).map_err(|error| PgnError {
+            move_number,
+            player,
+            san: san.to_string(),
+            error,
+        })?;
+        game_state.make_move(mv);
+        moves.push(mv);
+        if player == PieceColor::Black {
+            move_number += 1;
+        }
+    }
+    Ok(moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn start() -> GameState {
+        from_fen(START_FEN).unwrap()
+    }
+
+    #[test]
+    fn plays_scholars_mate() {
+        let mut game_state = start();
+        let moves = play_pgn(
+            &mut game_state,
+            "[Event \"Casual\"]\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6?? {Oops} 4. Qxf7# 1-0",
+        )
+        .unwrap();
+        assert_eq!(moves.len(), 7);
+        assert_eq!(
+            to_fen(&game_state),
+            "r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4"
+        );
+    }
+
+    #[test]
+    fn pgn_errors_give_the_move_number() {
+        let error = play_pgn(&mut start(), "1. e4 e5 2. Nf3 Ke7?? 3. Bb5 Qe9").unwrap_err();
+        assert_eq!(
+            error,
+            PgnError {
+                move_number: 3,
+                player: PieceColor::Black,
+                san: "Qe9".to_string(),
+                error: SanError::Malformed,
+            }
+        );
+
+        let error = play_pgn(&mut start(), "1. e4 e5 2. Ke3").unwrap_err();
+        assert_eq!((error.move_number, error.error), (2, SanError::Illegal));
+        assert_eq!(error.to_string(), "2. Ke3: illegal move");
+    }
+
+    #[test]
+    fn start_position_has_twenty_uci_moves() {
+        let mut game_state = start();
+        let mut moves = game_state.legal_moves_uci();
+        moves.sort();
+        assert_eq!(moves.len(), 20);
+        assert!(moves.iter().any(|mv| mv == "e2e4"));
+        assert!(moves.iter().any(|mv| mv == "g1f3"));
+        moves.dedup();
+        assert_eq!(moves.len(), 20);
+
+        assert_eq!(game_state.make_move_uci("e2e5"), Err(UciError::Illegal));
+        assert_eq!(game_state.make_move_uci("e2"), Err(UciError::Malformed));
+        assert_eq!(game_state.make_move_uci("e2e4"), Ok(()));
+        assert_eq!(
+            to_fen(&game_state),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+    }
+
+    #[test]
+    fn uci_moves_round_trip() {
+        for uci in ["e2e4", "e7e8q", "a2a1n", "e1g1"] {
+            assert_eq!(parse_uci(uci).map(to_uci).as_deref(), Some(uci));
+        }
+        assert_eq!(parse_uci("E7E8Q").map(to_uci).as_deref(), Some("e7e8q"));
+        for uci in ["", "e2", "e2e", "e2e9", "e7e8k", "e7e8x", "e7e8qq"] {
+            assert_eq!(parse_uci(uci), None, "{uci:?}");
+        }
+    }
+
+    #[test]
+    fn rule_state_shows_the_fen_fields() {
+        let game_state = from_fen("r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6 3 10").unwrap();
+        let hash = format!("{:016x}", game_state.zobrist_hash());
+        assert_eq!(
+            rule_state(&game_state),
+            format!("Castling: Kq\nEn passant: d6\nHalfmove clock: 3\nHash: {hash}")
+        );
+
+        // Without the en passant square it's a different position, so the hash changes too
+        let game_state = from_fen("r3k3/8/8/3pP3/8/8/8/4K2R w - - 0 10").unwrap();
+        let state = rule_state(&game_state);
+        assert!(state.starts_with("Castling: -\nEn passant: -\nHalfmove clock: 0\nHash: "));
+        assert!(!state.ends_with(&hash));
+    }
+
+    #[test]
+    fn fen_errors_are_reported_without_panicking() {
+        let error = |fen| from_fen(fen).unwrap_err();
+        assert_eq!(error(""), FenError::FieldCount(0));
+        assert_eq!(error("8/8/8/8/8/8/8/8 w - - 0"), FenError::FieldCount(5));
+        assert_eq!(error("8/8/8/8/8/8/8 w - - 0 1"), FenError::Placement);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K4 w - - 0 1"), FenError::Placement);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4X3 w - - 0 1"), FenError::Placement);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 x - - 0 1"), FenError::SideToMove);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w K - 0 1"), FenError::Castling);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - x 1"), FenError::Counter);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - 0 0"), FenError::Counter);
+        assert_eq!(
+            error("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::Board(BoardError::KingCount(PieceColor::Black, 0))
+        );
+
+        // En passant squares on the edge of the board have nothing behind them to look at
+        for (side, square) in [
+            ("w", "e1"),
+            ("w", "e8"),
+            ("b", "e1"),
+            ("b", "e8"),
+            ("w", "a1"),
+        ] {
+            let fen = format!("4k3/8/8/3pP3/8/8/8/4K3 {side} - {square} 0 1");
+            assert_eq!(from_fen(&fen).unwrap_err(), FenError::EnPassant, "{fen}");
+        }
+        // Or on the wrong rank for the side to move, without a pawn to take, or not a square at all
+        for (side, square) in [
+            ("w", "e3"),
+            ("b", "d6"),
+            ("w", "c6"),
+            ("w", "i6"),
+            ("w", "d"),
+        ] {
+            let fen = format!("4k3/8/8/3pP3/8/8/8/4K3 {side} - {square} 0 1");
+            assert_eq!(from_fen(&fen).unwrap_err(), FenError::EnPassant, "{fen}");
+        }
+        assert!(from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").is_ok());
+    }
+}
@@ -1,48 +1,195 @@
-use std::fmt;
+use std::{f32::consts::PI, fmt, str::FromStr};
 
-use bevy::prelude::*;
+use bevy::{asset::LoadState, ecs::schedule::ShouldRun, prelude::*};
 
-use crate::board::BoardPosition;
+use crate::{
+    board::{BoardPosition, BoardScale, HoveredSquare, Square},
+    game::{Captured, GameState, TurnData, TurnPhase},
+};
 
-#[rustfmt::skip]
-const PIECE_TRANSFORMS: [Transform; 6] = [
-    Transform { translation: Vec3::new(-0.2, 0.0, -1.9),  rotation: Quat::IDENTITY, scale: Vec3::new(0.2, 0.2, 0.2) }, // King
-    Transform { translation: Vec3::new(-0.2, 0.0, -0.95), rotation: Quat::IDENTITY, scale: Vec3::new(0.2, 0.2, 0.2) }, // Queen
-    Transform { translation: Vec3::new(-0.1, 0.0, 1.8),   rotation: Quat::IDENTITY, scale: Vec3::new(0.2, 0.2, 0.2) }, // Rook
-    Transform { translation: Vec3::new(-0.1, 0.0, 0.0),   rotation: Quat::IDENTITY, scale: Vec3::new(0.2, 0.2, 0.2) }, // Bishop
-    Transform { translation: Vec3::new(-0.2, 0.0, 0.9),   rotation: Quat::IDENTITY, scale: Vec3::new(0.2, 0.2, 0.2) }, // Knight
-    Transform { translation: Vec3::new(-0.05, 0.0, 2.6),  rotation: Quat::IDENTITY, scale: Vec3::new(0.2, 0.2, 0.2) }, // Pawn
+/// 3D models for every kind of piece, all loaded from one asset file.
+///
+/// Sets can be read from text with a `path <asset file>` line and then one line per kind of piece:
+/// `<king|queen|rook|bishop|knight|pawn> <x> <y> <z> <scale> <mesh label>...`. The translation and scale
+/// place the meshes on their square, and the labels name meshes within the file, e.g. `Mesh0/Primitive0`.
+/// Blank lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug)]
+pub struct PieceSet {
+    pub path: String,
+    pub models: [PieceModel; 6], // King, queen, rook, bishop, knight, pawn
+}
+
+#[derive(Clone, Debug)]
+pub struct PieceModel {
+    pub meshes: Vec<String>, // Labels of the meshes within the set's file
+    pub transform: Transform,
+}
+
+const PIECE_SET_KINDS: [PieceKind; 6] = [
+    PieceKind::King,
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+    PieceKind::Pawn(false),
 ];
 
+fn model_index(kind: PieceKind) -> usize {
+    match kind {
+        PieceKind::King => 0,
+        PieceKind::Queen => 1,
+        PieceKind::Rook => 2,
+        PieceKind::Bishop => 3,
+        PieceKind::Knight => 4,
+        PieceKind::Pawn(_) => 5,
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        let model = |meshes: &[&str], x, z| PieceModel {
+            meshes: meshes.iter().map(|label| label.to_string()).collect(),
+            transform: Transform::from_xyz(x, 0.0, z).with_scale(Vec3::splat(0.2)),
+        };
+        Self {
+            path: "models/pieces.glb".to_string(),
+            models: [
+                model(&["Mesh0/Primitive0", "Mesh1/Primitive0"], -0.2, -1.9),
+                model(&["Mesh7/Primitive0"], -0.2, -0.95),
+                model(&["Mesh5/Primitive0"], -0.1, 1.8),
+                model(&["Mesh6/Primitive0"], -0.1, 0.0),
+                model(&["Mesh3/Primitive0", "Mesh4/Primitive0"], -0.2, 0.9),
+                model(&["Mesh2/Primitive0"], -0.05, 2.6),
+            ],
+        }
+    }
+}
+
+impl PieceSet {
+    pub fn model(&self, kind: PieceKind) -> &PieceModel {
+        &self.models[model_index(kind)]
+    }
+
+    // Asset paths of the meshes for a kind of piece, e.g. "models/pieces.glb#Mesh0/Primitive0"
+    fn mesh_paths(&self, kind: PieceKind) -> impl Iterator<Item = String> + '_ {
+        self.model(kind)
+            .meshes
+            .iter()
+            .map(|label| format!("{}#{}", self.path, label))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PieceSetError {
+    Line { line: usize, reason: &'static str },
+    MissingPath,
+    MissingModel(PieceKind),
+}
+
+impl fmt::Display for PieceSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Line { line, reason } => write!(f, "line {line}: {reason}"),
+            Self::MissingPath => write!(f, "no asset path given"),
+            Self::MissingModel(kind) => write!(f, "no model for the {kind:?}"),
+        }
+    }
+}
+
+impl FromStr for PieceSet {
+    type Err = PieceSetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut path = None;
+        let mut models: [Option<PieceModel>; 6] = Default::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |reason| PieceSetError::Line {
+                line: i + 1,
+                reason,
+            };
+
+            let mut fields = line.split_whitespace();
+            let kind = match fields.next() {
+                Some("path") => {
+                    let value = fields.next().ok_or_else(|| err("missing asset path"))?;
+                    path = Some(value.to_string());
+                    continue;
+                }
+                Some("king") => PieceKind::King,
+                Some("queen") => PieceKind::Queen,
+                Some("rook") => PieceKind::Rook,
+                Some("bishop") => PieceKind::Bishop,
+                Some("knight") => PieceKind::Knight,
+                Some("pawn") => PieceKind::Pawn(false),
+                _ => return Err(err("expected a path or a kind of piece")),
+            };
+
+            let mut numbers = [0.0; 4];
+            for number in &mut numbers {
+                *number = fields
+                    .next()
+                    .and_then(|field| field.parse().ok())
+                    .ok_or_else(|| err("expected x, y, z and scale"))?;
+            }
+            let [x, y, z, scale] = numbers;
+            let meshes: Vec<_> = fields.map(|label| label.to_string()).collect();
+            if meshes.is_empty() {
+                return Err(err("no meshes for piece"));
+            }
+
+            let model = &mut models[model_index(kind)];
+            if model.is_some() {
+                return Err(err("piece already has a model"));
+            }
+            *model = Some(PieceModel {
+                meshes,
+                transform: Transform::from_xyz(x, y, z).with_scale(Vec3::splat(scale)),
+            });
+        }
+
+        let path = path.ok_or(PieceSetError::MissingPath)?;
+        for (model, kind) in models.iter().zip(PIECE_SET_KINDS) {
+            if model.is_none() {
+                return Err(PieceSetError::MissingModel(kind));
+            }
+        }
+        Ok(Self {
+            path,
+            models: models.map(Option::unwrap),
+        })
+    }
+}
+
 struct PiecePbr {
     meshes: Vec<Handle<Mesh>>,
     transform: Transform,
 }
 
 struct PiecesRenderData {
-    king: PiecePbr,
-    queen: PiecePbr,
-    rook: PiecePbr,
-    bishop: PiecePbr,
-    knight: PiecePbr,
-    pawn: PiecePbr,
+    pieces: [PiecePbr; 6], // In PIECE_SET_KINDS order
     white_mat: Handle<StandardMaterial>,
     black_mat: Handle<StandardMaterial>,
+    ring_mesh: Handle<Mesh>,
+    white_ring_mat: Handle<StandardMaterial>,
+    black_ring_mat: Handle<StandardMaterial>,
 }
 
 impl FromWorld for PiecesRenderData {
     fn from_world(world: &mut World) -> Self {
         // Load all the meshes
-        // TODO: make the mesh path part of the const data table?
+        let piece_set = world.get_resource::<PieceSet>().unwrap();
         let asset_server = world.get_resource::<AssetServer>().unwrap();
-        let king: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh0/Primitive0");
-        let king_cross: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh1/Primitive0");
-        let pawn: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh2/Primitive0");
-        let knight_1: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh3/Primitive0");
-        let knight_2: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh4/Primitive0");
-        let rook: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh5/Primitive0");
-        let bishop: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh6/Primitive0");
-        let queen: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh7/Primitive0");
+        let pieces = PIECE_SET_KINDS.map(|kind| PiecePbr {
+            meshes: piece_set
+                .mesh_paths(kind)
+                .map(|path| asset_server.load(&path))
+                .collect(),
+            transform: piece_set.model(kind).transform,
+        });
 
         // Create materials
         let mut materials = world
@@ -50,36 +197,56 @@ impl FromWorld for PiecesRenderData {
             .unwrap();
         let white_mat = materials.add(Color::rgb(1., 0.8, 0.8).into());
         let black_mat = materials.add(Color::rgb(0., 0.2, 0.2).into());
+        let white_ring_mat = materials.add(Color::rgb(1.0, 0.9, 0.2).into());
+        let black_ring_mat = materials.add(Color::rgb(0.2, 0.1, 0.5).into());
+
+        // Ring around the base of each piece, used to tell the colors apart
+        let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+        let ring_mesh = meshes.add(Mesh::from(shape::Torus {
+            radius: 0.35,
+            ring_radius: 0.04,
+            ..default()
+        }));
 
         Self {
-            king: PiecePbr {
-                meshes: vec![king, king_cross],
-                transform: PIECE_TRANSFORMS[0],
-            },
-            queen: PiecePbr {
-                meshes: vec![queen],
-                transform: PIECE_TRANSFORMS[1],
-            },
-            rook: PiecePbr {
-                meshes: vec![rook],
-                transform: PIECE_TRANSFORMS[2],
-            },
-            bishop: PiecePbr {
-                meshes: vec![bishop],
-                transform: PIECE_TRANSFORMS[3],
-            },
-            knight: PiecePbr {
-                meshes: vec![knight_1, knight_2],
-                transform: PIECE_TRANSFORMS[4],
-            },
-            pawn: PiecePbr {
-                meshes: vec![pawn],
-                transform: PIECE_TRANSFORMS[5],
-            },
+            pieces,
             white_mat,
             black_mat,
+            ring_mesh,
+            white_ring_mat,
+            black_ring_mat,
+        }
+    }
+}
+
+// A mesh label that isn't in the set's file just never loads, which would leave the piece invisible.
+// Check them all once the file has loaded so a broken set is reported instead.
+fn check_piece_meshes(
+    mut checked: Local<bool>,
+    piece_set: Res<PieceSet>,
+    render_data: Res<PiecesRenderData>,
+    asset_server: Res<AssetServer>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    if *checked {
+        return;
+    }
+
+    let first = &render_data.pieces[0].meshes[0];
+    match asset_server.get_load_state(first) {
+        LoadState::Loaded => {
+            for (pbr, kind) in render_data.pieces.iter().zip(PIECE_SET_KINDS) {
+                for (mesh, path) in pbr.meshes.iter().zip(piece_set.mesh_paths(kind)) {
+                    if meshes.get(mesh).is_none() {
+                        error!("Piece set has no mesh {path} for the {kind:?}");
+                    }
+                }
+            }
         }
+        LoadState::Failed => {} // Shown on screen by the asset check
+        _ => return,            // Still loading
     }
+    *checked = true;
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
@@ -121,61 +288,78 @@ pub enum PieceKind {
     Pawn(bool),
 }
 
+impl fmt::Display for PieceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::King => "King",
+                Self::Queen => "Queen",
+                Self::Rook => "Rook",
+                Self::Bishop => "Bishop",
+                Self::Knight => "Knight",
+                Self::Pawn(_) => "Pawn",
+            }
+        )
+    }
+}
+
 #[derive(Clone, Component, Copy, Debug, Eq, PartialEq)]
 pub struct Piece {
     pub color: PieceColor,
     pub kind: PieceKind,
 }
 
-struct PieceConstData {
-    piece: Piece,
-    pos: BoardPosition,
-}
-
-#[rustfmt::skip]
-const STARTING_BOARD: [PieceConstData; 32] = [
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Rook,        }, pos: BoardPosition { row: 0, col: 0 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Knight,      }, pos: BoardPosition { row: 0, col: 1 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Bishop,      }, pos: BoardPosition { row: 0, col: 2 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Queen,       }, pos: BoardPosition { row: 0, col: 3 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::King,        }, pos: BoardPosition { row: 0, col: 4 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Bishop,      }, pos: BoardPosition { row: 0, col: 5 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Knight,      }, pos: BoardPosition { row: 0, col: 6 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Rook,        }, pos: BoardPosition { row: 0, col: 7 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 0 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 1 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 2 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 3 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 4 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 5 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 6 } },
-    PieceConstData { piece: Piece { color: PieceColor::White, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 1, col: 7 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 0 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 1 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 2 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 3 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 4 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 5 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 6 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Pawn(false), }, pos: BoardPosition { row: 6, col: 7 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Rook,        }, pos: BoardPosition { row: 7, col: 0 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Knight,      }, pos: BoardPosition { row: 7, col: 1 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Bishop,      }, pos: BoardPosition { row: 7, col: 2 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Queen,       }, pos: BoardPosition { row: 7, col: 3 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::King,        }, pos: BoardPosition { row: 7, col: 4 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Bishop,      }, pos: BoardPosition { row: 7, col: 5 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Knight,      }, pos: BoardPosition { row: 7, col: 6 } },
-    PieceConstData { piece: Piece { color: PieceColor::Black, kind: PieceKind::Rook,        }, pos: BoardPosition { row: 7, col: 7 } },
-];
+fn create_pieces(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    piece_render_data: Res<PiecesRenderData>,
+    board_scale: Res<BoardScale>,
+) {
+    spawn_board_pieces(
+        &mut commands,
+        &game_state.board(),
+        &piece_render_data,
+        &board_scale,
+    );
+}
 
-fn create_pieces(mut commands: Commands, piece_render_data: Res<PiecesRenderData>) {
-    for piece_data in STARTING_BOARD {
-        spawn_piece(
-            &mut commands,
-            piece_data.piece,
-            piece_data.pos,
-            &piece_render_data,
-        );
+fn spawn_board_pieces(
+    commands: &mut Commands,
+    board: &[[Option<Piece>; 8]; 8],
+    render_data: &Res<PiecesRenderData>,
+    board_scale: &BoardScale,
+) {
+    for (row, pieces) in board.iter().enumerate() {
+        for (col, piece) in pieces.iter().enumerate() {
+            if let Some(piece) = piece {
+                let pos = BoardPosition {
+                    row: row as i8,
+                    col: col as i8,
+                };
+                spawn_piece(commands, *piece, pos, render_data, board_scale);
+            }
+        }
+    }
+}
+
+// Replaces every piece on the board, e.g. to show a different position than the one being played
+pub struct RebuildPiecesEvent(pub [[Option<Piece>; 8]; 8]);
+
+fn rebuild_pieces(
+    mut commands: Commands,
+    mut events: EventReader<RebuildPiecesEvent>,
+    piece_query: Query<Entity, With<Piece>>,
+    render_data: Res<PiecesRenderData>,
+    board_scale: Res<BoardScale>,
+) {
+    // Only the latest board matters
+    if let Some(event) = events.iter().last() {
+        for entity in &piece_query {
+            commands.entity(entity).despawn_recursive();
+        }
+        spawn_board_pieces(&mut commands, &event.0, &render_data, &board_scale);
     }
 }
 
@@ -184,43 +368,327 @@ fn spawn_piece(
     piece: Piece,
     board_pos: BoardPosition,
     render_data: &Res<PiecesRenderData>,
+    board_scale: &BoardScale,
 ) {
-    let pbr = match piece.kind {
-        PieceKind::King => &render_data.king,
-        PieceKind::Queen => &render_data.queen,
-        PieceKind::Rook => &render_data.rook,
-        PieceKind::Bishop => &render_data.bishop,
-        PieceKind::Knight => &render_data.knight,
-        PieceKind::Pawn(_) => &render_data.pawn,
-    };
-    let mat = match piece.color {
-        PieceColor::White => &render_data.white_mat,
-        PieceColor::Black => &render_data.black_mat,
+    let ring_mat = match piece.color {
+        PieceColor::White => &render_data.white_ring_mat,
+        PieceColor::Black => &render_data.black_ring_mat,
     };
 
     commands
-        .spawn_bundle(PbrBundle::default())
+        .spawn_bundle(PbrBundle {
+            transform: Transform::from_scale(Vec3::splat(board_scale.0)),
+            ..default()
+        })
         .insert(piece)
         .insert(board_pos)
+        .insert(Lift::default())
         .with_children(|parent| {
-            for mesh in &pbr.meshes {
-                parent.spawn_bundle(PbrBundle {
-                    mesh: mesh.clone(),
-                    material: mat.clone(),
-                    transform: pbr.transform,
+            spawn_piece_meshes(parent, piece, render_data);
+
+            // Hidden until show_color_rings() decides otherwise
+            parent
+                .spawn_bundle(PbrBundle {
+                    mesh: render_data.ring_mesh.clone(),
+                    material: ring_mat.clone(),
+                    visibility: Visibility { is_visible: false },
                     ..default()
-                });
+                })
+                .insert(ColorRing);
+        });
+}
+
+fn spawn_piece_meshes(parent: &mut ChildBuilder, piece: Piece, render_data: &PiecesRenderData) {
+    let pbr = &render_data.pieces[model_index(piece.kind)];
+    let mat = match piece.color {
+        PieceColor::White => &render_data.white_mat,
+        PieceColor::Black => &render_data.black_mat,
+    };
+
+    let transform = mesh_transform(pbr.transform, piece.color);
+    for mesh in &pbr.meshes {
+        parent
+            .spawn_bundle(PbrBundle {
+                mesh: mesh.clone(),
+                material: mat.clone(),
+                transform,
+                ..default()
+            })
+            .insert(PieceMesh);
+    }
+}
+
+// Black's meshes are turned around to face White, so knights look across the board at each other. The
+// turn is about the centre of the square, so it carries on working whichever way the board itself faces.
+fn mesh_transform(set_transform: Transform, color: PieceColor) -> Transform {
+    match color {
+        PieceColor::White => set_transform,
+        PieceColor::Black => Transform::from_rotation(Quat::from_rotation_y(PI)) * set_transform,
+    }
+}
+
+// A piece that changes after being spawned has been promoted, so swap its meshes for the new kind
+#[allow(clippy::type_complexity)]
+fn rebuild_changed_pieces(
+    mut commands: Commands,
+    render_data: Res<PiecesRenderData>,
+    piece_query: Query<
+        (
+            Entity,
+            &Piece,
+            &Children,
+            ChangeTrackers<Piece>,
+            Option<&PieceTint>,
+        ),
+        Changed<Piece>,
+    >,
+    mesh_query: Query<(), With<PieceMesh>>,
+) {
+    for (entity, piece, children, tracker, tint) in &piece_query {
+        if tracker.is_added() {
+            continue;
+        }
+
+        for child in children {
+            if mesh_query.contains(*child) {
+                commands.entity(*child).despawn_recursive();
             }
+        }
+        commands.entity(entity).with_children(|parent| {
+            spawn_piece_meshes(parent, *piece, &render_data);
         });
+
+        // Re-inserting the tint gets apply_piece_tints() to cover the new meshes too
+        if let Some(tint) = tint {
+            commands.entity(entity).insert(*tint);
+        }
+    }
 }
 
+// One of the (possibly several) meshes that make up a piece
+#[derive(Component)]
+struct PieceMesh;
+
+#[derive(Clone, Component, Copy, Debug)]
+pub struct PieceTint(pub Color);
+
+// Tints every mesh of a piece, or restores its normal material when the tint is None
+pub fn set_piece_tint(commands: &mut Commands, entity: Entity, tint: Option<Color>) {
+    match tint {
+        Some(color) => {
+            commands.entity(entity).insert(PieceTint(color));
+        }
+        None => {
+            commands.entity(entity).remove::<PieceTint>();
+        }
+    }
+}
+
+// Each tint color gets one material, made the first time it's used and shared by every piece tinted it
+fn apply_piece_tints(
+    mut tint_mats: Local<Vec<(Color, Handle<StandardMaterial>)>>,
+    render_data: Res<PiecesRenderData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    removed_tints: RemovedComponents<PieceTint>,
+    tinted_query: Query<(&PieceTint, &Children), Changed<PieceTint>>,
+    piece_query: Query<(&Piece, &Children)>,
+    mut mesh_query: Query<&mut Handle<StandardMaterial>, With<PieceMesh>>,
+) {
+    // Handle removals first so a tint that was removed and re-added this frame wins
+    for entity in removed_tints.iter() {
+        if let Ok((piece, children)) = piece_query.get(entity) {
+            let mat = match piece.color {
+                PieceColor::White => &render_data.white_mat,
+                PieceColor::Black => &render_data.black_mat,
+            };
+            for child in children {
+                if let Ok(mut material) = mesh_query.get_mut(*child) {
+                    *material = mat.clone();
+                }
+            }
+        }
+    }
+
+    for (tint, children) in &tinted_query {
+        let mat = match tint_mats.iter().find(|(color, _)| *color == tint.0) {
+            Some((_, mat)) => mat.clone(),
+            None => {
+                let mat = materials.add(tint.0.into());
+                tint_mats.push((tint.0, mat.clone()));
+                mat
+            }
+        };
+        for child in children {
+            if let Ok(mut material) = mesh_query.get_mut(*child) {
+                *material = mat.clone();
+            }
+        }
+    }
+}
+
+fn tint_selected_piece(
+    mut commands: Commands,
+    turn_data: Res<TurnData>,
+    tinted_query: Query<Entity, With<PieceTint>>,
+    piece_query: Query<(), With<Piece>>,
+) {
+    if !turn_data.is_changed() {
+        return;
+    }
+
+    for entity in &tinted_query {
+        if Some(entity) != turn_data.move_piece {
+            set_piece_tint(&mut commands, entity, None);
+        }
+    }
+    if let Some(entity) = turn_data.move_piece {
+        // The piece may have been despawned since it was picked, before the turn manager noticed
+        if piece_query.contains(entity) && !tinted_query.contains(entity) {
+            set_piece_tint(&mut commands, entity, Some(Color::rgb(1.0, 0.6, 0.0)));
+        }
+    }
+}
+
+// Marks each piece's color with a ring around its base
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColorblindMode(pub bool);
+
+#[derive(Component)]
+struct ColorRing;
+
+fn toggle_colorblind_mode(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut colorblind_mode: ResMut<ColorblindMode>,
+) {
+    if keyboard_input.just_pressed(KeyCode::C) {
+        colorblind_mode.0 = !colorblind_mode.0;
+    }
+}
+
+fn show_color_rings(
+    colorblind_mode: Res<ColorblindMode>,
+    mut query: Query<(&mut Visibility, ChangeTrackers<ColorRing>)>,
+) {
+    for (mut visibility, tracker) in &mut query {
+        if colorblind_mode.is_changed() || tracker.is_added() {
+            visibility.is_visible = colorblind_mode.0;
+        }
+    }
+}
+
+/// Whether a piece the player could move rises a little while the cursor's over it, toggled with U.
+#[derive(Clone, Copy, Debug)]
+pub struct HoverLift(pub bool);
+
+impl Default for HoverLift {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+const LIFT_HEIGHT: f32 = 0.2;
+const LIFT_SPEED: f32 = 1.5; // Per second
+
+/// How high a piece is lifted `dt` seconds on from `current`: rising towards its full height while
+/// it's lifted, and sinking back to the board otherwise.
+pub fn lift_offset(current: f32, lifted: bool, dt: f32) -> f32 {
+    let target = if lifted { LIFT_HEIGHT } else { 0.0 };
+    let step = LIFT_SPEED * dt;
+    if (target - current).abs() <= step {
+        target
+    } else {
+        current + step.copysign(target - current)
+    }
+}
+
+// How high a piece's meshes are raised off its square. Only the meshes move, so the piece itself is
+// left for animate_pieces() to slide around.
+#[derive(Component, Default)]
+struct Lift(f32);
+
+fn toggle_hover_lift(keyboard_input: Res<Input<KeyCode>>, mut hover_lift: ResMut<HoverLift>) {
+    if keyboard_input.just_pressed(KeyCode::U) {
+        hover_lift.0 = !hover_lift.0;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn lift_hovered_piece(
+    time: Res<Time>,
+    hover_lift: Res<HoverLift>,
+    hovered_square: Res<HoveredSquare>,
+    game_state: Res<GameState>,
+    turn_data: Res<TurnData>,
+    piece_set: Res<PieceSet>,
+    mut lifted_pos: Local<Option<BoardPosition>>,
+    square_query: Query<&BoardPosition, With<Square>>,
+    mut piece_query: Query<(&Piece, &BoardPosition, &Children, &mut Lift)>,
+    mut mesh_query: Query<&mut Transform, With<PieceMesh>>,
+) {
+    // Which piece should be up only changes with the hover or the game, so the moves aren't worked out
+    // every frame
+    if hovered_square.is_changed()
+        || game_state.is_changed()
+        || turn_data.is_changed()
+        || hover_lift.is_changed()
+    {
+        let hovered_pos = hovered_square
+            .entity
+            .and_then(|entity| square_query.get(entity).ok().copied());
+        let can_lift =
+            hover_lift.0 && game_state.game_over.is_none() && turn_data.move_target.is_none();
+        *lifted_pos = hovered_pos
+            .filter(|pos| can_lift && game_state.legal_moves().iter().any(|mv| mv.from == *pos));
+    }
+
+    for (piece, pos, children, mut lift) in &mut piece_query {
+        let offset = lift_offset(lift.0, *lifted_pos == Some(*pos), time.delta_seconds());
+        if offset == lift.0 {
+            continue;
+        }
+        lift.0 = offset;
+
+        // Set from the set's own placement rather than nudged, so meshes swapped in by a promotion
+        // end up at the same height as the rest
+        let y = piece_set.model(piece.kind).transform.translation.y + offset;
+        for child in children {
+            if let Ok(mut transform) = mesh_query.get_mut(*child) {
+                transform.translation.y = y;
+            }
+        }
+    }
+}
+
+/// Whether pieces slide to their new squares by themselves. Turn it off to animate them some other way,
+/// see PieceMoveEvent.
+#[derive(Clone, Copy, Debug)]
+pub struct BuiltinAnimation(pub bool);
+
+impl Default for BuiltinAnimation {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+fn builtin_animation_enabled(builtin_animation: Res<BuiltinAnimation>) -> ShouldRun {
+    if builtin_animation.0 {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+// A captured piece is left alone once it's on its way out, but not before: in Atomic the moving piece is
+// captured too, and still has to get to its square for the turn to go on
+#[allow(clippy::type_complexity)]
 fn animate_pieces(
     time: Res<Time>,
-    mut query: Query<(Entity, &mut Transform, &BoardPosition), With<Piece>>,
+    board_scale: Res<BoardScale>,
+    mut query: Query<(Entity, &mut Transform, &BoardPosition), (With<Piece>, Without<CaptureAnim>)>,
     mut anim_complete_events: EventWriter<PieceAnimCompleteEvent>,
 ) {
     for (entity, mut transform, board_pos) in &mut query {
-        let direction = board_pos.to_translation() - transform.translation;
+        let direction = board_pos.to_scaled_translation(&board_scale) - transform.translation;
         if direction.length() != 0.0 {
             let speed = 5.0;
             let step = direction.normalize() * time.delta_seconds() * speed;
@@ -236,11 +704,182 @@ fn animate_pieces(
     }
 }
 
+/// How a captured piece leaves the board. The turn doesn't end until it's gone.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CaptureStyle {
+    #[default]
+    Instant, // Removed as soon as the capturing piece lands
+    Fade,
+    SlideToTray, // Slides off to the side of the board nearest the capturing player
+}
+
+impl CaptureStyle {
+    /// How long the piece takes to go once the capturing piece has landed, in seconds.
+    pub fn duration(self) -> f32 {
+        match self {
+            Self::Instant => 0.0,
+            Self::Fade => 0.4,
+            Self::SlideToTray => 0.6,
+        }
+    }
+
+    /// How far through going the piece is after `elapsed` seconds, from 0 to 1 when it's gone.
+    pub fn progress(self, elapsed: f32) -> f32 {
+        match self.duration() {
+            d if d > 0.0 => (elapsed / d).min(1.0),
+            _ => 1.0,
+        }
+    }
+}
+
+impl FromStr for CaptureStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "instant" => Ok(Self::Instant),
+            "fade" => Ok(Self::Fade),
+            "slide" => Ok(Self::SlideToTray),
+            _ => Err(format!("Unknown capture style: {s}")),
+        }
+    }
+}
+
+// Where a captured piece slides to, beside the board on the capturing player's side
+fn tray_translation(captured: PieceColor, board_scale: &BoardScale) -> Vec3 {
+    let row = match captured {
+        PieceColor::White => 7,
+        PieceColor::Black => 0,
+    };
+    BoardPosition { row, col: 9 }.to_scaled_translation(board_scale)
+}
+
+// A captured piece on its way out, started once the capturing piece has landed
+#[derive(Component)]
+struct CaptureAnim {
+    elapsed: f32,
+    start: Vec3,
+    fade_mat: Option<Handle<StandardMaterial>>,
+}
+
+// Takes captured pieces off the board in the chosen style, then despawns them, which is what lets the
+// turn manager end the turn. Instant captures are despawned by the turn manager itself.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn animate_captures(
+    mut commands: Commands,
+    time: Res<Time>,
+    capture_style: Res<CaptureStyle>,
+    turn_data: Res<TurnData>,
+    board_scale: Res<BoardScale>,
+    render_data: Res<PiecesRenderData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<
+        (
+            Entity,
+            &Piece,
+            &mut Transform,
+            &Children,
+            Option<&mut CaptureAnim>,
+        ),
+        With<Captured>,
+    >,
+    mut mesh_query: Query<&mut Handle<StandardMaterial>, With<PieceMesh>>,
+    mut ring_query: Query<&mut Visibility, With<ColorRing>>,
+    mut anim_complete_events: EventWriter<PieceAnimCompleteEvent>,
+) {
+    if *capture_style == CaptureStyle::Instant || turn_data.phase() != TurnPhase::CheckCapture {
+        return;
+    }
+
+    for (entity, piece, mut transform, children, anim) in &mut query {
+        let mut anim = match anim {
+            Some(anim) => anim,
+            None => {
+                // The fade needs a see-through material of its own for each piece
+                let fade_mat = (*capture_style == CaptureStyle::Fade).then(|| {
+                    let base_color = match piece.color {
+                        PieceColor::White => materials.get(&render_data.white_mat),
+                        PieceColor::Black => materials.get(&render_data.black_mat),
+                    }
+                    .map_or(Color::WHITE, |mat| mat.base_color);
+                    let fade_mat = materials.add(StandardMaterial {
+                        base_color,
+                        alpha_mode: AlphaMode::Blend,
+                        ..default()
+                    });
+                    for child in children {
+                        if let Ok(mut material) = mesh_query.get_mut(*child) {
+                            *material = fade_mat.clone();
+                        }
+                        if let Ok(mut visibility) = ring_query.get_mut(*child) {
+                            visibility.is_visible = false;
+                        }
+                    }
+                    fade_mat
+                });
+                commands.entity(entity).insert(CaptureAnim {
+                    elapsed: 0.0,
+                    start: transform.translation,
+                    fade_mat,
+                });
+                continue;
+            }
+        };
+
+        anim.elapsed += time.delta_seconds();
+        let progress = capture_style.progress(anim.elapsed);
+        match *capture_style {
+            CaptureStyle::Instant => (),
+            CaptureStyle::Fade => {
+                let fade_mat = anim
+                    .fade_mat
+                    .as_ref()
+                    .and_then(|mat| materials.get_mut(mat));
+                if let Some(fade_mat) = fade_mat {
+                    fade_mat.base_color.set_a(1.0 - progress);
+                }
+            }
+            CaptureStyle::SlideToTray => {
+                let tray = tray_translation(piece.color, &board_scale);
+                transform.translation = anim.start.lerp(tray, progress);
+            }
+        }
+
+        if progress >= 1.0 {
+            commands.entity(entity).despawn_recursive();
+            anim_complete_events.send(PieceAnimCompleteEvent { entity });
+        }
+    }
+}
+
+// Without the builtin animation, new pieces would be left where they're spawned in the middle of the
+// board rather than sliding out to their squares, so they're put straight there instead
+fn place_new_pieces(
+    board_scale: Res<BoardScale>,
+    builtin_animation: Res<BuiltinAnimation>,
+    mut query: Query<(&mut Transform, &BoardPosition), Added<Piece>>,
+) {
+    if builtin_animation.0 {
+        return;
+    }
+    for (mut transform, board_pos) in &mut query {
+        transform.translation = board_pos.to_scaled_translation(&board_scale);
+    }
+}
+
+/// Sent when a piece has finished moving to its square. The turn doesn't go on until this has been sent
+/// for the piece that was moved.
 #[derive(Debug)]
 pub struct PieceAnimCompleteEvent {
     pub entity: Entity,
 }
 
+/// Sent when a piece moves. The pieces plugin sets the entity's BoardPosition to `target` but leaves its
+/// Transform where it was, for the animation to move (new pieces are put on their squares to begin with).
+/// That's the builtin one unless BuiltinAnimation is off, in which case a system of your own should read
+/// these, move the Transform to `target` however it likes and then send a PieceAnimCompleteEvent for the
+/// entity. When castling the rook's move is sent first, but only the king's completion holds up the turn.
+/// Captured pieces are dealt with separately, going by the CaptureStyle.
 #[derive(Debug)]
 pub struct PieceMoveEvent {
     pub entity: Entity,
@@ -276,10 +915,374 @@ pub struct PiecesPlugin;
 impl Plugin for PiecesPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(create_pieces)
+            .init_resource::<PieceSet>()
             .init_resource::<PiecesRenderData>()
-            .add_system(animate_pieces)
+            .add_system(check_piece_meshes)
+            .add_system(animate_pieces.with_run_criteria(builtin_animation_enabled))
+            .add_system(place_new_pieces)
+            .add_system(animate_captures.before(rebuild_pieces)) // So a rebuild's despawns come last
             .add_system(move_pieces)
+            .add_system(rebuild_changed_pieces)
+            .add_system(rebuild_pieces)
+            .add_system_to_stage(CoreStage::PostUpdate, apply_piece_tints) // Sees tint removals from this frame
+            .add_system(tint_selected_piece)
+            .add_system(toggle_colorblind_mode)
+            .add_system(show_color_rings)
+            .add_system(toggle_hover_lift)
+            .add_system(lift_hovered_piece.after(toggle_hover_lift))
+            .init_resource::<HoverLift>()
+            .init_resource::<CaptureStyle>()
+            .init_resource::<BuiltinAnimation>()
+            .init_resource::<ColorblindMode>()
             .add_event::<PieceMoveEvent>()
+            .add_event::<RebuildPiecesEvent>()
             .add_event::<PieceAnimCompleteEvent>();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::HandleId;
+
+    use crate::{
+        game::{ScriptedMoves, Variant},
+        notation,
+        test_app::{self, game_app, square},
+    };
+
+    #[test]
+    fn colorblind_mode_shows_one_ring_per_piece() {
+        let mut app = game_app();
+        app.insert_resource(ColorblindMode(true));
+        test_app::update(&mut app);
+
+        let render_data = app.world.resource::<PiecesRenderData>();
+        let (white_ring_mat, black_ring_mat) = (
+            render_data.white_ring_mat.clone(),
+            render_data.black_ring_mat.clone(),
+        );
+        let mut piece_query = app.world.query::<(&Piece, &Children)>();
+        let mut ring_query = app
+            .world
+            .query_filtered::<(&Visibility, &Handle<StandardMaterial>), With<ColorRing>>();
+        let pieces: Vec<_> = piece_query.iter(&app.world).collect();
+        assert_eq!(pieces.len(), 32);
+        for (piece, children) in pieces {
+            let rings: Vec<_> = children
+                .iter()
+                .filter_map(|child| ring_query.get(&app.world, *child).ok())
+                .collect();
+            assert_eq!(rings.len(), 1);
+            let (visibility, material) = rings[0];
+            assert!(visibility.is_visible);
+            let expected = match piece.color {
+                PieceColor::White => &white_ring_mat,
+                PieceColor::Black => &black_ring_mat,
+            };
+            assert_eq!(material, expected);
+        }
+    }
+
+    const TEST_SET: &str = "\
+path models/staunton.glb
+# Two meshes for the king, one each for the rest
+king 0.0 0.0 0.0 0.5 King/Base King/Cross
+queen 0.0 0.0 0.0 0.5 Queen
+rook 0.0 0.0 0.0 0.5 Rook
+bishop 0.0 0.0 0.0 0.5 Bishop
+knight 0.1 0.0 -0.1 0.5 Knight
+pawn 0.0 0.0 0.0 0.4 Pawn
+";
+
+    #[test]
+    fn piece_set_with_every_kind_builds_render_data() {
+        let mut app = game_app();
+        app.insert_resource(TEST_SET.parse::<PieceSet>().unwrap());
+        let render_data = PiecesRenderData::from_world(&mut app.world);
+
+        let expected = [
+            ["King/Base", "King/Cross"].as_slice(),
+            &["Queen"],
+            &["Rook"],
+            &["Bishop"],
+            &["Knight"],
+            &["Pawn"],
+        ];
+        for (pbr, labels) in render_data.pieces.iter().zip(expected) {
+            let ids: Vec<_> = pbr.meshes.iter().map(|mesh| mesh.id).collect();
+            let expected_ids: Vec<_> = labels
+                .iter()
+                .map(|label| HandleId::from(format!("models/staunton.glb#{label}").as_str()))
+                .collect();
+            assert_eq!(ids, expected_ids);
+        }
+        let knight = &render_data.pieces[model_index(PieceKind::Knight)];
+        assert_eq!(knight.transform.translation, Vec3::new(0.1, 0.0, -0.1));
+        assert_eq!(knight.transform.scale, Vec3::splat(0.5));
+    }
+
+    #[test]
+    fn piece_set_errors() {
+        let without_pawn = TEST_SET.replace("pawn 0.0 0.0 0.0 0.4 Pawn\n", "");
+        assert_eq!(
+            without_pawn.parse::<PieceSet>().unwrap_err(),
+            PieceSetError::MissingModel(PieceKind::Pawn(false))
+        );
+        let without_path = TEST_SET.replace("path models/staunton.glb\n", "");
+        assert_eq!(
+            without_path.parse::<PieceSet>().unwrap_err(),
+            PieceSetError::MissingPath
+        );
+        assert_eq!(
+            "path a.glb\nrook 0 0 0 1".parse::<PieceSet>().unwrap_err(),
+            PieceSetError::Line {
+                line: 2,
+                reason: "no meshes for piece"
+            }
+        );
+        assert_eq!(
+            "path a.glb\nrook 0 0 1 Rook"
+                .parse::<PieceSet>()
+                .unwrap_err(),
+            PieceSetError::Line {
+                line: 2,
+                reason: "expected x, y, z and scale"
+            }
+        );
+    }
+
+    fn mesh_rotations(app: &mut App, name: &str) -> Vec<Quat> {
+        let square = square(name);
+        let children = app
+            .world
+            .query::<(&BoardPosition, &Children)>()
+            .iter(&app.world)
+            .find(|(pos, _)| **pos == square)
+            .map(|(_, children)| children.to_vec())
+            .unwrap();
+        let mut mesh_query = app.world.query_filtered::<&Transform, With<PieceMesh>>();
+        children
+            .into_iter()
+            .filter_map(|child| mesh_query.get(&app.world, child).ok())
+            .map(|transform| transform.rotation)
+            .collect()
+    }
+
+    #[test]
+    fn black_knights_are_turned_half_way_round() {
+        let mut app = game_app();
+        let white = mesh_rotations(&mut app, "b1");
+        let black = mesh_rotations(&mut app, "b8");
+        assert_eq!(white.len(), 2);
+        assert_eq!(black.len(), 2);
+        for (white, black) in white.into_iter().zip(black) {
+            let turn = black * white.inverse();
+            let (axis, angle) = turn.to_axis_angle();
+            assert!((angle - PI).abs() < 1e-4);
+            assert!(axis.abs().abs_diff_eq(Vec3::Y, 1e-4));
+        }
+    }
+
+    fn knight_materials(app: &mut App, knight: Entity) -> Vec<Handle<StandardMaterial>> {
+        let children = app.world.get::<Children>(knight).unwrap().to_vec();
+        let mut mesh_query = app
+            .world
+            .query_filtered::<&Handle<StandardMaterial>, With<PieceMesh>>();
+        children
+            .into_iter()
+            .filter_map(|child| mesh_query.get(&app.world, child).ok().cloned())
+            .collect()
+    }
+
+    #[test]
+    fn tinting_a_knight_tints_all_its_meshes_until_cleared() {
+        let mut app = game_app();
+        let knight = app
+            .world
+            .query::<(Entity, &BoardPosition)>()
+            .iter(&app.world)
+            .find(|(_, pos)| **pos == BoardPosition { row: 0, col: 1 })
+            .map(|(entity, _)| entity)
+            .unwrap();
+        let white_mat = app.world.resource::<PiecesRenderData>().white_mat.clone();
+        assert_eq!(
+            knight_materials(&mut app, knight),
+            [white_mat.clone(), white_mat.clone()]
+        );
+
+        // Selecting the knight tints it
+        test_app::click(&mut app, MouseButton::Left, "b1");
+        test_app::update(&mut app);
+        let tinted = knight_materials(&mut app, knight);
+        assert_eq!(tinted.len(), 2);
+        assert_eq!(tinted[0], tinted[1]);
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        assert_eq!(
+            materials.get(&tinted[0]).unwrap().base_color,
+            Color::rgb(1.0, 0.6, 0.0)
+        );
+
+        // Cancelling the selection restores it
+        test_app::click(&mut app, MouseButton::Right, "b1");
+        test_app::update(&mut app);
+        assert_eq!(
+            knight_materials(&mut app, knight),
+            [white_mat.clone(), white_mat]
+        );
+
+        // Tinting it again reuses the material made the first time
+        let material_count = app.world.resource::<Assets<StandardMaterial>>().len();
+        test_app::click(&mut app, MouseButton::Left, "b1");
+        test_app::update(&mut app);
+        assert_eq!(knight_materials(&mut app, knight), tinted);
+        assert_eq!(
+            app.world.resource::<Assets<StandardMaterial>>().len(),
+            material_count
+        );
+    }
+
+    #[test]
+    fn lift_rises_while_hovered_and_sinks_after() {
+        // Up at LIFT_SPEED, stopping at LIFT_HEIGHT
+        let mut height = 0.0;
+        for expected in [0.15, 0.2, 0.2] {
+            height = lift_offset(height, true, 0.1);
+            assert!((height - expected).abs() < 1e-6, "{height}");
+        }
+        // And back down to exactly the board once the hover goes
+        for expected in [0.05, 0.0, 0.0] {
+            height = lift_offset(height, false, 0.1);
+            assert!((height - expected).abs() < 1e-6, "{height}");
+        }
+        assert_eq!(height, 0.0);
+
+        // A long frame goes straight to the target rather than past it
+        assert_eq!(lift_offset(0.0, true, 1.0), LIFT_HEIGHT);
+        assert_eq!(lift_offset(LIFT_HEIGHT, false, 1.0), 0.0);
+        assert_eq!(lift_offset(0.1, true, 0.0), 0.1);
+    }
+
+    #[test]
+    fn capture_progress_runs_over_the_style_duration() {
+        assert_eq!(CaptureStyle::Instant.progress(0.0), 1.0);
+        for style in [CaptureStyle::Fade, CaptureStyle::SlideToTray] {
+            let duration = style.duration();
+            assert_eq!(style.progress(0.0), 0.0);
+            assert!((style.progress(duration / 2.0) - 0.5).abs() < 1e-6);
+            assert_eq!(style.progress(duration), 1.0);
+            assert_eq!(style.progress(duration * 2.0), 1.0);
+        }
+        assert!(CaptureStyle::Fade.duration() < CaptureStyle::SlideToTray.duration());
+    }
+
+    const FRAME: f32 = 0.05;
+
+    // A game with the builtin animation on, so pieces take as long to move as they would on screen
+    fn timed_app(style: CaptureStyle, variant: Variant, fen: &str) -> (App, test_app::Clock) {
+        let mut app = game_app();
+        app.insert_resource(style);
+        app.world.resource_mut::<GameState>().rules.variant = variant;
+        test_app::load(&mut app, fen);
+        app.insert_resource(BuiltinAnimation(true));
+        let mut clock = test_app::Clock::new(&mut app);
+        for _ in 0..40 {
+            clock.update(&mut app, FRAME); // Time for the pieces to reach their squares
+        }
+        (app, clock)
+    }
+
+    // Plays the move and returns how long the turn spent on the capture, panicking if it never gets to
+    // the next move
+    fn capture_time(app: &mut App, clock: &mut test_app::Clock, uci: &str) -> f32 {
+        let mv = notation::parse_uci(uci).unwrap();
+        app.insert_resource(ScriptedMoves::new([mv]));
+        let mut capturing = 0.0;
+        for _ in 0..100 {
+            clock.update(app, FRAME);
+            let turn_data = app.world.resource::<TurnData>();
+            if turn_data.phase() == TurnPhase::CheckCapture {
+                capturing += FRAME;
+            }
+            if app.world.resource::<GameState>().plies == 1 && turn_data.is_awaiting_move() {
+                return capturing;
+            }
+        }
+        panic!("{uci} never finished");
+    }
+
+    #[test]
+    fn capture_style_sets_how_long_the_turn_waits() {
+        for style in [
+            CaptureStyle::Instant,
+            CaptureStyle::Fade,
+            CaptureStyle::SlideToTray,
+        ] {
+            let fen = "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1";
+            let (mut app, mut clock) = timed_app(style, Variant::Standard, fen);
+            let waited = capture_time(&mut app, &mut clock, "e4d5");
+            // A frame to start the animation and maybe another to round up the last step
+            let expected = style.duration() + FRAME;
+            assert!(
+                waited >= expected - 1e-3 && waited <= expected + FRAME + 1e-3,
+                "{style:?} waited {waited}"
+            );
+            assert_eq!(app.world.query::<&Piece>().iter(&app.world).count(), 3);
+        }
+    }
+
+    #[test]
+    fn atomic_capture_ends_the_turn_in_every_style() {
+        for style in [
+            CaptureStyle::Instant,
+            CaptureStyle::Fade,
+            CaptureStyle::SlideToTray,
+        ] {
+            // The rook takes d5 and is blown up along with the knight, leaving the pawn
+            let fen = "4k3/8/2n1p3/3p4/8/8/8/3RK3 w - - 0 1";
+            let (mut app, mut clock) = timed_app(style, Variant::Atomic, fen);
+            capture_time(&mut app, &mut clock, "d1d5");
+
+            let game_state = app.world.resource::<GameState>();
+            assert_eq!(game_state.curr_player, PieceColor::Black);
+            assert_eq!(game_state.pieces().count(), 3);
+            assert_eq!(
+                test_app::entity_board(&mut app),
+                app.world.resource::<GameState>().board()
+            );
+        }
+    }
+
+    #[test]
+    fn without_builtin_animation_the_turn_waits_for_the_completion_event() {
+        let mut app = game_app();
+        app.insert_resource(ScriptedMoves::new(notation::parse_uci("e2e4")));
+        let mut moved = None;
+        let mut reader = app.world.resource::<Events<PieceMoveEvent>>().get_reader();
+        for _ in 0..10 {
+            app.update(); // Nothing finishes the animation
+            let events = app.world.resource::<Events<PieceMoveEvent>>();
+            moved = moved.or_else(|| reader.iter(events).next().map(|ev| ev.entity));
+        }
+        let entity = moved.unwrap();
+        assert_eq!(
+            app.world.resource::<TurnData>().phase(),
+            TurnPhase::AnimateMove
+        );
+        // Left on e2 for the custom animation to move
+        let e2 = square("e2");
+        let scale = *app.world.resource::<BoardScale>();
+        let translation = app.world.get::<Transform>(entity).unwrap().translation;
+        assert_eq!(translation, e2.to_scaled_translation(&scale));
+
+        app.world.send_event(PieceAnimCompleteEvent { entity });
+        for _ in 0..5 {
+            app.update();
+        }
+        assert!(app.world.resource::<TurnData>().is_awaiting_move());
+        assert_eq!(
+            app.world.resource::<GameState>().curr_player,
+            PieceColor::Black
+        );
+    }
+}
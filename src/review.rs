@@ -0,0 +1,250 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::ShadowSquares,
+    game::{GameState, MoveHistory, TakeBackToEvent, TurnData, ValidMove},
+    pieces::RebuildPiecesEvent,
+};
+
+// Stepping back through the moves made so far. The live game carries on untouched underneath.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReviewState {
+    pub ply: Option<usize>, // Number of half-moves into the game being shown, or None for the live position
+    pub confirming_take_back: bool, // Asked to carry on the game from the position being shown
+}
+
+impl ReviewState {
+    // Where to go when stepping to `ply`, where reaching the end of the game means going back to the live position
+    fn step_to(ply: usize, history: &MoveHistory) -> Self {
+        Self {
+            ply: (ply < history.moves().len()).then_some(ply),
+            confirming_take_back: false,
+        }
+    }
+}
+
+fn review_input(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut review_state: ResMut<ReviewState>,
+    move_history: Res<MoveHistory>,
+    mut turn_data: ResMut<TurnData>,
+    valid_moves_query: Query<Entity, With<ValidMove>>,
+    mut take_back_events: EventWriter<TakeBackToEvent>,
+) {
+    if turn_data.move_target.is_some() {
+        return; // Let the move in progress finish first
+    }
+
+    // Carrying on from the position being shown drops every move after it, so it's asked about first
+    if let Some(ply) = review_state.ply {
+        if review_state.confirming_take_back {
+            if keyboard_input.just_pressed(KeyCode::Y) {
+                take_back_events.send(TakeBackToEvent(ply));
+                review_state.confirming_take_back = false;
+            } else if keyboard_input.any_just_pressed([KeyCode::N, KeyCode::Escape]) {
+                review_state.confirming_take_back = false;
+            }
+        } else if keyboard_input.just_pressed(KeyCode::Return) {
+            review_state.confirming_take_back = true;
+        }
+    }
+
+    let last = move_history.moves().len();
+    let curr = review_state.ply.unwrap_or(last);
+    let next = if keyboard_input.just_pressed(KeyCode::Left) {
+        curr.saturating_sub(1)
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        curr + 1
+    } else if keyboard_input.just_pressed(KeyCode::Home) {
+        0
+    } else if keyboard_input.just_pressed(KeyCode::End) {
+        last
+    } else {
+        return;
+    };
+
+    let next = ReviewState::step_to(next, &move_history);
+    if next.ply == review_state.ply {
+        return;
+    }
+
+    if review_state.ply.is_none() {
+        // Entering review, so drop any half-made move
+        turn_data.reset();
+        for entity in &valid_moves_query {
+            commands.entity(entity).remove::<ValidMove>();
+        }
+    }
+    *review_state = next;
+}
+
+fn show_review_position(
+    review_state: Res<ReviewState>,
+    game_state: Res<GameState>,
+    move_history: Res<MoveHistory>,
+    mut shadow_squares: ResMut<ShadowSquares>,
+    mut rebuild_events: EventWriter<RebuildPiecesEvent>,
+) {
+    if !review_state.is_changed() || review_state.is_added() {
+        return;
+    }
+
+    let (board, last_move) = match review_state.ply {
+        Some(ply) => (
+            move_history.position_at(ply).board(),
+            ply.checked_sub(1).map(|i| move_history.moves()[i]),
+        ),
+        None => (game_state.board(), move_history.moves().last().copied()),
+    };
+    rebuild_events.send(RebuildPiecesEvent(board));
+    shadow_squares.source = last_move.map(|mv| mv.from);
+    shadow_squares.target = last_move.map(|mv| mv.to);
+}
+
+pub struct ReviewPlugin;
+
+impl Plugin for ReviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReviewState>()
+            .add_system(review_input)
+            .add_system(show_review_position.after(review_input));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{RequestMoveEvent, ResignEvent, TurnPhase},
+        notation,
+        pieces::{Piece, PieceColor},
+        test_app::{self, game_app},
+    };
+
+    #[test]
+    fn stepping_to_the_start_and_end_of_the_game() {
+        let mut app = game_app();
+        app.add_plugin(ReviewPlugin);
+        let start = app.world.resource::<GameState>().board();
+        test_app::play(&mut app, &["e2e4", "e7e5", "g1f3"]);
+        let end = app.world.resource::<GameState>().board();
+
+        test_app::press(&mut app, KeyCode::Home);
+        assert_eq!(app.world.resource::<ReviewState>().ply, Some(0));
+        assert_eq!(
+            app.world.resource::<MoveHistory>().position_at(0).board(),
+            start
+        );
+        assert_eq!(test_app::entity_board(&mut app), start);
+
+        // The live game can't be moved in while reviewing
+        test_app::click(&mut app, MouseButton::Left, "b8");
+        test_app::update(&mut app);
+        assert_eq!(app.world.resource::<TurnData>().selected_piece(), None);
+
+        test_app::press(&mut app, KeyCode::End);
+        assert_eq!(app.world.resource::<ReviewState>().ply, None);
+        assert_eq!(test_app::entity_board(&mut app), end);
+        assert_eq!(app.world.resource::<GameState>().board(), end);
+    }
+
+    #[test]
+    fn carrying_on_from_move_two_drops_the_rest() {
+        let mut app = game_app();
+        app.add_plugin(ReviewPlugin);
+        test_app::play(&mut app, &["e2e4", "e7e5"]);
+        let after_two = notation::to_fen(app.world.resource::<GameState>());
+        let board = app.world.resource::<GameState>().board();
+        test_app::play(&mut app, &["g1f3", "b8c6", "f1c4"]);
+
+        test_app::press(&mut app, KeyCode::Home);
+        test_app::press(&mut app, KeyCode::Right);
+        test_app::press(&mut app, KeyCode::Right);
+        assert_eq!(app.world.resource::<ReviewState>().ply, Some(2));
+
+        // Nothing's dropped until it's confirmed
+        test_app::press(&mut app, KeyCode::Return);
+        test_app::press(&mut app, KeyCode::N);
+        assert_eq!(app.world.resource::<MoveHistory>().moves().len(), 5);
+        assert_eq!(app.world.resource::<ReviewState>().ply, Some(2));
+
+        test_app::press(&mut app, KeyCode::Return);
+        test_app::press(&mut app, KeyCode::Y);
+        test_app::update(&mut app);
+        assert_eq!(app.world.resource::<ReviewState>().ply, None);
+        assert_eq!(app.world.resource::<MoveHistory>().moves().len(), 2);
+        assert_eq!(
+            notation::to_fen(app.world.resource::<GameState>()),
+            after_two
+        );
+        assert_eq!(test_app::entity_board(&mut app), board);
+
+        // And the game carries on from there
+        test_app::play(&mut app, &["d2d4"]);
+        assert_eq!(app.world.resource::<MoveHistory>().moves().len(), 3);
+    }
+
+    #[test]
+    fn resigning_while_a_move_is_shown_still_lets_the_game_be_reviewed() {
+        let mut app = game_app();
+        app.add_plugin(ReviewPlugin);
+        test_app::play(&mut app, &["e2e4", "d7d5"]);
+        app.world
+            .send_event(RequestMoveEvent(notation::parse_uci("e4d5").unwrap()));
+        app.update(); // The capture starts, but its animation hasn't finished
+        assert!(app.world.resource::<TurnData>().move_target.is_some());
+        app.world.send_event(ResignEvent(PieceColor::Black));
+        for _ in 0..10 {
+            test_app::update(&mut app);
+        }
+
+        let turn_data = app.world.resource::<TurnData>();
+        assert_eq!(turn_data.move_target, None);
+        assert_eq!(turn_data.phase(), TurnPhase::CheckForGameOver);
+        // The taken pawn's gone, rather than left behind as it was captured
+        assert_eq!(app.world.query::<&Piece>().iter(&app.world).count(), 31);
+        let board = app.world.resource::<GameState>().board();
+
+        test_app::press(&mut app, KeyCode::Left);
+        assert_eq!(app.world.resource::<ReviewState>().ply, Some(2));
+        assert_eq!(
+            test_app::entity_board(&mut app),
+            app.world.resource::<MoveHistory>().position_at(2).board()
+        );
+        test_app::press(&mut app, KeyCode::Right);
+        assert_eq!(app.world.resource::<ReviewState>().ply, None);
+        assert_eq!(test_app::entity_board(&mut app), board);
+    }
+
+    #[test]
+    fn after_the_game_only_review_works() {
+        let mut app = game_app();
+        app.add_plugin(ReviewPlugin);
+        test_app::play(&mut app, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert!(app.world.resource::<GameState>().game_over.is_some());
+        let fen = notation::to_fen(app.world.resource::<GameState>());
+
+        // White's lost, but tries to play on anyway
+        for square in ["e2", "e4", "h2", "h4"] {
+            test_app::click(&mut app, MouseButton::Left, square);
+            test_app::update(&mut app);
+        }
+        assert_eq!(notation::to_fen(app.world.resource::<GameState>()), fen);
+        assert_eq!(app.world.resource::<TurnData>().selected_piece(), None);
+
+        test_app::press(&mut app, KeyCode::Left);
+        assert_eq!(app.world.resource::<ReviewState>().ply, Some(3));
+        assert_eq!(
+            test_app::entity_board(&mut app),
+            app.world.resource::<MoveHistory>().position_at(3).board()
+        );
+        test_app::press(&mut app, KeyCode::Right);
+        assert_eq!(app.world.resource::<ReviewState>().ply, None);
+        assert_eq!(
+            test_app::entity_board(&mut app),
+            app.world.resource::<GameState>().board()
+        );
+        assert_eq!(notation::to_fen(app.world.resource::<GameState>()), fen);
+    }
+}
@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{BoardPosition, ClickSquareEvent, InputBindings},
+    game::GameState,
+    notation::{fen_fragment, piece_list},
+};
+
+/// Squares picked out for analysis by shift-clicking them, in the order they were picked. Only used for
+/// exporting what's on them (X), so it never affects the game.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SelectionSet {
+    squares: Vec<BoardPosition>,
+}
+
+impl SelectionSet {
+    pub fn squares(&self) -> &[BoardPosition] {
+        &self.squares
+    }
+
+    pub fn contains(&self, pos: BoardPosition) -> bool {
+        self.squares.contains(&pos)
+    }
+
+    /// Adds the square if it isn't in the set yet, otherwise takes it out. Returns whether it's in now.
+    pub fn toggle(&mut self, pos: BoardPosition) -> bool {
+        match self.squares.iter().position(|square| *square == pos) {
+            Some(index) => {
+                self.squares.remove(index);
+                false
+            }
+            None => {
+                self.squares.push(pos);
+                true
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.squares.clear();
+    }
+}
+
+#[derive(Component)]
+struct SelectionText;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(130.0), // Above the move count
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(SelectionText);
+}
+
+fn select_squares(
+    bindings: Res<InputBindings>,
+    mut selection: ResMut<SelectionSet>,
+    mut click_square_events: EventReader<ClickSquareEvent>,
+) {
+    for ev in click_square_events.iter() {
+        // Whichever button selects pieces also picks squares, so long as shift is held
+        let pos = match ev.board_pos {
+            Some(pos) if ev.shift && ev.kind == bindings.select => pos,
+            _ => continue,
+        };
+        selection.toggle(pos);
+    }
+}
+
+// X writes out what's on the selected squares both ways, Delete empties the selection
+fn export_selection(
+    keyboard_input: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut selection: ResMut<SelectionSet>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        selection.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::X) && !selection.squares().is_empty() {
+        info!(
+            "Selected pieces: {}",
+            piece_list(&game_state.board(), selection.squares())
+        );
+        info!(
+            "Selected pieces as FEN: {}",
+            fen_fragment(&game_state.board(), selection.squares())
+        );
+    }
+}
+
+fn update_selection_ui(
+    selection: Res<SelectionSet>,
+    game_state: Res<GameState>,
+    mut query: Query<&mut Text, With<SelectionText>>,
+) {
+    if !selection.is_changed() && !game_state.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    text.sections[0].value = if selection.squares().is_empty() {
+        String::new()
+    } else {
+        let squares: Vec<_> = selection
+            .squares()
+            .iter()
+            .filter_map(|pos| pos.to_algebraic())
+            .collect();
+        format!(
+            "Selected: {}\nPieces: {}",
+            squares.join(" "),
+            piece_list(&game_state.board(), selection.squares())
+        )
+    };
+}
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectionSet>()
+            .add_startup_system(setup)
+            .add_system(select_squares)
+            .add_system(export_selection)
+            .add_system(
+                update_selection_ui
+                    .after(select_squares)
+                    .after(export_selection),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::TurnData,
+        test_app::{self, game_app},
+    };
+
+    fn shift_click(app: &mut App, square: &str) {
+        app.world.send_event(ClickSquareEvent {
+            kind: MouseButton::Left,
+            board_pos: BoardPosition::from_algebraic(square),
+            shift: true,
+        });
+        test_app::update(app);
+    }
+
+    fn selected(app: &App) -> Vec<String> {
+        app.world
+            .resource::<SelectionSet>()
+            .squares()
+            .iter()
+            .filter_map(|pos| pos.to_algebraic())
+            .collect()
+    }
+
+    #[test]
+    fn clicking_a_square_again_takes_it_out() {
+        let mut app = game_app();
+        app.add_plugin(SelectionPlugin);
+        // The app's already started, so the plugin's startup system won't get to make this
+        app.world
+            .spawn()
+            .insert(Text::from_section("", default()))
+            .insert(SelectionText);
+
+        for square in ["e1", "e2", "d8"] {
+            shift_click(&mut app, square);
+        }
+        assert_eq!(selected(&app), ["e1", "e2", "d8"]);
+        // Separate from picking a piece to move
+        assert_eq!(app.world.resource::<TurnData>().selected_piece(), None);
+
+        shift_click(&mut app, "e2");
+        assert_eq!(selected(&app), ["e1", "d8"]);
+        shift_click(&mut app, "e2");
+        assert_eq!(selected(&app), ["e1", "d8", "e2"]);
+
+        // Only shift-clicks count
+        test_app::click(&mut app, MouseButton::Left, "a2");
+        test_app::update(&mut app);
+        assert_eq!(selected(&app), ["e1", "d8", "e2"]);
+        shift_click(&mut app, "g1");
+
+        let game_state = app.world.resource::<GameState>();
+        let squares = app.world.resource::<SelectionSet>().squares();
+        assert_eq!(
+            piece_list(&game_state.board(), squares),
+            "Ke1, qd8, Pe2, Ng1"
+        );
+        assert_eq!(
+            fen_fragment(&game_state.board(), squares),
+            "3q4/8/8/8/8/8/4P3/4K1N1"
+        );
+
+        test_app::press(&mut app, KeyCode::Delete);
+        assert!(selected(&app).is_empty());
+    }
+}
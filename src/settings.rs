@@ -0,0 +1,260 @@
+use std::{fmt, fs, io, path::Path, str::FromStr};
+
+use bevy::{prelude::*, window::WindowMode};
+
+use crate::{
+    board::{BoardScale, ShowMoveHints},
+    confetti::ShowConfetti,
+    display::DisplaySettings,
+    lighting::Lighting,
+    pieces::ColorblindMode,
+};
+
+/// Where the settings are kept between runs.
+pub const SETTINGS_FILE: &str = "settings.txt";
+
+/// The player's preferences, saved whenever one changes so they carry over to the next run. While the
+/// game is running each one lives in the resource it controls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub show_move_hints: bool,
+    pub colorblind_mode: bool,
+    pub shadows: bool,
+    pub board_scale: f32,
+    pub window_mode: WindowMode,
+    pub confetti: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            show_move_hints: ShowMoveHints::default().0,
+            colorblind_mode: ColorblindMode::default().0,
+            shadows: Lighting::default().shadows,
+            board_scale: BoardScale::default().0,
+            window_mode: DisplaySettings::default().mode,
+            confetti: ShowConfetti::default().0,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings file, falling back to the defaults if it's missing or unreadable.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        // This happens before the app has started, so there's no logging yet
+        match fs::read_to_string(path) {
+            Ok(settings) => settings.parse().unwrap_or_else(|e| {
+                eprintln!("Ignoring settings file, {e}");
+                Self::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                eprintln!("Couldn't read settings file: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Puts each setting in its resource. The window mode isn't one of them since the window is set up
+    /// from the DisplaySettings, which the caller makes.
+    pub fn insert_resources(&self, app: &mut App) {
+        app.insert_resource(ShowMoveHints(self.show_move_hints))
+            .insert_resource(ColorblindMode(self.colorblind_mode))
+            .insert_resource(Lighting {
+                shadows: self.shadows,
+                ..default()
+            })
+            .insert_resource(BoardScale(self.board_scale))
+            .insert_resource(ShowConfetti(self.confetti));
+    }
+}
+
+fn window_mode_name(mode: WindowMode) -> &'static str {
+    match mode {
+        WindowMode::Windowed => "windowed",
+        WindowMode::Fullscreen => "fullscreen",
+        WindowMode::BorderlessFullscreen => "borderless",
+        WindowMode::SizedFullscreen => "sized",
+    }
+}
+
+// One "<name> <value>" per line, e.g. "shadows true"
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "move_hints {}", self.show_move_hints)?;
+        writeln!(f, "colorblind_mode {}", self.colorblind_mode)?;
+        writeln!(f, "shadows {}", self.shadows)?;
+        writeln!(f, "board_scale {}", self.board_scale)?;
+        writeln!(f, "window {}", window_mode_name(self.window_mode))?;
+        writeln!(f, "confetti {}", self.confetti)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SettingsError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+// Any setting left out keeps its default
+impl FromStr for Settings {
+    type Err = SettingsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |reason| SettingsError {
+                line: i + 1,
+                reason,
+            };
+
+            let (name, value) = line
+                .split_once(char::is_whitespace)
+                .map(|(name, value)| (name, value.trim()))
+                .ok_or_else(|| err("expected a name and a value"))?;
+            let flag = || value.parse().map_err(|_| err("expected true or false"));
+            match name {
+                "move_hints" => settings.show_move_hints = flag()?,
+                "colorblind_mode" => settings.colorblind_mode = flag()?,
+                "shadows" => settings.shadows = flag()?,
+                "confetti" => settings.confetti = flag()?,
+                "board_scale" => {
+                    settings.board_scale = value
+                        .parse()
+                        .ok()
+                        .filter(|scale: &f32| scale.is_finite() && *scale > 0.0)
+                        .ok_or_else(|| err("expected a positive scale"))?;
+                }
+                "window" => {
+                    settings.window_mode = match value {
+                        "windowed" => WindowMode::Windowed,
+                        "fullscreen" => WindowMode::Fullscreen,
+                        "borderless" => WindowMode::BorderlessFullscreen,
+                        "sized" => WindowMode::SizedFullscreen,
+                        _ => return Err(err("unknown window mode")),
+                    };
+                }
+                _ => return Err(err("unknown setting")),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+fn save_settings(
+    show_move_hints: Res<ShowMoveHints>,
+    colorblind_mode: Res<ColorblindMode>,
+    lighting: Res<Lighting>,
+    board_scale: Res<BoardScale>,
+    display: Res<DisplaySettings>,
+    show_confetti: Res<ShowConfetti>,
+) {
+    // Everything was just loaded on the first run, so there's nothing new to save then
+    let changed = show_move_hints.is_changed()
+        || colorblind_mode.is_changed()
+        || lighting.is_changed()
+        || board_scale.is_changed()
+        || display.is_changed()
+        || show_confetti.is_changed();
+    if !changed || show_move_hints.is_added() {
+        return;
+    }
+
+    let settings = Settings {
+        show_move_hints: show_move_hints.0,
+        colorblind_mode: colorblind_mode.0,
+        shadows: lighting.shadows,
+        board_scale: board_scale.0,
+        window_mode: display.mode,
+        confetti: show_confetti.0,
+    };
+    if let Err(e) = settings.save(SETTINGS_FILE) {
+        warn!("Couldn't save settings: {e}");
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::Last, save_settings); // After this frame's toggles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("schach-{}-{name}", std::process::id()))
+    }
+
+    fn changed_settings() -> Settings {
+        Settings {
+            show_move_hints: false,
+            colorblind_mode: true,
+            shadows: false,
+            board_scale: 1.25,
+            window_mode: WindowMode::BorderlessFullscreen,
+            confetti: false,
+        }
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        for settings in [Settings::default(), changed_settings()] {
+            assert_eq!(settings.to_string().parse::<Settings>(), Ok(settings));
+        }
+
+        let path = temp_file("round-trip.txt");
+        changed_settings().save(&path).unwrap();
+        let loaded = Settings::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, changed_settings());
+    }
+
+    #[test]
+    fn missing_or_broken_files_give_the_defaults() {
+        assert_eq!(
+            Settings::load(temp_file("missing.txt")),
+            Settings::default()
+        );
+
+        let path = temp_file("broken.txt");
+        fs::write(&path, "shadows maybe\n").unwrap();
+        let loaded = Settings::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Settings::default());
+
+        assert_eq!(
+            "board_scale -1".parse::<Settings>(),
+            Err(SettingsError {
+                line: 1,
+                reason: "expected a positive scale"
+            })
+        );
+        assert_eq!(
+            "# Only one\nconfetti false".parse::<Settings>(),
+            Ok(Settings {
+                confetti: false,
+                ..Settings::default()
+            })
+        );
+    }
+}
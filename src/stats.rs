@@ -0,0 +1,130 @@
+use std::{fmt, time::Duration};
+
+use crate::{
+    ai::{material_balance, PieceValues},
+    game::MoveHistory,
+    pieces::PieceColor,
+};
+
+/// A summary of how a game went, for showing once it's over.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GameStats {
+    pub plies: usize,
+    pub white_captures: u32, // Pieces White took, not counting their own lost to Atomic explosions
+    pub black_captures: u32,
+    pub longest_think: Option<Duration>, // Longest anyone spent on one move, None before any moves
+    pub material_balance: i32,           // White's material minus Black's at the end, in centipawns
+}
+
+impl GameStats {
+    /// The material balance is scored with `values`, the same as the AI scores it.
+    pub fn from_history(
+        history: &MoveHistory,
+        move_times: &[Duration],
+        values: &PieceValues,
+    ) -> Self {
+        let mut stats = Self {
+            plies: history.moves().len(),
+            white_captures: 0,
+            black_captures: 0,
+            longest_think: move_times.iter().max().copied(),
+            material_balance: 0,
+        };
+
+        let mut game_state = history.position_at(0);
+        for mv in history.moves() {
+            let player = game_state.curr_player;
+            let captures = game_state
+                .make_move(*mv)
+                .iter()
+                .filter(|(piece, _)| piece.color != player)
+                .count() as u32;
+            match player {
+                PieceColor::White => stats.white_captures += captures,
+                PieceColor::Black => stats.black_captures += captures,
+            }
+        }
+        stats.material_balance = material_balance(&game_state, values);
+        stats
+    }
+}
+
+impl fmt::Display for GameStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Moves: {}", (self.plies + 1) / 2)?;
+        writeln!(
+            f,
+            "Captures: White {}, Black {}",
+            self.white_captures, self.black_captures
+        )?;
+        if let Some(think) = self.longest_think {
+            writeln!(f, "Longest think: {:.1}s", think.as_secs_f32())?;
+        }
+        let pawns = self.material_balance as f32 / 100.0;
+        match self.material_balance {
+            0 => write!(f, "Material: even"),
+            balance if balance > 0 => write!(f, "Material: White +{pawns:.1}"),
+            _ => write!(f, "Material: Black +{:.1}", -pawns),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_app::{self, game_app};
+
+    #[test]
+    fn stats_for_a_game_with_a_lost_queen() {
+        let mut app = game_app();
+        test_app::play(
+            &mut app,
+            &["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "d5g2", "f1g2"],
+        );
+        let move_times = [
+            Duration::from_secs(2),
+            Duration::from_secs(9),
+            Duration::from_secs(1),
+        ];
+        let history = app.world.resource::<MoveHistory>();
+        let stats = GameStats::from_history(history, &move_times, &PieceValues::default());
+
+        assert_eq!(
+            stats,
+            GameStats {
+                plies: 7,
+                white_captures: 2,
+                black_captures: 2,
+                longest_think: Some(Duration::from_secs(9)),
+                material_balance: 800,
+            }
+        );
+        assert_eq!(
+            stats.to_string(),
+            "Moves: 4\nCaptures: White 2, Black 2\nLongest think: 9.0s\nMaterial: White +8.0"
+        );
+
+        // Goes by the values the AI's been tuned with
+        let values = PieceValues {
+            queen: 1000,
+            ..PieceValues::default()
+        };
+        let stats = GameStats::from_history(history, &move_times, &values);
+        assert_eq!(stats.material_balance, 900);
+    }
+
+    #[test]
+    fn stats_before_any_moves() {
+        let app = game_app();
+        let stats = GameStats::from_history(
+            app.world.resource::<MoveHistory>(),
+            &[],
+            &PieceValues::default(),
+        );
+        assert_eq!(stats, GameStats::default());
+        assert_eq!(
+            stats.to_string(),
+            "Moves: 0\nCaptures: White 0, Black 0\nMaterial: even"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+// Headless apps for tests that go through the game's systems rather than calling GameState directly. Piece
+// animations are finished the frame they start, so a move only takes a few frames.
+use bevy::{
+    asset::AssetPlugin,
+    audio::AudioPlugin,
+    input::{keyboard::KeyboardInput, ButtonState, InputPlugin},
+    prelude::*,
+    time::{create_time_channels, TimeSender},
+    utils::{Duration, Instant},
+};
+use bevy_mod_picking::PickingEvent;
+
+use crate::{
+    board::{
+        BoardPlugin, BoardPosition, BoardScale, ClickSquareEvent, HoveredSquare, InputBindings,
+        ShadowSquares, ShowMoveHints,
+    },
+    coach::CoachPlugin,
+    game::{GamePlugin, GameState, LoadPositionEvent, ScriptedMoves, TurnData},
+    notation::{from_fen, parse_uci},
+    pieces::{BuiltinAnimation, Piece, PieceAnimCompleteEvent, PieceMoveEvent, PiecesPlugin},
+};
+
+fn base_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(AudioPlugin)
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .insert_resource(BuiltinAnimation(false));
+    app
+}
+
+// The game and its pieces, without the board's squares
+pub fn game_app() -> App {
+    let mut app = base_app();
+    app.add_event::<ClickSquareEvent>()
+        .init_resource::<InputBindings>()
+        .init_resource::<ShowMoveHints>()
+        .init_resource::<ShadowSquares>()
+        .init_resource::<BoardScale>()
+        .init_resource::<HoveredSquare>()
+        .add_plugin(PiecesPlugin)
+        .add_plugin(GamePlugin);
+    app.update();
+    app
+}
+
+// The game along with the board's squares and highlights
+pub fn board_app() -> App {
+    board_app_with(|_| ())
+}
+
+// board_app(), with `setup` run before it starts, e.g. to insert settings the startup systems read
+pub fn board_app_with(setup: impl FnOnce(&mut App)) -> App {
+    let mut app = base_app();
+    app.add_event::<PickingEvent>()
+        .add_plugin(BoardPlugin)
+        .add_plugin(PiecesPlugin)
+        .add_plugin(GamePlugin)
+        .add_plugin(CoachPlugin);
+    setup(&mut app);
+    app.update();
+    app
+}
+
+// Runs a frame, then finishes any piece animations it started
+pub fn update(app: &mut App) {
+    app.update();
+    let events = app.world.resource::<Events<PieceMoveEvent>>();
+    let entities: Vec<Entity> = events
+        .get_reader()
+        .iter(events)
+        .map(|ev| ev.entity)
+        .collect();
+    for entity in entities {
+        app.world.send_event(PieceAnimCompleteEvent { entity });
+    }
+}
+
+// Starts a new game from the position
+pub fn load(app: &mut App, fen: &str) {
+    let game_state = from_fen(fen).unwrap();
+    app.world.send_event(LoadPositionEvent(game_state));
+    for _ in 0..3 {
+        update(app);
+    }
+}
+
+// Plays the moves, given in UCI notation, then runs until the next move is wanted or the game is over
+pub fn play(app: &mut App, moves: &[&str]) {
+    let target = app.world.resource::<GameState>().plies + moves.len() as u32;
+    let frames = 20 * (moves.len() + 1);
+    app.insert_resource(ScriptedMoves::new(
+        moves.iter().map(|uci| parse_uci(uci).unwrap()),
+    ));
+    for _ in 0..frames {
+        update(app);
+        let game_state = app.world.resource::<GameState>();
+        let settled =
+            game_state.game_over.is_some() || app.world.resource::<TurnData>().is_awaiting_move();
+        if game_state.plies >= target && settled {
+            return;
+        }
+    }
+    panic!("Moves weren't all played");
+}
+
+// The square with the name, e.g. "e4", for setting up and checking positions
+pub fn square(name: &str) -> BoardPosition {
+    BoardPosition::from_algebraic(name).unwrap_or_else(|| panic!("Not a square: {name}"))
+}
+
+// Clicks a square, as a player would with the mouse
+pub fn click(app: &mut App, kind: MouseButton, square: &str) {
+    app.world.send_event(ClickSquareEvent {
+        kind,
+        board_pos: BoardPosition::from_algebraic(square),
+        shift: false,
+    });
+    update(app);
+}
+
+// Presses and lets go of a key, over two frames
+pub fn press(app: &mut App, key_code: KeyCode) {
+    for state in [ButtonState::Pressed, ButtonState::Released] {
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key_code),
+            state,
+        });
+        update(app);
+    }
+}
+
+// The board as the piece entities have it
+pub fn entity_board(app: &mut App) -> [[Option<Piece>; 8]; 8] {
+    let mut board = [[None; 8]; 8];
+    for (piece, pos) in app
+        .world
+        .query::<(&Piece, &BoardPosition)>()
+        .iter(&app.world)
+    {
+        board[pos.row as usize][pos.col as usize] = Some(*piece);
+    }
+    board
+}
+
+// A clock the test moves on itself, for systems that go by how much time has passed
+pub struct Clock {
+    sender: TimeSender,
+    now: Instant,
+}
+
+impl Clock {
+    // From now on the app's time only moves when the clock does
+    pub fn new(app: &mut App) -> Self {
+        let (sender, receiver) = create_time_channels();
+        app.insert_resource(receiver);
+        Self {
+            sender,
+            now: Instant::now(),
+        }
+    }
+
+    // Runs a frame `secs` after the last one, without finishing any animations
+    pub fn update(&mut self, app: &mut App, secs: f32) {
+        self.now += Duration::from_secs_f32(secs);
+        self.sender.0.send(self.now).unwrap();
+        app.update();
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+
+use crate::{
+    assets::FONT_PATH,
+    board::{BoardPosition, BoardScale},
+    game::{Move, MoveHistory},
+    review::ReviewState,
+};
+
+/// Numbers the squares the last few moves went to, so the order they were played in can be followed.
+/// Toggled with M.
+#[derive(Clone, Copy, Debug)]
+pub struct MoveTrail {
+    pub show: bool,
+    pub length: usize, // How many moves back it goes
+}
+
+impl Default for MoveTrail {
+    fn default() -> Self {
+        Self {
+            show: false,
+            length: 5,
+        }
+    }
+}
+
+/// The squares the last `length` of the moves went to, numbered from 1 for the oldest. A square that was
+/// moved to more than once only keeps its latest number.
+pub fn move_trail(moves: &[Move], length: usize) -> Vec<(usize, BoardPosition)> {
+    let recent = &moves[moves.len().saturating_sub(length)..];
+    recent
+        .iter()
+        .enumerate()
+        .filter(|(i, mv)| !recent[i + 1..].iter().any(|later| later.to == mv.to))
+        .map(|(i, mv)| (i + 1, mv.to))
+        .collect()
+}
+
+#[derive(Component)]
+struct TrailLabel;
+
+fn toggle_move_trail(keyboard_input: Res<Input<KeyCode>>, mut move_trail: ResMut<MoveTrail>) {
+    if keyboard_input.just_pressed(KeyCode::M) {
+        move_trail.show = !move_trail.show;
+    }
+}
+
+// The labels are UI text kept over their squares, so they're placed again every frame in case the camera
+// has moved
+#[allow(clippy::too_many_arguments)]
+fn show_move_trail(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    trail_settings: Res<MoveTrail>,
+    move_history: Res<MoveHistory>,
+    review_state: Res<ReviewState>,
+    board_scale: Res<BoardScale>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut label_query: Query<(Entity, &mut Text, &mut Style, &mut Visibility), With<TrailLabel>>,
+) {
+    // Only the moves up to the position on the board, which is an earlier one while reviewing
+    let shown = review_state.ply.unwrap_or(move_history.moves().len());
+    let trail = if trail_settings.show {
+        move_trail(&move_history.moves()[..shown], trail_settings.length)
+    } else {
+        Vec::new()
+    };
+
+    // One label per entry, spawned hidden and shown once they've been placed
+    let mut labels: Vec<_> = label_query.iter_mut().collect();
+    for (entity, ..) in labels.drain(trail.len().min(labels.len())..) {
+        commands.entity(entity).despawn();
+    }
+    for _ in labels.len()..trail.len() {
+        commands
+            .spawn_bundle(TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 22.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(TrailLabel);
+    }
+
+    let (camera, camera_transform) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let newest = trail.last().map_or(0, |(number, _)| *number);
+    for ((_, mut text, mut style, mut visibility), (number, pos)) in labels.into_iter().zip(&trail)
+    {
+        // Towards the corner of the square, clear of the piece standing on it
+        let corner = Vec3::new(-0.3, 0.0, 0.3) * board_scale.0;
+        let world_pos = pos.to_scaled_translation(&board_scale) + corner;
+        let screen_pos = match camera.world_to_viewport(camera_transform, world_pos) {
+            Some(screen_pos) => screen_pos,
+            None => {
+                visibility.is_visible = false;
+                continue;
+            }
+        };
+        let position = UiRect {
+            left: Val::Px(screen_pos.x),
+            bottom: Val::Px(screen_pos.y),
+            ..default()
+        };
+
+        // Older moves fade out, the latest is fully opaque
+        let age = newest - number;
+        let alpha = 1.0 - age as f32 / trail_settings.length.max(1) as f32;
+        let value = number.to_string();
+        let color = Color::rgba(1.0, 0.9, 0.3, alpha.max(0.2));
+
+        // Only touched when something's different, so an idle board doesn't lay the text out again
+        if style.position != position {
+            style.position = position;
+        }
+        if text.sections[0].value != value || text.sections[0].style.color != color {
+            text.sections[0].value = value;
+            text.sections[0].style.color = color;
+        }
+        if !visibility.is_visible {
+            visibility.is_visible = true;
+        }
+    }
+}
+
+pub struct MoveTrailPlugin;
+
+impl Plugin for MoveTrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MoveTrail>()
+            .add_system(toggle_move_trail)
+            .add_system(show_move_trail.after(toggle_move_trail));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        notation,
+        test_app::{self, game_app, square},
+    };
+
+    fn moves(ucis: &[&str]) -> Vec<Move> {
+        ucis.iter()
+            .map(|uci| notation::parse_uci(uci).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn three_moves_make_a_numbered_trail() {
+        let played = moves(&["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(
+            move_trail(&played, 5),
+            [(1, square("e4")), (2, square("e5")), (3, square("f3"))]
+        );
+        // Only as far back as the trail's length
+        assert_eq!(
+            move_trail(&played, 2),
+            [(1, square("e5")), (2, square("f3"))]
+        );
+        assert!(move_trail(&[], 5).is_empty());
+
+        // A square gone to again only keeps its latest number
+        let played = moves(&["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"]);
+        assert_eq!(
+            move_trail(&played, 5),
+            [
+                (2, square("f6")),
+                (3, square("g1")),
+                (4, square("g8")),
+                (5, square("f3"))
+            ]
+        );
+    }
+
+    #[test]
+    fn a_label_for_each_move_while_shown() {
+        let mut app = game_app();
+        app.add_plugin(MoveTrailPlugin);
+        test_app::load(
+            &mut app,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        );
+        test_app::play(&mut app, &["e2e4", "e7e5", "g1f3"]);
+        let labels = |app: &mut App| {
+            test_app::update(app);
+            app.world
+                .query_filtered::<(), With<TrailLabel>>()
+                .iter(&app.world)
+                .count()
+        };
+        assert_eq!(labels(&mut app), 0);
+
+        test_app::press(&mut app, KeyCode::M);
+        assert_eq!(labels(&mut app), 3);
+        test_app::press(&mut app, KeyCode::M);
+        assert_eq!(labels(&mut app), 0);
+    }
+}
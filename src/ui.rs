@@ -1,10 +1,119 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 
-use crate::game::{GameOver, GameState};
+use crate::{
+    ai::PieceValues,
+    board::{BoardPosition, HoveredSquare},
+    clock::ChessClock,
+    coach::GuardedMove,
+    display::FlipBoardEvent,
+    editor::Editor,
+    game::{
+        game_over_message, ClaimDrawEvent, ClaimableDraw, GameRng, GameRules, GameState,
+        IllegalMove, IllegalMoveEvent, InCheck, InvalidBoard, MoveHistory, MoveMadeEvent,
+        MoveTimes, NewGameEvent, OfferDrawEvent, PlayerConfig, PlayerKind, RequestUndoEvent,
+        ResignEvent, TurnData, Variant, PROMOTION_KEYS,
+    },
+    notation::piece_char,
+    pieces::{Piece, PieceColor},
+    review::ReviewState,
+    stats::GameStats,
+};
 
 #[derive(Component)]
 struct GameStateText;
 
+#[derive(Component)]
+struct ClaimDrawText;
+
+#[derive(Component)]
+struct VariantText;
+
+#[derive(Component)]
+struct ClockText;
+
+#[derive(Component)]
+struct ReviewText;
+
+#[derive(Component)]
+struct HoveredSquareText;
+
+#[derive(Component)]
+struct IllegalMoveText;
+
+#[derive(Component)]
+struct MoveCountText;
+
+#[derive(Component)]
+struct StatsText;
+
+#[derive(Component)]
+struct GuardedMoveText;
+
+#[derive(Component)]
+struct PromotionText;
+
+#[derive(Component)]
+struct CapturePopupBadge;
+
+#[derive(Component)]
+struct CapturePopupLetter;
+
+#[derive(Component)]
+struct CapturePopupText;
+
+// The capture popup stays up for a while, then fades out over the end of that
+const CAPTURE_POPUP_SECS: f32 = 2.0;
+const CAPTURE_POPUP_FADE_SECS: f32 = 0.5;
+
+// The buttons down the right-hand side, each doing the same as its key (if it has one)
+#[derive(Clone, Component, Copy, Debug, Eq, PartialEq)]
+enum PanelButton {
+    NewGame,
+    Resign,
+    OfferDraw,
+    Undo,
+    FlipBoard,
+}
+
+const PANEL_BUTTONS: [PanelButton; 5] = [
+    PanelButton::NewGame,
+    PanelButton::Resign,
+    PanelButton::OfferDraw,
+    PanelButton::Undo,
+    PanelButton::FlipBoard,
+];
+
+impl PanelButton {
+    fn label(self) -> &'static str {
+        match self {
+            PanelButton::NewGame => "New Game",
+            PanelButton::Resign => "Resign",
+            PanelButton::OfferDraw => "Offer Draw",
+            PanelButton::Undo => "Undo",
+            PanelButton::FlipBoard => "Flip Board",
+        }
+    }
+
+    // Whether clicking it would do anything. `playing` is whether the game is still going, `plies` how
+    // many half-moves have been made so far, and `moving` whether a move is still being shown. Resigning
+    // or undoing has to wait for that move to finish.
+    fn is_enabled(self, playing: bool, plies: usize, reviewing: bool, moving: bool) -> bool {
+        match self {
+            PanelButton::NewGame | PanelButton::FlipBoard => true,
+            PanelButton::Resign => playing && !reviewing && !moving,
+            PanelButton::OfferDraw => playing && !reviewing,
+            PanelButton::Undo => plies > 0 && !reviewing && !moving,
+        }
+    }
+}
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const BUTTON_PRESSED_COLOR: Color = Color::rgb(0.35, 0.55, 0.35);
+const BUTTON_DISABLED_COLOR: Color = Color::rgba(0.15, 0.15, 0.15, 0.5);
+
 fn setup(mut commands: Commands, asset_server: ResMut<AssetServer>) {
     commands
         .spawn_bundle(
@@ -30,26 +139,837 @@ fn setup(mut commands: Commands, asset_server: ResMut<AssetServer>) {
             }),
         )
         .insert(GameStateText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ClaimDrawText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(VariantText);
+
+    let clock_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 40.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(
+            TextBundle::from_sections([
+                TextSection::new("", clock_style.clone()),
+                TextSection::new("", clock_style),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ClockText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ReviewText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(50.0), // Above the claim draw text
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(HoveredSquareText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::ORANGE,
+                },
+            )
+            .with_text_alignment(TextAlignment::BOTTOM_CENTER)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(25.0),
+                    right: Val::Percent(25.0),
+                    bottom: Val::Px(90.0), // Clear of the text along the bottom edge
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(IllegalMoveText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::YELLOW,
+                },
+            )
+            .with_text_alignment(TextAlignment::BOTTOM_CENTER)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(25.0),
+                    right: Val::Percent(25.0),
+                    bottom: Val::Px(130.0), // Above the illegal move text
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(GuardedMoveText);
+
+    // In the same place as the guarded move text, since a move is only guarded once it's complete
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::YELLOW,
+                },
+            )
+            .with_text_alignment(TextAlignment::BOTTOM_CENTER)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(25.0),
+                    right: Val::Percent(25.0),
+                    bottom: Val::Px(130.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(PromotionText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(90.0), // Above the hovered square's name
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(MoveCountText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    top: Val::Percent(30.0), // Below the variant text
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(StatsText);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    top: Val::Percent(30.0), // Below the clock
+                    ..default()
+                },
+                flex_direction: FlexDirection::ColumnReverse, // Top to bottom
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for button in PANEL_BUTTONS {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(160.0), Val::Px(40.0)),
+                            margin: UiRect::all(Val::Px(4.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..default()
+                    })
+                    .insert(button)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            button.label(),
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                    });
+            }
+        });
 }
 
-fn update_ui(game_state: Res<GameState>, mut query: Query<&mut Text, With<GameStateText>>) {
-    if !game_state.is_changed() {
+fn illegal_move_text(reason: IllegalMove) -> &'static str {
+    match reason {
+        IllegalMove::NotADestination => "Not a legal destination",
+        IllegalMove::LeavesKingInCheck => "That move leaves your king in check",
+        IllegalMove::Pinned => "That piece is pinned to your king",
+        IllegalMove::DestroysOwnKing => "That move blows up your own king",
+    }
+}
+
+fn update_ui(
+    game_state: Res<GameState>,
+    rules: Res<GameRules>,
+    invalid_board: Res<InvalidBoard>,
+    in_check: Res<InCheck>,
+    editor: Res<Editor>,
+    mut query: Query<&mut Text, With<GameStateText>>,
+) {
+    if !game_state.is_changed()
+        && !invalid_board.is_changed()
+        && !in_check.is_changed()
+        && !editor.is_changed()
+    {
         return;
     }
 
     let mut text = query.get_single_mut().unwrap();
-    let value = match game_state.game_over {
-        Some(GameOver::Checkmate(winner)) => format!("CHECKMATE!\n{} wins!", winner),
-        Some(GameOver::Stalemate) => String::from("STALEMATE"),
+    if editor.active {
+        text.sections[0].value = format!("EDITING\n{} moves first", editor.to_move);
+        return;
+    }
+    if let Some(err) = invalid_board.0 {
+        text.sections[0].value = format!("INVALID BOARD\n{err}");
+        return;
+    }
+
+    let value = match &game_state.game_over {
+        Some(game_over) => game_over_message(game_over, rules.variant),
+        None if in_check.0.is_some() => format!("CHECK!\n{} to move", game_state.curr_player),
         None => format!("{} to move", game_state.curr_player),
     };
     text.sections[0].value = value;
 }
 
+// A summary of the game once it's over
+fn update_stats_ui(
+    game_state: Res<GameState>,
+    move_history: Res<MoveHistory>,
+    move_times: Res<MoveTimes>,
+    values: Res<PieceValues>,
+    mut query: Query<&mut Text, With<StatsText>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    text.sections[0].value = match game_state.game_over {
+        Some(_) => GameStats::from_history(&move_history, move_times.times(), &values).to_string(),
+        None => String::new(),
+    };
+}
+
+fn update_claim_draw_ui(
+    game_state: Res<GameState>,
+    claimable_draw: Res<ClaimableDraw>,
+    mut query: Query<&mut Text, With<ClaimDrawText>>,
+) {
+    if !game_state.is_changed() && !claimable_draw.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    let value = match (game_state.game_over, claimable_draw.0) {
+        (None, Some(reason)) => format!("Press D to claim a draw ({})", reason.description()),
+        _ => String::new(),
+    };
+    text.sections[0].value = value;
+}
+
+fn update_variant_ui(
+    game_state: Res<GameState>,
+    rules: Res<GameRules>,
+    game_rng: Res<GameRng>,
+    mut query: Query<&mut Text, With<VariantText>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    // The seed's there for playing the same game again with `--seed`
+    let mut value = format!("Seed {}\n", game_rng.seed);
+    if let Some(number) = rules.start.chess960_number() {
+        value += &format!("Chess960 #{number}\n");
+    }
+    if rules.variant == Variant::ThreeCheck {
+        value += &format!(
+            "Checks\nWhite: {}\nBlack: {}",
+            game_state.checks.white, game_state.checks.black
+        );
+    }
+    text.sections[0].value = value;
+}
+
+fn update_move_count_ui(
+    game_state: Res<GameState>,
+    mut query: Query<&mut Text, With<MoveCountText>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    text.sections[0].value = format!(
+        "Move {}\nHalf-moves: {}",
+        game_state.fullmove_number(),
+        game_state.plies
+    );
+}
+
+fn format_clock(time: Duration) -> String {
+    let secs = time.as_secs();
+    if time < Duration::from_secs(10) {
+        format!("{:.1}", time.as_secs_f32()) // Tenths matter at this point
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn update_clock_ui(
+    game_state: Res<GameState>,
+    clock: Option<Res<ChessClock>>,
+    mut query: Query<&mut Text, With<ClockText>>,
+) {
+    let clock = match clock {
+        Some(clock) => clock,
+        None => return,
+    };
+
+    let mut text = query.get_single_mut().unwrap();
+    for (section, player) in text
+        .sections
+        .iter_mut()
+        .zip([PieceColor::White, PieceColor::Black])
+    {
+        section.value = format!("{}: {}\n", player, format_clock(clock.remaining(player)));
+        section.style.color = if player == game_state.curr_player && clock.is_low(player) {
+            Color::RED
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+fn update_review_ui(
+    review_state: Res<ReviewState>,
+    game_state: Res<GameState>,
+    move_history: Res<MoveHistory>,
+    mut query: Query<&mut Text, With<ReviewText>>,
+) {
+    if !review_state.is_changed() && !game_state.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    let total = move_history.moves().len();
+    text.sections[0].value = match review_state.ply {
+        Some(ply) if review_state.confirming_take_back => format!(
+            "Carry on from here? The last {} half-moves will be lost (Y/N)",
+            total - ply
+        ),
+        Some(ply) => format!(
+            "Reviewing {ply}/{total} (Left/Right to step, End to return, Enter to play on from here)"
+        ),
+        None if game_state.game_over.is_some() => {
+            String::from("Game over (Left/Home to review the moves, or start a new game)")
+        }
+        None => String::new(),
+    };
+}
+
+fn update_hovered_square_ui(
+    hovered_square: Res<HoveredSquare>,
+    board_pos_query: Query<&BoardPosition>,
+    mut query: Query<&mut Text, With<HoveredSquareText>>,
+) {
+    if !hovered_square.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    text.sections[0].value = hovered_square
+        .entity
+        .and_then(|entity| board_pos_query.get(entity).ok())
+        .and_then(|pos| pos.to_algebraic())
+        .unwrap_or_default();
+}
+
+/// What the popup says when a piece is taken, e.g. "White captured a Knight".
+pub fn capture_message(captured: Piece) -> String {
+    format!("{} captured a {}", captured.color.next(), captured.kind)
+}
+
+/// How opaque the capture popup is `elapsed` seconds after it came up: fully until it starts to fade, then
+/// down to nothing by the time it goes.
+pub fn capture_popup_alpha(elapsed: f32) -> f32 {
+    let fade_start = CAPTURE_POPUP_SECS - CAPTURE_POPUP_FADE_SECS;
+    (1.0 - (elapsed - fade_start) / CAPTURE_POPUP_FADE_SECS).clamp(0.0, 1.0)
+}
+
+fn setup_capture_popup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(0.0),
+                    right: Val::Px(0.0),
+                    top: Val::Px(60.0), // Under the variant and clock text
+                    ..default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            // The captured piece's letter on a square of its color, standing in for an icon of it
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(36.0), Val::Px(36.0)),
+                        margin: UiRect {
+                            right: Val::Px(10.0),
+                            ..default()
+                        },
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(CapturePopupBadge)
+                .with_children(|badge| {
+                    badge
+                        .spawn_bundle(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 30.0,
+                                color: Color::NONE,
+                            },
+                        ))
+                        .insert(CapturePopupLetter);
+                });
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font,
+                        font_size: 30.0,
+                        color: Color::NONE,
+                    },
+                ))
+                .insert(CapturePopupText);
+        });
+}
+
+// Says what was just taken, then fades away. A new capture starts it over.
+#[allow(clippy::type_complexity)]
+fn update_capture_popup(
+    time: Res<Time>,
+    mut move_made_events: EventReader<MoveMadeEvent>,
+    mut shown: Local<Option<(Piece, f32)>>, // What was captured and how long ago
+    mut badge_query: Query<&mut UiColor, With<CapturePopupBadge>>,
+    mut letter_query: Query<&mut Text, (With<CapturePopupLetter>, Without<CapturePopupText>)>,
+    mut text_query: Query<&mut Text, (With<CapturePopupText>, Without<CapturePopupLetter>)>,
+) {
+    let new_capture = move_made_events
+        .iter()
+        .filter_map(|ev| ev.captured)
+        .next_back();
+    if let Some(piece) = new_capture {
+        *shown = Some((piece, 0.0));
+        letter_query.single_mut().sections[0].value =
+            piece_char(piece).to_ascii_uppercase().to_string();
+        text_query.single_mut().sections[0].value = capture_message(piece);
+    }
+    let (piece, elapsed) = match &mut *shown {
+        Some((piece, elapsed)) => (*piece, elapsed),
+        None => return,
+    };
+    if new_capture.is_none() {
+        *elapsed += time.delta_seconds();
+    }
+
+    let alpha = capture_popup_alpha(*elapsed);
+    let (mut back, mut front) = match piece.color {
+        PieceColor::White => (Color::WHITE, Color::BLACK),
+        PieceColor::Black => (Color::BLACK, Color::WHITE),
+    };
+    let mut message = Color::WHITE;
+    badge_query.single_mut().0 = *back.set_a(alpha);
+    letter_query.single_mut().sections[0].style.color = *front.set_a(alpha);
+    text_query.single_mut().sections[0].style.color = *message.set_a(alpha);
+    if *elapsed >= CAPTURE_POPUP_SECS {
+        *shown = None;
+    }
+}
+
+// Says why the last move attempt wasn't allowed, for a couple of seconds
+fn update_illegal_move_ui(
+    time: Res<Time>,
+    mut illegal_move_events: EventReader<IllegalMoveEvent>,
+    mut timer: Local<Timer>,
+    mut query: Query<&mut Text, With<IllegalMoveText>>,
+) {
+    let mut text = query.get_single_mut().unwrap();
+    if let Some(event) = illegal_move_events.iter().last() {
+        text.sections[0].value = illegal_move_text(event.reason).to_string();
+        *timer = Timer::from_seconds(2.0, false);
+    } else if timer.tick(time.delta()).just_finished() {
+        text.sections[0].value.clear();
+    }
+}
+
+fn update_guarded_move_ui(
+    guarded_move: Res<GuardedMove>,
+    mut query: Query<&mut Text, With<GuardedMoveText>>,
+) {
+    if !guarded_move.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    text.sections[0].value = match guarded_move.0 {
+        Some(_) => "That move gives away material. Play it anyway? (Y/N)".to_string(),
+        None => String::new(),
+    };
+}
+
+fn update_promotion_ui(turn_data: Res<TurnData>, mut query: Query<&mut Text, With<PromotionText>>) {
+    if !turn_data.is_changed() {
+        return;
+    }
+
+    let mut text = query.get_single_mut().unwrap();
+    text.sections[0].value = if turn_data.is_choosing_promotion() {
+        let choices: Vec<_> = PROMOTION_KEYS
+            .iter()
+            .enumerate()
+            .map(|(i, (_, kind))| format!("{} {kind:?}", i + 1))
+            .collect();
+        format!("Promote to: {} (Esc to cancel)", choices.join(", "))
+    } else {
+        String::new()
+    };
+}
+
+fn claim_draw_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut claim_draw_events: EventWriter<ClaimDrawEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::D) {
+        claim_draw_events.send(ClaimDrawEvent);
+    }
+}
+
+fn undo_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut request_undo_events: EventWriter<RequestUndoEvent>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if ctrl && keyboard_input.just_pressed(KeyCode::Z) {
+        request_undo_events.send(RequestUndoEvent);
+    }
+}
+
+// Who the buttons act for. Against the AI or a remote player that's always the one at this computer,
+// otherwise it's whoever's turn it is.
+fn local_player(players: &PlayerConfig, curr_player: PieceColor) -> PieceColor {
+    match (players.white, players.black) {
+        (PlayerKind::Human, PlayerKind::Human) => curr_player,
+        (PlayerKind::Human, _) => PieceColor::White,
+        (_, PlayerKind::Human) => PieceColor::Black,
+        _ => curr_player,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_panel_buttons(
+    game_state: Res<GameState>,
+    invalid_board: Res<InvalidBoard>,
+    move_history: Res<MoveHistory>,
+    review_state: Res<ReviewState>,
+    turn_data: Res<TurnData>,
+    players: Res<PlayerConfig>,
+    claimable_draw: Res<ClaimableDraw>,
+    mut button_query: Query<(
+        &PanelButton,
+        &Interaction,
+        ChangeTrackers<Interaction>,
+        &mut UiColor,
+        &Children,
+    )>,
+    mut text_query: Query<&mut Text>,
+    mut new_game_events: EventWriter<NewGameEvent>,
+    mut resign_events: EventWriter<ResignEvent>,
+    mut offer_draw_events: EventWriter<OfferDrawEvent>,
+    mut claim_draw_events: EventWriter<ClaimDrawEvent>,
+    mut request_undo_events: EventWriter<RequestUndoEvent>,
+    mut flip_board_events: EventWriter<FlipBoardEvent>,
+) {
+    let playing = game_state.game_over.is_none() && invalid_board.0.is_none();
+    let plies = move_history.moves().len();
+    let reviewing = review_state.ply.is_some();
+    let moving = turn_data.move_target.is_some();
+    let player = local_player(&players, game_state.curr_player);
+
+    for (button, interaction, tracker, mut color, children) in &mut button_query {
+        let enabled = button.is_enabled(playing, plies, reviewing, moving);
+        let new_color = match (enabled, interaction) {
+            (false, _) => BUTTON_DISABLED_COLOR,
+            (true, Interaction::Clicked) => BUTTON_PRESSED_COLOR,
+            (true, Interaction::Hovered) => BUTTON_HOVERED_COLOR,
+            (true, Interaction::None) => BUTTON_COLOR,
+        };
+        if color.0 != new_color {
+            color.0 = new_color;
+            for child in children {
+                if let Ok(mut text) = text_query.get_mut(*child) {
+                    text.sections[0].style.color = if enabled { Color::WHITE } else { Color::GRAY };
+                }
+            }
+        }
+
+        // Only act on the click itself, not for as long as the button's held down
+        if !enabled || *interaction != Interaction::Clicked || !tracker.is_changed() {
+            continue;
+        }
+        match button {
+            PanelButton::NewGame => new_game_events.send(NewGameEvent),
+            PanelButton::Resign => resign_events.send(ResignEvent(player)),
+            // A draw that can be claimed doesn't need the opponent to agree
+            PanelButton::OfferDraw if claimable_draw.0.is_some() => {
+                claim_draw_events.send(ClaimDrawEvent)
+            }
+            PanelButton::OfferDraw => offer_draw_events.send(OfferDrawEvent(player)),
+            PanelButton::Undo => request_undo_events.send(RequestUndoEvent),
+            PanelButton::FlipBoard => flip_board_events.send(FlipBoardEvent),
+        }
+    }
+}
+
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup).add_system(update_ui);
+        app.add_startup_system(setup)
+            .add_startup_system(setup_capture_popup)
+            .add_system(update_ui)
+            .add_system(update_stats_ui)
+            .add_system(update_claim_draw_ui)
+            .add_system(update_variant_ui)
+            .add_system(update_clock_ui)
+            .add_system(update_review_ui)
+            .add_system(update_hovered_square_ui)
+            .add_system(update_illegal_move_ui)
+            .add_system(update_capture_popup)
+            .add_system(update_guarded_move_ui)
+            .add_system(update_promotion_ui)
+            .add_system(update_move_count_ui)
+            .add_system(claim_draw_input)
+            .add_system(undo_input)
+            .add_system(update_panel_buttons);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notation, pieces::PieceKind};
+
+    #[test]
+    fn panel_buttons_are_enabled_when_they_would_do_something() {
+        let enabled = |playing, plies, reviewing, moving| {
+            PANEL_BUTTONS.map(|button| button.is_enabled(playing, plies, reviewing, moving))
+        };
+        // New game, resign, offer draw, undo, flip board
+        assert_eq!(
+            enabled(true, 0, false, false),
+            [true, true, true, false, true]
+        );
+        assert_eq!(
+            enabled(true, 3, false, false),
+            [true, true, true, true, true]
+        );
+        assert_eq!(
+            enabled(false, 3, false, false),
+            [true, false, false, true, true]
+        );
+        assert_eq!(
+            enabled(false, 0, false, false),
+            [true, false, false, false, true]
+        );
+        assert_eq!(
+            enabled(true, 3, true, false),
+            [true, false, false, false, true]
+        );
+        assert_eq!(
+            enabled(true, 3, false, true),
+            [true, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn capture_message_names_the_capturer_and_the_piece() {
+        let piece = |color, kind| Piece { color, kind };
+        assert_eq!(
+            capture_message(piece(PieceColor::Black, PieceKind::Knight)),
+            "White captured a Knight"
+        );
+        assert_eq!(
+            capture_message(piece(PieceColor::White, PieceKind::Queen)),
+            "Black captured a Queen"
+        );
+        // Whether or not the pawn had moved
+        for moved in [false, true] {
+            assert_eq!(
+                capture_message(piece(PieceColor::Black, PieceKind::Pawn(moved))),
+                "White captured a Pawn"
+            );
+        }
+
+        // As taken in a game
+        let mut game_state =
+            notation::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let mut taken = Vec::new();
+        for uci in ["e2e4", "d7d5", "e4d5", "d8d5"] {
+            let mv = notation::parse_uci(uci).unwrap();
+            taken.extend(game_state.make_move(mv).into_iter().map(|(piece, _)| piece));
+        }
+        let messages: Vec<String> = taken.into_iter().map(capture_message).collect();
+        assert_eq!(messages, ["White captured a Pawn", "Black captured a Pawn"]);
+    }
+
+    #[test]
+    fn capture_popup_holds_then_fades() {
+        let fade_start = CAPTURE_POPUP_SECS - CAPTURE_POPUP_FADE_SECS;
+        assert_eq!(capture_popup_alpha(0.0), 1.0);
+        assert_eq!(capture_popup_alpha(fade_start), 1.0);
+        let halfway = capture_popup_alpha(fade_start + CAPTURE_POPUP_FADE_SECS / 2.0);
+        assert!((halfway - 0.5).abs() < 1e-6);
+        assert_eq!(capture_popup_alpha(CAPTURE_POPUP_SECS), 0.0);
+        assert_eq!(capture_popup_alpha(10.0), 0.0);
     }
 }